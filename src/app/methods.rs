@@ -1,9 +1,29 @@
-use std::{fs, thread};
+use std::{fs, thread, time::Instant};
 
+use chrono::Local;
 use eframe::egui;
 
-use super::{App, CloseFileAction, ConcurrentMessage};
-use crate::{export::export_html, file_dialog, print_info, File, KEY};
+use super::render::plurals;
+use super::{
+    App, BackupEntry, BankImport, CloseFileAction, ConcurrentMessage, ExportFormat, ExternalChange,
+    Import, LockedFile, PasswordAction, RecoverFile, RulesPreview, Undo, AUTOSAVE_INTERVAL,
+    LOCK_REFRESH_INTERVAL,
+};
+use crate::{
+    config,
+    csv::{Csv, CsvRow},
+    export::{self, export_html, export_pdf, Subtotals, Template},
+    file::{self, BackupSettings, FileError, LockMode, LockOwner, Snapshot, SnapshotLock},
+    file_dialog,
+    import::{
+        self,
+        bank::{self, Profile},
+        Transaction,
+    },
+    print_info, qif,
+    rules::{self, Rule, RuleSet},
+    File,
+};
 
 impl App {
     // * Error messages
@@ -40,37 +60,78 @@ impl App {
         let path_option = self.file.path().cloned();
 
         if let Some(path) = path_option {
-            // File exists (registered)
-            self.file_save_existing(&path, ctx);
+            if self.file.is_read_only() {
+                // File is open in another program instance
+                // Save a copy instead
+                self.file_save_as();
+            } else if self.file.password().is_some() {
+                // File exists (registered)
+                self.file_save_existing(&path, ctx);
+            } else {
+                // File has no password (legacy file)
+                // Ask for new password, then save
+                self.request_password(PasswordAction::SaveFile(path));
+            }
         } else {
             // File is unregistered on system
             // Save as
-            self.file_save_as(ctx);
+            self.file_save_as();
         }
     }
 
     /// Save file as
     ///
-    /// Shows *save file* dialog
-    pub fn file_save_as(&mut self, ctx: &egui::Context) {
+    /// Shows *save file* dialog, then asks for new password
+    pub fn file_save_as(&mut self) {
         print_info!("Save as");
 
         if let Some(path) = file_dialog::mgx()
             .save_file()
             .map(|path_buf| path_buf.display().to_string())
         {
-            self.file.set_path(&path);
+            // Do not save over file which is open in another program instance
+            if Some(&path) != self.file.path() {
+                match File::path_lock_owner(&path) {
+                    Ok(None) => (),
+                    Ok(Some(owner)) => {
+                        self.set_error_message(FileError::Locked(owner).to_string());
+                        return;
+                    }
+                    Err(error) => {
+                        self.set_error_message(error.to_string());
+                        return;
+                    }
+                }
+            }
 
-            self.file_save_existing(&path, ctx);
+            self.request_password(PasswordAction::SaveFile(path));
         };
     }
 
     /// Save existing file in new thread (concurrent / synchronous)
     ///
-    /// Should not be ran, unless file is already registered
+    /// Should not be ran, unless file is already registered, and has a password
     pub fn file_save_existing(&mut self, path: &str, ctx: &egui::Context) {
         print_info!("Save existing");
 
+        // File on disk was changed by another program, since it was opened or saved
+        // Ask to overwrite, reload, or save as
+        let changed_on_disk = match self.file.is_changed_on_disk() {
+            Ok(changed) => changed,
+            Err(error) => {
+                self.set_error_message(error.to_string());
+                return;
+            }
+        };
+        if !self
+            .attempting_external_save
+            .check_condition(!changed_on_disk)
+        {
+            self.request_external_save(path.to_string(), ctx);
+            return;
+        }
+        self.attempting_external_save.reset_attempt();
+
         // Set as writing
         *self.writing.lock().unwrap() = true;
         // Request to draw a new frame to update writing status
@@ -83,6 +144,13 @@ impl App {
         // This is why a message needs to be sent to the main thread to update save status
         let mut file = self.file.clone();
 
+        // File should always have password here, but do not save without one
+        let Some(password) = file.password().cloned() else {
+            self.set_error_message("Cannot save file without a password");
+            *self.writing.lock().unwrap() = false;
+            return;
+        };
+
         // path (type String), and ctx (type Context) can be cloned with no troubles
         let path = path.to_owned();
         let ctx = ctx.clone();
@@ -92,18 +160,33 @@ impl App {
         let sender = self.channel.sender.clone();
         let concurrent_write = self.writing.clone();
         let error_message = self.error_message.clone();
+        let backup_count = self.backup_settings().count;
 
         // Create a new thread, moving values into closure
         thread::spawn(move || {
+            // Keep previous version of file, before it is replaced
+            // File is still saved if this fails
+            if let Err(error) = File::backup_path(&path, backup_count) {
+                *error_message.lock().unwrap() = Some(error.to_string());
+            }
+
             // Save file and Handle errors
             // This can be a slow process (especially in debug build), hence the concurrent thread
-            match file.save_to_path_encrypted(&path, KEY) {
+            match file.save_to_path_encrypted(&path, &password) {
                 // Successful save
                 Ok(()) => {
+                    // Oldest backups are only removed once new version is saved
+                    if let Err(error) = File::rotate_backups(&path, backup_count) {
+                        *error_message.lock().unwrap() = Some(error.to_string());
+                    }
+
                     // Send a message to main thread, to update value of save status
                     // This will be recieved on the next frame (requested above)
                     sender
-                        .send(ConcurrentMessage::FinishConcurrentSave)
+                        .send(ConcurrentMessage::FinishConcurrentSave(
+                            file.header().clone(),
+                            file.disk_state().cloned(),
+                        ))
                         .expect("Send message")
                 }
 
@@ -151,114 +234,1734 @@ impl App {
                 return;
             }
 
-            // This can be a slow process (especially in debug build), but should not use concurrent thread,
-            //      as no user actions can be performed until file loads anyway
-            match File::open_path_and_decrypt(path, KEY) {
-                // Successful read
+            // File is open in another program instance
+            // Ask to open read-only or anyway
+            match File::path_lock_owner(&path) {
+                Ok(None) => self.file_open_path(path, LockMode::Lock),
+                Ok(Some(owner)) => self.request_locked_open(path, owner),
+                Err(error) => self.set_error_message(error.to_string()),
+            }
+        };
+    }
+
+    /// Open file at path, asking for password if needed
+    fn file_open_path(&mut self, path: String, mode: LockMode) {
+        match File::path_uses_legacy_key(&path) {
+            // Old file without password
+            // Open with built-in key, and ask for a new password on next save
+            Ok(true) => match File::open_path_with_mode(&path, None, mode) {
                 Ok(file) => {
-                    self.file = file;
+                    print_info!("Opened legacy file");
+                    self.set_file(file);
                 }
+                Err(error) => self.file_open_failed(path, None, mode, error),
+            },
 
-                // An error occurred
-                // Display a readable  error on UI
-                Err(error) => self.set_error_message(error.to_string()),
-            }
+            // Ask for password, then open
+            Ok(false) => self.request_password(PasswordAction::OpenFile(path, mode)),
+
+            // An error occurred
+            // Display a readable  error on UI
+            Err(error) => self.set_error_message(error.to_string()),
+        }
+    }
+
+    // * Files changed by another program
+
+    /// Ask whether to overwrite file which was changed by another program
+    ///
+    /// Compares rows of file on disk with current rows, in another thread
+    fn request_external_save(&mut self, path: String, ctx: &egui::Context) {
+        // Clone values to move to new thread
+        let rows = self.file.contents().rows.clone();
+        let password = self.file.password().cloned();
+        let disk_path = path.clone();
+        let sender = self.channel.sender.clone();
+        let ctx = ctx.clone();
+
+        // Decrypting can be a slow process (especially in debug build), like saving
+        thread::spawn(move || {
+            let difference =
+                File::open_path_with_mode(&disk_path, password.as_deref(), LockMode::Ignore)
+                    .ok()
+                    .map(|disk| {
+                        let disk_rows = &disk.contents().rows;
+                        (
+                            disk_rows.iter().filter(|row| !rows.contains(row)).count(),
+                            rows.iter().filter(|row| !disk_rows.contains(row)).count(),
+                        )
+                    });
+
+            sender
+                .send(ConcurrentMessage::CompareExternalChange(
+                    disk_path, difference,
+                ))
+                .expect("Send message");
+            ctx.request_repaint();
+        });
+
+        self.attempting_external_save.set_action(ExternalChange {
+            path,
+            difference: None,
+            comparing: true,
+        });
+        self.focus_new_element_on_next_frame = true;
+    }
+
+    /// Show difference of file on disk, once it is compared in another thread
+    pub fn finish_external_compare(&mut self, path: String, difference: Option<(usize, usize)>) {
+        // Dialog may be closed, or for another file
+        let Some(change) = self.attempting_external_save.action_mut() else {
+            return;
         };
+        if change.path == path {
+            change.difference = difference;
+            change.comparing = false;
+        }
     }
 
-    // * New file
+    /// Get message for file which was changed by another program
+    pub fn external_change_message(&self) -> Option<String> {
+        let ExternalChange {
+            path,
+            difference,
+            comparing,
+        } = self.attempting_external_save.action().as_ref()?;
 
-    /// Create new file
+        let mut message = format!("{path} was changed by another program since it was opened.");
+        if *comparing {
+            message += "\nComparing with the file on disk…";
+        } else if let Some((only_on_disk, only_here)) = difference {
+            message += &format!(
+                "\n{only_on_disk} row{} only in the file on disk, {only_here} row{} only here.",
+                plurals(*only_on_disk),
+                plurals(*only_here),
+            );
+        }
+        Some(message)
+    }
+
+    /// Save over file which was changed by another program
+    pub fn overwrite_external_change(&mut self, ctx: &egui::Context) {
+        let Some(ExternalChange { path, .. }) = self.attempting_external_save.action() else {
+            return;
+        };
+        let path = path.clone();
+        self.attempting_external_save.override_condition();
+        self.file_save_existing(&path, ctx);
+    }
+
+    /// Discard unsaved changes, and read file which was changed by another program
+    pub fn reload_external_change(&mut self) {
+        self.attempting_external_save.reset_attempt();
+        if let Err(error) = self.file.reload() {
+            self.set_error_message(error.to_string());
+        }
+    }
+
+    /// Save to another path, instead of file which was changed by another program
+    pub fn save_as_external_change(&mut self) {
+        self.attempting_external_save.reset_attempt();
+        self.file_save_as();
+    }
+
+    /// Stop saving file which was changed by another program
+    pub fn cancel_external_change(&mut self) {
+        self.attempting_external_save.reset_attempt();
+    }
+
+    // * Locked files
+
+    /// Ask how to open file which is open in another program instance
+    fn request_locked_open(&mut self, path: String, owner: LockOwner) {
+        self.attempting_locked_open
+            .set_action(LockedFile { path, owner });
+        self.focus_new_element_on_next_frame = true;
+    }
+
+    /// Get message for file which is open in another program instance
+    pub fn locked_file_message(&self) -> Option<String> {
+        let LockedFile { path, owner } = self.attempting_locked_open.action().as_ref()?;
+        Some(format!(
+            "{path} is open in another MagicTax window (last active {}). Changes saved in one window may overwrite the other.",
+            owner.time.with_timezone(&Local).format("%H:%M"),
+        ))
+    }
+
+    /// Open file which is open in another program instance, read-only or ignoring lock
+    pub fn open_locked_file(&mut self, mode: LockMode) {
+        let Some(LockedFile { path, .. }) = self.attempting_locked_open.action() else {
+            return;
+        };
+        let path = path.clone();
+        self.attempting_locked_open.reset_attempt();
+        self.file_open_path(path, mode);
+    }
+
+    /// Stop opening file which is open in another program instance
+    pub fn cancel_locked_open(&mut self) {
+        self.attempting_locked_open.reset_attempt();
+    }
+
+    /// Refresh lock of current file, if enough time has passed, so it does not become stale
+    pub fn refresh_lock(&mut self, ctx: &egui::Context) {
+        if let Some(last_time) = self.lock_refreshed {
+            let elapsed = last_time.elapsed();
+            if elapsed < LOCK_REFRESH_INTERVAL {
+                // Run again when interval has passed, even without user interaction
+                ctx.request_repaint_after(LOCK_REFRESH_INTERVAL - elapsed);
+                return;
+            }
+        }
+        self.lock_refreshed = Some(Instant::now());
+        ctx.request_repaint_after(LOCK_REFRESH_INTERVAL);
+
+        if let Err(error) = self.file.refresh_lock() {
+            eprintln!("[ERROR] Failed to refresh lock: {error}");
+        }
+        if let Some(lock) = &self.autosave.lock {
+            if let Err(error) = lock.refresh() {
+                eprintln!("[ERROR] Failed to refresh lock of snapshot: {error}");
+            }
+        }
+    }
+
+    /// Replace current file, removing any quarantined rows and snapshot of previous file
+    fn set_file(&mut self, file: File) {
+        self.file = file;
+        self.undo = None;
+        self.quarantine.clear();
+        self.remove_own_snapshot();
+    }
+
+    /// Show error from opening file
     ///
-    /// Attempts to close current file (See `self.attempt_file_close`)
+    /// If some rows could not be parsed, offer to open file without them
     ///
-    /// Sets current file to empty and unregistered (default)
-    pub fn file_new(&mut self) {
-        print_info!("? New file");
+    /// If file was locked after it was checked, ask how to open it
+    fn file_open_failed(
+        &mut self,
+        path: String,
+        password: Option<String>,
+        mode: LockMode,
+        error: FileError,
+    ) {
+        match error {
+            FileError::CsvParse(error) => {
+                self.attempting_recovery.set_action(RecoverFile {
+                    path,
+                    password,
+                    mode,
+                    error: error.to_string(),
+                });
+                self.focus_new_element_on_next_frame = true;
+            }
+            FileError::Locked(owner) => self.request_locked_open(path, owner),
+            error => self.set_error_message(error.to_string()),
+        }
+    }
 
-        if !self.file_can_close() {
-            self.attempting_file_close
-                .set_action(CloseFileAction::NewFile);
-            self.focus_new_element_on_next_frame = true;
+    // * Recover bad rows
+
+    /// Get error of file which is being recovered
+    pub fn recovery_error(&self) -> Option<&str> {
+        self.attempting_recovery
+            .action()
+            .as_ref()
+            .map(|recover| recover.error.as_str())
+    }
+
+    /// Open file which is being recovered, moving bad rows to quarantine
+    pub fn recover_file(&mut self) {
+        let Some(RecoverFile {
+            path,
+            password,
+            mode,
+            ..
+        }) = self.attempting_recovery.action()
+        else {
             return;
+        };
+
+        match File::open_path_and_recover(path, password.as_deref(), *mode) {
+            Ok((file, bad_rows)) => {
+                print_info!("Recovered file, with {} bad rows", bad_rows.len());
+                self.set_file(file);
+                self.quarantine = bad_rows;
+            }
+            Err(error) => self.set_error_message(error.to_string()),
         }
 
-        println!("New file");
+        self.attempting_recovery.reset_attempt();
+    }
 
-        self.file = File::default();
+    /// Stop recovering file, without opening it
+    pub fn cancel_recovery(&mut self) {
+        self.attempting_recovery.reset_attempt();
     }
 
-    /// Export data to html
+    /// Parse quarantined row again, and add it to file if valid
     ///
-    /// Shows *save file* dialog
-    pub fn file_export_html(&mut self) {
-        print_info!("Export as html");
+    /// Error of row is updated if still not valid
+    pub fn retry_bad_row(&mut self, index: usize) {
+        let Some(bad_row) = self.quarantine.get_mut(index) else {
+            return;
+        };
 
-        if let Some(path) = file_dialog::html()
-            .save_file()
-            .map(|path_buf| path_buf.display().to_string())
-        {
-            // println!("{}", path);
-            // println!("{:#?}", self.file.contents());
+        match bad_row.parse() {
+            Ok(row) => {
+                self.quarantine.remove(index);
+                self.file.contents_mut().rows.push(row);
+                self.file.mark_as_unsaved();
+            }
+            Err(error) => bad_row.error = error,
+        }
+    }
 
-            // Try to convert to html
-            let html = match export_html(self.file.contents()) {
-                Ok(html) => html,
-                Err(error) => {
-                    eprintln!("{:?}", error);
-                    self.set_error_message("Failed to convert data to html");
-                    return;
+    /// Remove quarantined row, without adding it to file
+    pub fn discard_bad_row(&mut self, index: usize) {
+        if index < self.quarantine.len() {
+            self.quarantine.remove(index);
+        }
+    }
+
+    // * Autosave
+
+    /// Take snapshot of unsaved changes in new thread, if enough time has passed since last snapshot
+    ///
+    /// Files without a password are encrypted with a random key of this program instance
+    ///
+    /// Also finds snapshots left by a crash, on first run
+    pub fn autosave(&mut self, ctx: &egui::Context) {
+        let Some(folder) = self.autosave.folder.clone() else {
+            return;
+        };
+
+        // Find snapshots left by a crash, once when program starts
+        if self.autosave.leftovers.is_none() {
+            match SnapshotLock::acquire(&folder, &self.autosave.session) {
+                Ok(lock) => self.autosave.lock = Some(lock),
+                Err(error) => eprintln!("[ERROR] Failed to lock snapshot: {error}"),
+            }
+
+            let leftovers =
+                File::list_snapshots(&folder, &self.autosave.session).unwrap_or_else(|error| {
+                    eprintln!("[ERROR] Failed to find snapshots: {error}");
+                    Vec::new()
+                });
+            if !leftovers.is_empty() {
+                print_info!("Found {} snapshots", leftovers.len());
+                self.focus_new_element_on_next_frame = true;
+            }
+            self.autosave.leftovers = Some(leftovers);
+        }
+
+        if !self.file.is_changed() {
+            return;
+        }
+
+        // Wait until interval has passed
+        if let Some(last_time) = self.autosave.last_time {
+            let elapsed = last_time.elapsed();
+            if elapsed < AUTOSAVE_INTERVAL {
+                // Run again when interval has passed, even without user interaction
+                ctx.request_repaint_after(AUTOSAVE_INTERVAL - elapsed);
+                return;
+            }
+        }
+        self.autosave.last_time = Some(Instant::now());
+        ctx.request_repaint_after(AUTOSAVE_INTERVAL);
+
+        // Nothing changed since last snapshot
+        if self.autosave.last_contents.as_ref() == Some(self.file.contents()) {
+            return;
+        }
+        self.autosave.last_contents = Some(self.file.contents().clone());
+
+        print_info!("Autosave");
+
+        // Clone values to move to new thread
+        let file = self.file.clone();
+        let session = self.autosave.session.clone();
+        let key = self.autosave.key.clone();
+        let removals = self.autosave.removals.clone();
+        let removals_before = *removals.lock().unwrap();
+
+        // Encrypting can be a slow process (especially in debug build), like saving
+        thread::spawn(move || {
+            // Failed snapshot should not interrupt user, as file can still be saved
+            let result = file.encrypt_snapshot(&key).and_then(|data| {
+                let removals = removals.lock().unwrap();
+                // Snapshot was removed while encrypting, such as if file was saved
+                if *removals != removals_before {
+                    return Ok(());
                 }
-            };
+                file.write_snapshot(&folder, &session, &data, &key)
+            });
+            if let Err(error) = result {
+                eprintln!("[ERROR] Failed to autosave: {error}");
+            }
+        });
+    }
 
-            // Write to file or show error
-            if let Err(error) = fs::write(path, html) {
-                self.set_error_message(error.to_string());
+    /// Delete snapshot of this program instance, when unsaved changes are saved or discarded
+    pub fn remove_own_snapshot(&mut self) {
+        self.autosave.last_time = None;
+        self.autosave.last_contents = None;
+
+        // Snapshot being written in another thread waits, then is skipped
+        let mut removals = self.autosave.removals.lock().unwrap();
+        *removals += 1;
+
+        if let Some(folder) = &self.autosave.folder {
+            if let Err(error) = File::remove_snapshot(folder, &self.autosave.session) {
+                eprintln!("[ERROR] Failed to remove snapshot: {error}");
             }
+        }
+    }
+
+    /// Get newest snapshot left by a crash, which was not restored or discarded yet
+    pub fn leftover_snapshot(&self) -> Option<&Snapshot> {
+        self.autosave.leftovers.as_ref()?.first()
+    }
+
+    /// Restore unsaved changes from snapshot left by a crash
+    ///
+    /// Asks for password, unless file had no password
+    ///
+    /// Attempts to close current file (See `self.attempt_file_close`)
+    pub fn restore_leftover_snapshot(&mut self) {
+        if !self.file_can_close() {
+            self.set_error_message("Save or close current file before restoring unsaved changes");
+            return;
+        }
+
+        let Some(snapshot) = self.leftover_snapshot().cloned() else {
+            return;
         };
+        if snapshot.key.is_none() {
+            self.request_password(PasswordAction::RestoreSnapshot(snapshot));
+            return;
+        }
+        if let Err(error) = self.restore_snapshot(&snapshot, None) {
+            self.set_error_message(error.to_string());
+        }
     }
 
-    // * Handle file close
+    /// Replace current file with snapshot, and delete snapshot
+    ///
+    /// Restored changes are autosaved again, as a snapshot of this instance
+    fn restore_snapshot(
+        &mut self,
+        snapshot: &Snapshot,
+        password: Option<&str>,
+    ) -> Result<(), FileError> {
+        let file = File::open_snapshot(snapshot, password)?;
+        print_info!("Restored snapshot");
+        self.set_file(file);
 
-    /// Returns `true` if file is not changed, or condition is overridden
-    pub fn file_can_close(&self) -> bool {
-        self.attempting_file_close
-            .check_condition(!self.file.is_changed())
+        if let Some(leftovers) = &mut self.autosave.leftovers {
+            leftovers.retain(|other| other != snapshot);
+        }
+        if let Err(error) = snapshot.remove() {
+            eprintln!("[ERROR] Failed to remove snapshot: {error}");
+        }
+        Ok(())
     }
 
-    /// Run close action if allowed
-    pub fn call_close_action(&mut self) {
-        // Cancel if file is not allowed to close (unsaved file)
-        if !self.file_can_close() {
+    /// Delete snapshot left by a crash, without restoring it
+    pub fn discard_leftover_snapshot(&mut self) {
+        let Some(leftovers) = &mut self.autosave.leftovers else {
+            return;
+        };
+        if leftovers.is_empty() {
             return;
         }
 
-        // If action was registered
-        if let Some(action) = &self.attempting_file_close.action() {
-            match action {
-                // These 2 actions were registered by methods in this file,
-                //      and call themselves again
-                // They must reset the close action, or it will loop
-                CloseFileAction::OpenFile => {
-                    self.file_open();
-                    self.reset_close_action();
-                }
-                CloseFileAction::NewFile => {
-                    self.file_new();
-                    self.reset_close_action();
+        let snapshot = leftovers.remove(0);
+        print_info!("Discard snapshot");
+        if let Err(error) = snapshot.remove() {
+            self.set_error_message(format!("Failed to delete unsaved changes: {error}"));
+        }
+        self.focus_new_element_on_next_frame = true;
+    }
+
+    // * Backups
+
+    /// Get backup settings of user, reading them from config folder if not read yet
+    fn backup_settings(&mut self) -> BackupSettings {
+        if let Some(settings) = self.backups.settings {
+            return settings;
+        }
+
+        let settings =
+            match config::path(file::BACKUP_SETTINGS_FILE).map(|path| config::load(&path)) {
+                Some(Ok(settings)) => settings,
+                Some(Err(error)) => {
+                    self.set_error_message(format!("Failed to read backup settings: {error}"));
+                    Default::default()
                 }
+                None => Default::default(),
+            };
+        self.backups.settings = Some(settings);
+        settings
+    }
 
-                // This action was registered by the `on_close_event` method
-                // This cannot call `reset_close_action,
-                //      as window will not close on next frame
-                // This will not loop, as program will close before that
-                CloseFileAction::CloseWindow => self.close_window_on_next_frame = true,
+    /// Save backup settings of user to config folder, if they were changed
+    pub fn save_backup_settings(&mut self) {
+        let Some(settings) = &self.backups.settings else {
+            return;
+        };
+        if !self.backups.changed {
+            return;
+        }
+        let Some(path) = config::path(file::BACKUP_SETTINGS_FILE) else {
+            self.set_error_message("Failed to save backup settings: Config folder is unknown");
+            return;
+        };
+
+        match config::save(&path, settings) {
+            Ok(()) => self.backups.changed = false,
+            Err(error) => {
+                self.set_error_message(format!("Failed to save backup settings: {error}"))
             }
         }
     }
 
-    /// Reset close action
-    pub fn reset_close_action(&mut self) {
-        self.attempting_file_close.reset_attempt();
+    /// Show *restore from backup* dialog
+    ///
+    /// Each backup is opened with password of current file
+    pub fn file_show_backups(&mut self, ctx: &egui::Context) {
+        print_info!("Show backups");
+        self.backup_settings();
+
+        let Some(path) = self.file.path() else {
+            return;
+        };
+
+        let backups = match File::list_backups(path) {
+            Ok(backups) => backups,
+            Err(error) => {
+                self.set_error_message(error.to_string());
+                return;
+            }
+        };
+
+        // Clone values to move to new thread
+        let opening = backups.clone();
+        let password = self.file.password().cloned();
+        let sender = self.channel.sender.clone();
+        let ctx = ctx.clone();
+
+        // Decrypting can be slow with many backups, so they are opened in another thread
+        thread::spawn(move || {
+            for backup in opening {
+                let contents = File::open_backup(&backup, password.as_deref())
+                    .map(|file| file.contents().clone())
+                    .map_err(|error| error.to_string());
+
+                sender
+                    .send(ConcurrentMessage::OpenBackup(backup.path, contents))
+                    .expect("Send message");
+                ctx.request_repaint();
+            }
+        });
+
+        let entries = backups
+            .into_iter()
+            .map(|backup| BackupEntry {
+                backup,
+                contents: None,
+            })
+            .collect();
+
+        self.backups.entries = Some(entries);
+        self.focus_new_element_on_next_frame = true;
+    }
+
+    /// Show contents of backup, once it is opened in another thread
+    pub fn finish_open_backup(&mut self, path: String, contents: Result<Csv, String>) {
+        // Dialog may be closed
+        let Some(entries) = &mut self.backups.entries else {
+            return;
+        };
+        if let Some(entry) = entries.iter_mut().find(|entry| entry.backup.path == path) {
+            entry.contents = Some(contents);
+        }
     }
+
+    /// Replace contents of current file with backup
+    ///
+    /// File is not saved until user saves it
+    pub fn restore_backup(&mut self, index: usize) {
+        let Some(Ok(contents)) = self
+            .backups
+            .entries
+            .as_ref()
+            .and_then(|entries| entries.get(index))
+            .and_then(|entry| entry.contents.clone())
+        else {
+            return;
+        };
+
+        print_info!("Restore backup");
+        *self.file.contents_mut() = contents;
+        self.file.mark_as_unsaved();
+        self.close_backups();
+    }
+
+    /// Close *restore from backup* dialog
+    ///
+    /// Backup settings are saved, if they are still being edited
+    pub fn close_backups(&mut self) {
+        self.backups.entries = None;
+        self.save_backup_settings();
+    }
+
+    // * Passwords
+
+    /// Show password dialog, and run action when password is entered
+    fn request_password(&mut self, action: PasswordAction) {
+        self.password_input.clear();
+        self.password_confirm_input.clear();
+        self.attempting_password.set_action(action);
+        self.focus_new_element_on_next_frame = true;
+    }
+
+    /// Returns `true` if password dialog is asking for a new password, which must be confirmed
+    pub fn password_needs_confirmation(&self) -> bool {
+        matches!(
+            self.attempting_password.action(),
+            Some(PasswordAction::SaveFile(_) | PasswordAction::ChangePasswordNew(_))
+        )
+    }
+
+    /// Get title of password dialog, depending on action
+    pub fn password_dialog_title(&self) -> &'static str {
+        match self.attempting_password.action() {
+            Some(PasswordAction::ChangePasswordOld) => "Enter current password",
+            Some(PasswordAction::ChangePasswordNew(_)) => "Choose a new password",
+            _ if self.password_needs_confirmation() => "Choose a password",
+            _ => "Enter password",
+        }
+    }
+
+    /// Change password of current file
+    ///
+    /// Asks for current password, then new password
+    ///
+    /// Files without a password (legacy files) only ask for new password
+    pub fn file_change_password(&mut self) {
+        print_info!("Change password");
+
+        if self.file.password().is_some() {
+            self.request_password(PasswordAction::ChangePasswordOld);
+        } else {
+            self.request_password(PasswordAction::ChangePasswordNew(None));
+        }
+    }
+
+    /// Run password action with entered password
+    ///
+    /// New passwords must match `confirmation`, otherwise it is ignored
+    ///
+    /// Password dialog stays open if password is not accepted
+    pub fn submit_password(&mut self, password: String, confirmation: String, ctx: &egui::Context) {
+        let Some(action) = self.attempting_password.action().clone() else {
+            return;
+        };
+
+        match action {
+            PasswordAction::OpenFile(path, mode) => {
+                // This can be a slow process (especially in debug build), but should not use concurrent thread,
+                //      as no user actions can be performed until file loads anyway
+                match File::open_path_with_mode(&path, Some(&password), mode) {
+                    // Successful read
+                    Ok(file) => {
+                        self.set_file(file);
+                    }
+
+                    // Password was correct, but some rows are not valid
+                    // Offer to open file without them
+                    // Or file was opened in another program instance, since it was checked
+                    Err(error @ (FileError::CsvParse(_) | FileError::Locked(_))) => {
+                        self.file_open_failed(path, Some(password), mode, error);
+                    }
+
+                    // An error occurred
+                    // Display a readable  error on UI, and allow password to be entered again
+                    Err(error) => {
+                        self.set_error_message(error.to_string());
+                        return;
+                    }
+                }
+            }
+
+            PasswordAction::MergeFile(path) => {
+                match File::open_path_with_mode(&path, Some(&password), LockMode::Ignore) {
+                    Ok(other) => self.merge_file(&path, other),
+
+                    // Allow password to be entered again
+                    Err(error) => {
+                        self.set_error_message(error.to_string());
+                        return;
+                    }
+                }
+            }
+
+            PasswordAction::SaveFile(path) => {
+                if password.is_empty() {
+                    self.set_error_message("Password cannot be empty");
+                    return;
+                }
+                if password != confirmation {
+                    self.set_error_message("Passwords do not match");
+                    return;
+                }
+
+                // Allow another path to be chosen
+                if let Err(error) = self.file.set_path(&path) {
+                    self.set_error_message(error.to_string());
+                    self.cancel_password();
+                    return;
+                }
+                self.file.set_password(password);
+
+                self.file_save_existing(&path, ctx);
+            }
+
+            PasswordAction::RestoreSnapshot(snapshot) => {
+                // Allow password to be entered again
+                if let Err(error) = self.restore_snapshot(&snapshot, Some(&password)) {
+                    self.set_error_message(error.to_string());
+                    return;
+                }
+            }
+
+            PasswordAction::ChangePasswordOld => {
+                if self.file.password() != Some(&password) {
+                    self.set_error_message("Incorrect password for file");
+                    return;
+                }
+
+                // Ask for new password
+                self.request_password(PasswordAction::ChangePasswordNew(Some(password)));
+                return;
+            }
+
+            PasswordAction::ChangePasswordNew(old_password) => {
+                if password.is_empty() {
+                    self.set_error_message("Password cannot be empty");
+                    return;
+                }
+                if password != confirmation {
+                    self.set_error_message("Passwords do not match");
+                    return;
+                }
+
+                let backup_count = self.backup_settings().count;
+                if let Err(error) =
+                    self.file
+                        .change_password(old_password.as_deref(), &password, backup_count)
+                {
+                    self.set_error_message(error.to_string());
+                } else {
+                    print_info!("Password changed");
+                }
+            }
+        }
+
+        self.cancel_password();
+    }
+
+    /// Close password dialog, without running action
+    pub fn cancel_password(&mut self) {
+        self.password_input.clear();
+        self.password_confirm_input.clear();
+        self.attempting_password.reset_attempt();
+    }
+
+    // * New file
+
+    /// Create new file
+    ///
+    /// Attempts to close current file (See `self.attempt_file_close`)
+    ///
+    /// Sets current file to empty and unregistered (default)
+    pub fn file_new(&mut self) {
+        print_info!("? New file");
+
+        if !self.file_can_close() {
+            self.attempting_file_close
+                .set_action(CloseFileAction::NewFile);
+            self.focus_new_element_on_next_frame = true;
+            return;
+        }
+
+        println!("New file");
+
+        self.set_file(File::default());
+    }
+
+    /// Export data to html
+    ///
+    /// Shows *save file* dialog
+    pub fn file_export_html(&mut self) {
+        print_info!("Export as html");
+
+        // Check template before choosing where to save
+        self.load_report_settings();
+        let settings = self.report.settings.clone().unwrap_or_default();
+        let template = match Template::read(&settings) {
+            Ok(template) => template,
+            Err(error) => {
+                self.set_error_message(format!("Failed to use report template: {error}"));
+                return;
+            }
+        };
+
+        if let Some(path) = file_dialog::html()
+            .save_file()
+            .map(|path_buf| path_buf.display().to_string())
+        {
+            // println!("{}", path);
+            // println!("{:#?}", self.file.contents());
+
+            // Try to convert to html
+            let file_name = self
+                .file
+                .path()
+                .map(|path| file_name(std::path::Path::new(path)));
+            let html = match export_html(
+                self.file.contents(),
+                file_name.as_deref(),
+                &template,
+                settings.subtotals,
+            ) {
+                Ok(html) => html,
+                Err(error) => {
+                    eprintln!("{:?}", error);
+                    self.set_error_message(format!("Failed to convert data to html: {error}"));
+                    return;
+                }
+            };
+
+            // Write to file or show error
+            if let Err(error) = fs::write(path, html) {
+                self.set_error_message(error.to_string());
+            }
+        };
+    }
+
+    /// Export data to pdf
+    ///
+    /// Shows *save file* dialog
+    pub fn file_export_pdf(&mut self) {
+        print_info!("Export as pdf");
+
+        let Some(path) = file_dialog::pdf().save_file() else {
+            return;
+        };
+
+        self.load_report_settings();
+        let settings = self.report.settings.clone().unwrap_or_default();
+        let pdf = export_pdf(self.file.contents(), settings.subtotals);
+        if let Err(error) = fs::write(path, pdf) {
+            self.set_error_message(error.to_string());
+        }
+    }
+
+    // * Import and export csv
+
+    /// Import rows from plaintext csv file
+    ///
+    /// Shows *open file* dialog, then asks whether to add rows to current file, or a new file
+    pub fn file_import_csv(&mut self) {
+        print_info!("Import csv");
+
+        let Some(path) = file_dialog::csv().pick_file() else {
+            return;
+        };
+        let source = file_name(&path);
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) => {
+                self.set_error_message(format!("Failed to read {source}: {error}"));
+                return;
+            }
+        };
+
+        match Csv::decode(&text) {
+            Ok(csv) => self.request_import(source, csv.rows),
+            Err(error) => self.set_error_message(format!("Failed to import {source}: {error}")),
+        }
+    }
+
+    /// Import transactions from bank statement (OFX, QFX, camt.053, or MT940)
+    ///
+    /// Transactions which were already imported into current file are skipped
+    pub fn file_import_statement(&mut self) {
+        print_info!("Import statement");
+
+        let Some(path) = file_dialog::statement().pick_file() else {
+            return;
+        };
+        let source = file_name(&path);
+
+        // Older statements are often not UTF-8, such as OFX 1.x files with `CHARSET:1252`
+        let text = match fs::read(&path) {
+            Ok(bytes) => import::decode_text(&bytes),
+            Err(error) => {
+                self.set_error_message(format!("Failed to read {source}: {error}"));
+                return;
+            }
+        };
+
+        match import::parse_statement(&text) {
+            Ok(transactions) => self.request_import_transactions(source, transactions),
+            Err(error) => self.set_error_message(format!("Failed to import {source}: {error}")),
+        }
+    }
+
+    /// Ask whether to add transactions from bank statement to current file, or a new file
+    ///
+    /// Transactions which were already imported into current file are skipped
+    ///
+    /// Rows which are suspected duplicates of rows in current file are unchecked, to be reviewed
+    ///
+    /// Rules are applied first, so rows can be compared with rows which rules were already applied to
+    fn request_import_transactions(&mut self, source: String, transactions: Vec<Transaction>) {
+        let count = transactions.len();
+
+        // Kept aside, in case rows are imported as a new file instead
+        let imported = &self.file.header().imported_ids;
+        let (mut skipped, mut transactions): (Vec<_>, Vec<_>) =
+            transactions.into_iter().partition(|transaction| {
+                transaction
+                    .id
+                    .as_ref()
+                    .is_some_and(|id| imported.contains(id))
+            });
+        import::skip_imported(&mut transactions, &[]);
+
+        if count > 0 && skipped.len() == count {
+            self.set_error_message(format!(
+                "All {count} transaction{} in {source} were already imported",
+                plurals(count)
+            ));
+            return;
+        }
+        if transactions.is_empty() {
+            self.set_error_message(format!("No rows were found in {source}"));
+            return;
+        }
+
+        let (ids, mut rows): (Vec<_>, Vec<_>) = transactions
+            .into_iter()
+            .map(|transaction| (transaction.id, transaction.row))
+            .unzip();
+
+        // Rows are still imported if rules are not valid
+        let rules = self.all_rules();
+        let changed_by_rules = match RuleSet::new(&rules) {
+            Ok(rules) => {
+                for transaction in &mut skipped {
+                    if let Some(row) = rules.apply(&transaction.row) {
+                        transaction.row = row;
+                    }
+                }
+                rules.apply_all(&mut rows)
+            }
+            Err(error) => {
+                self.set_error_message(format!("Rules were not applied to import: {error}"));
+                0
+            }
+        };
+
+        self.review_import(Import {
+            source,
+            rows,
+            ids,
+            skipped,
+            changed_by_rules,
+            ..Import::default()
+        });
+    }
+
+    /// Ask whether to add imported rows, which have no IDs, to current file, or a new file
+    ///
+    /// See `request_import_transactions`
+    fn request_import(&mut self, source: String, rows: Vec<CsvRow>) {
+        let transactions = rows
+            .into_iter()
+            .map(|row| Transaction { id: None, row })
+            .collect();
+        self.request_import_transactions(source, transactions);
+    }
+
+    /// Show rows to import, with suspected duplicates of rows in current file unchecked
+    ///
+    /// Duplicates and checked rows of `import` are replaced
+    fn review_import(&mut self, mut import: Import) {
+        import.duplicates = self.file.contents().find_duplicates(&import.rows);
+        import.selected = import.duplicates.iter().map(Option::is_none).collect();
+
+        self.attempting_import.set_action(import);
+        self.focus_new_element_on_next_frame = true;
+    }
+
+    /// Get message for rows which are being imported
+    pub fn import_message(&self) -> Option<String> {
+        let import = self.attempting_import.action().as_ref()?;
+
+        let selected = import.selected.iter().filter(|selected| **selected).count();
+        let mut message = format!(
+            "{} {selected} of {} row{} from {}?",
+            if import.merge { "Merge" } else { "Import" },
+            import.rows.len(),
+            plurals(import.rows.len()),
+            import.source,
+        );
+
+        if import.changed_by_rules > 0 {
+            message += &format!(
+                "\n{} row{} changed by rules.",
+                import.changed_by_rules,
+                if import.changed_by_rules == 1 {
+                    " was"
+                } else {
+                    "s were"
+                },
+            );
+        }
+
+        if !import.skipped.is_empty() {
+            message += &format!(
+                "\n{} transaction{} already imported into this file, and skipped.",
+                import.skipped.len(),
+                if import.skipped.len() == 1 {
+                    " was"
+                } else {
+                    "s were"
+                },
+            );
+        }
+
+        let duplicates = import.duplicates.iter().flatten().count();
+        if duplicates > 0 {
+            message += &format!(
+                "\n{duplicates} row{} may already be in this file, and {} unchecked.",
+                plurals(duplicates),
+                if duplicates == 1 { "is" } else { "are" },
+            );
+        }
+        Some(message)
+    }
+
+    /// Add checked imported rows to end of current file
+    pub fn import_append(&mut self) {
+        let Some(import) = self.attempting_import.action() else {
+            return;
+        };
+        let (rows, ids) = (import.selected_rows(), import.selected_ids());
+        let description = if import.merge {
+            format!("merge of {}", import.source)
+        } else {
+            format!("import from {}", import.source)
+        };
+        self.attempting_import.reset_attempt();
+
+        print_info!("Import {} rows", rows.len());
+        let before = self.before_undoable_change();
+        self.file.contents_mut().rows.extend(rows);
+        let header = self.file.header_mut();
+        for id in ids {
+            if !header.imported_ids.contains(&id) {
+                header.imported_ids.push(id);
+            }
+        }
+        self.file.mark_as_unsaved();
+        self.finish_undoable_change(description, before);
+    }
+
+    /// Create new file with checked imported rows
+    ///
+    /// Attempts to close current file (See `self.attempt_file_close`)
+    pub fn import_as_new_file(&mut self) {
+        if !self.file_can_close() {
+            self.attempting_file_close
+                .set_action(CloseFileAction::ImportAsNewFile);
+            self.focus_new_element_on_next_frame = true;
+            return;
+        }
+
+        let Some(mut import) = self.attempting_import.action_mut().take() else {
+            return;
+        };
+        self.attempting_import.reset_attempt();
+
+        print_info!("Import {} rows as new file", import.rows.len());
+        self.set_file(File::default());
+
+        // Rows are reviewed again, as nothing is imported into new file yet, and it has no duplicates
+        for transaction in import.skipped.drain(..) {
+            import.rows.push(transaction.row);
+            import.ids.push(transaction.id);
+        }
+        self.review_import(import);
+    }
+
+    /// Stop importing rows
+    pub fn cancel_import(&mut self) {
+        self.attempting_import.reset_attempt();
+    }
+
+    /// Import rows from csv file exported by a bank
+    ///
+    /// Shows *open file* dialog, then import wizard to choose which columns to read
+    pub fn file_import_bank_csv(&mut self) {
+        print_info!("Import bank csv");
+
+        let Some(path) = file_dialog::bank_csv().pick_file() else {
+            return;
+        };
+        let source = file_name(&path);
+
+        let text = match fs::read(&path) {
+            Ok(bytes) => import::decode_text(&bytes),
+            Err(error) => {
+                self.set_error_message(format!("Failed to read {source}: {error}"));
+                return;
+            }
+        };
+
+        // Profiles are optional, so wizard still opens if they cannot be read
+        let profiles = match config::path(bank::PROFILES_FILE).map(|path| config::load(&path)) {
+            Some(Ok(profiles)) => profiles,
+            Some(Err(error)) => {
+                self.set_error_message(format!("Failed to read saved bank profiles: {error}"));
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+
+        let profile = Profile::guess(&text);
+        let mut import = BankImport {
+            source,
+            records: Ok(Vec::new()),
+            text,
+            profile,
+            profiles,
+        };
+        import.reread();
+        self.bank_import = Some(import);
+    }
+
+    /// Use saved profile for bank import
+    pub fn bank_import_use_profile(&mut self, index: usize) {
+        let Some(import) = &mut self.bank_import else {
+            return;
+        };
+        let Some(profile) = import.profiles.get(index) else {
+            return;
+        };
+
+        import.profile = profile.clone();
+        import.reread();
+    }
+
+    /// Save current mapping of bank import as profile, replacing any profile with the same name
+    pub fn bank_import_save_profile(&mut self) {
+        let Some(import) = &mut self.bank_import else {
+            return;
+        };
+
+        let profile = import.profile.clone();
+        match import
+            .profiles
+            .iter_mut()
+            .find(|other| other.name == profile.name)
+        {
+            Some(other) => *other = profile,
+            None => import.profiles.push(profile),
+        }
+
+        let profiles = import.profiles.clone();
+        self.write_bank_profiles(&profiles);
+    }
+
+    /// Delete saved profile with the same name as current mapping of bank import
+    pub fn bank_import_delete_profile(&mut self) {
+        let Some(import) = &mut self.bank_import else {
+            return;
+        };
+
+        let name = import.profile.name.clone();
+        import.profiles.retain(|other| other.name != name);
+
+        let profiles = import.profiles.clone();
+        self.write_bank_profiles(&profiles);
+    }
+
+    /// Save bank profiles to user config folder, or show error
+    fn write_bank_profiles(&mut self, profiles: &[Profile]) {
+        let Some(path) = config::path(bank::PROFILES_FILE) else {
+            self.set_error_message("Failed to save bank profile: Config folder is unknown");
+            return;
+        };
+        if let Err(error) = config::save(&path, profiles) {
+            self.set_error_message(format!("Failed to save bank profile: {error}"));
+        }
+    }
+
+    /// Convert rows of bank import with current mapping, then ask where to add them
+    ///
+    /// Rows which cannot be converted are skipped
+    pub fn finish_bank_import(&mut self) {
+        let Some(import) = self.bank_import.take() else {
+            return;
+        };
+        let records = import.records.as_deref().unwrap_or_default();
+
+        let rows: Vec<_> = records
+            .iter()
+            .filter_map(|record| import.profile.convert(&record.cells).ok())
+            .collect();
+
+        let skipped = records.len() - rows.len();
+        let source = if skipped > 0 {
+            format!(
+                "{} ({skipped} row{} skipped)",
+                import.source,
+                plurals(skipped)
+            )
+        } else {
+            import.source
+        };
+
+        self.request_import(source, rows);
+    }
+
+    /// Close bank import wizard
+    pub fn cancel_bank_import(&mut self) {
+        self.bank_import = None;
+    }
+
+    /// Import rows from QIF file
+    ///
+    /// Shows *open file* dialog, then asks whether to add rows to current file, or a new file
+    pub fn file_import_qif(&mut self) {
+        print_info!("Import qif");
+
+        let Some(path) = file_dialog::qif().pick_file() else {
+            return;
+        };
+        let source = file_name(&path);
+
+        // Older programs do not write UTF-8
+        let text = match fs::read(&path) {
+            Ok(bytes) => import::decode_text(&bytes),
+            Err(error) => {
+                self.set_error_message(format!("Failed to read {source}: {error}"));
+                return;
+            }
+        };
+
+        match qif::decode(&text) {
+            Ok(csv) => self.request_import(source, csv.rows),
+            Err(error) => self.set_error_message(format!("Failed to import {source}: {error}")),
+        }
+    }
+
+    /// Export rows to plaintext csv file
+    ///
+    /// Warns that file is not encrypted, before showing *save file* dialog
+    pub fn file_export_csv(&mut self) {
+        print_info!("Export as csv");
+
+        self.attempting_unencrypted_export
+            .set_action(ExportFormat::Csv);
+        self.focus_new_element_on_next_frame = true;
+    }
+
+    /// Export rows to plaintext QIF file
+    ///
+    /// Warns that file is not encrypted, before showing *save file* dialog
+    pub fn file_export_qif(&mut self) {
+        print_info!("Export as qif");
+
+        self.attempting_unencrypted_export
+            .set_action(ExportFormat::Qif);
+        self.focus_new_element_on_next_frame = true;
+    }
+
+    /// Get warning for unencrypted export
+    ///
+    /// `None` if not exporting
+    pub fn unencrypted_export_message(&self) -> Option<String> {
+        let format = match self.attempting_unencrypted_export.action().as_ref()? {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Qif => "QIF",
+        };
+        Some(format!("{format} files are not encrypted. Anyone who can access the exported file can read all of its data."))
+    }
+
+    /// Export rows to plaintext file, after warning was accepted
+    ///
+    /// Shows *save file* dialog
+    pub fn confirm_unencrypted_export(&mut self) {
+        let Some(format) = *self.attempting_unencrypted_export.action() else {
+            return;
+        };
+        self.attempting_unencrypted_export.reset_attempt();
+
+        let (dialog, text) = match format {
+            ExportFormat::Csv => (file_dialog::csv(), self.file.contents().encode()),
+            ExportFormat::Qif => (file_dialog::qif(), qif::encode(self.file.contents())),
+        };
+
+        if let Some(path) = dialog.save_file() {
+            // Write to file or show error
+            if let Err(error) = fs::write(path, text) {
+                self.set_error_message(error.to_string());
+            }
+        }
+    }
+
+    /// Stop exporting to plaintext file
+    pub fn cancel_unencrypted_export(&mut self) {
+        self.attempting_unencrypted_export.reset_attempt();
+    }
+
+    // * Rules
+
+    /// Read rules of user from config folder, if not read yet
+    fn load_user_rules(&mut self) {
+        if self.rules.user.is_some() {
+            return;
+        }
+
+        let rules = match config::path(rules::FILE).map(|path| config::load(&path)) {
+            Some(Ok(rules)) => rules,
+            Some(Err(error)) => {
+                self.set_error_message(format!("Failed to read saved rules: {error}"));
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+        self.rules.user = Some(rules);
+    }
+
+    /// Get rules of user, then rules of current file
+    ///
+    /// Rules are cloned, so rows of file can be changed while rules are used
+    fn all_rules(&mut self) -> Vec<Rule> {
+        self.load_user_rules();
+
+        let user = self.rules.user.iter().flatten();
+        user.chain(&self.file.header().rules).cloned().collect()
+    }
+
+    /// Open window to edit and apply rules
+    pub fn file_show_rules(&mut self) {
+        print_info!("Show rules");
+        self.load_user_rules();
+        self.rules.open = true;
+    }
+
+    /// Close rules window
+    ///
+    /// Unsaved changes to rules of user are kept until program is closed
+    pub fn close_rules(&mut self) {
+        self.rules.open = false;
+        self.rules.preview = None;
+    }
+
+    /// Save rules of user to config folder
+    pub fn save_user_rules(&mut self) {
+        let Some(user) = &self.rules.user else {
+            return;
+        };
+        let Some(path) = config::path(rules::FILE) else {
+            self.set_error_message("Failed to save rules: Config folder is unknown");
+            return;
+        };
+
+        match config::save(&path, user) {
+            Ok(()) => self.rules.changed = false,
+            Err(error) => self.set_error_message(format!("Failed to save rules: {error}")),
+        }
+    }
+
+    /// Take preview of rows of current file which would be changed by rules
+    ///
+    /// Preview is only made again if rules or rows changed since last preview
+    ///
+    /// Preview should be given back to `self.rules.preview` when done
+    pub(super) fn take_rules_preview(&mut self) -> RulesPreview {
+        let rules = self.all_rules();
+        let rows = &self.file.contents().rows;
+
+        if let Some(preview) = self.rules.preview.take() {
+            if preview.rules == rules && &preview.rows == rows {
+                return preview;
+            }
+        }
+
+        let changes = RuleSet::new(&rules)
+            .map(|rule_set| rule_set.preview(rows))
+            .map_err(|error| error.to_string());
+        RulesPreview {
+            rows: rows.clone(),
+            rules,
+            changes,
+        }
+    }
+
+    /// Apply rules to every row of current file
+    pub fn apply_rules(&mut self) {
+        let rules = self.all_rules();
+        let rules = match RuleSet::new(&rules) {
+            Ok(rules) => rules,
+            Err(error) => {
+                self.set_error_message(error.to_string());
+                return;
+            }
+        };
+
+        let before = self.before_undoable_change();
+        let count = rules.apply_all(&mut self.file.contents_mut().rows);
+        print_info!("Rules changed {count} rows");
+        if count > 0 {
+            self.file.mark_as_unsaved();
+            self.finish_undoable_change("rules".to_string(), before);
+        }
+    }
+
+    // * Report settings
+
+    /// Read report settings of user from config folder, if not read yet
+    fn load_report_settings(&mut self) {
+        if self.report.settings.is_some() {
+            return;
+        }
+
+        let settings = match config::path(export::SETTINGS_FILE).map(|path| config::load(&path)) {
+            Some(Ok(settings)) => settings,
+            Some(Err(error)) => {
+                self.set_error_message(format!("Failed to read report settings: {error}"));
+                Default::default()
+            }
+            None => Default::default(),
+        };
+        self.report.settings = Some(settings);
+    }
+
+    /// Open window to choose custom template and subtotals of report
+    pub fn file_show_report_settings(&mut self) {
+        print_info!("Show report settings");
+        self.load_report_settings();
+        self.report.open = true;
+    }
+
+    /// Close report settings window
+    ///
+    /// Report settings are saved, if they are still being edited
+    pub fn close_report_settings(&mut self) {
+        self.report.open = false;
+        self.save_report_settings();
+    }
+
+    /// Choose custom handlebars template of html report
+    ///
+    /// Shows *open file* dialog
+    pub fn choose_report_template(&mut self) {
+        if let Some(path) = file_dialog::handlebars().pick_file() {
+            self.change_report_settings(|settings| settings.template = Some(path));
+        }
+    }
+
+    /// Choose custom css style of html report
+    ///
+    /// Shows *open file* dialog
+    pub fn choose_report_style(&mut self) {
+        if let Some(path) = file_dialog::css().pick_file() {
+            self.change_report_settings(|settings| settings.style = Some(path));
+        }
+    }
+
+    /// Use built-in template and style of html report
+    ///
+    /// Subtotals are kept
+    pub fn reset_report_template(&mut self) {
+        self.change_report_settings(|settings| {
+            settings.template = None;
+            settings.style = None;
+        });
+    }
+
+    /// Change subtotal rows of report, while they are being edited
+    ///
+    /// Settings are saved with `save_report_settings` once editing is finished, not every frame while dragging
+    pub fn edit_report_subtotals(&mut self, subtotals: Subtotals) {
+        if let Some(settings) = &mut self.report.settings {
+            settings.subtotals = subtotals;
+            self.report.changed = true;
+        }
+    }
+
+    /// Change report settings of user, then save and check them
+    fn change_report_settings(&mut self, change: impl FnOnce(&mut export::ReportSettings)) {
+        self.load_report_settings();
+        let Some(settings) = &mut self.report.settings else {
+            return;
+        };
+        change(settings);
+        self.report.changed = true;
+        self.save_report_settings();
+    }
+
+    /// Save report settings of user to config folder, if they were changed, then check them
+    pub fn save_report_settings(&mut self) {
+        let Some(settings) = &self.report.settings else {
+            return;
+        };
+        if !self.report.changed {
+            return;
+        }
+
+        match config::path(export::SETTINGS_FILE) {
+            Some(path) => match config::save(&path, settings) {
+                Ok(()) => self.report.changed = false,
+                Err(error) => {
+                    self.set_error_message(format!("Failed to save report settings: {error}"))
+                }
+            },
+            None => {
+                self.set_error_message("Failed to save report settings: Config folder is unknown")
+            }
+        }
+
+        self.check_report_template();
+    }
+
+    /// Check that custom template can be read and rendered, to show result in template window
+    pub fn check_report_template(&mut self) {
+        let settings = self.report.settings.clone().unwrap_or_default();
+        let check = Template::read(&settings)
+            .map(|_| ())
+            .map_err(|error| error.to_string());
+        self.report.check = Some(check);
+    }
+
+    // * Merge and undo
+
+    /// Merge rows of another file into current file
+    ///
+    /// Shows *open file* dialog, asks for password of other file, then shows rows to review
+    pub fn file_merge(&mut self) {
+        print_info!("Merge");
+
+        let Some(path) = file_dialog::mgx()
+            .pick_file()
+            .map(|path_buf| path_buf.display().to_string())
+        else {
+            return;
+        };
+
+        if Some(&path) == self.file.path() {
+            self.set_error_message("Cannot merge a file into itself");
+            return;
+        }
+
+        // Other file is only read, so it is not locked, and can be open in another instance
+        match File::path_uses_legacy_key(&path) {
+            // Old file without password
+            Ok(true) => match File::open_path_with_mode(&path, None, LockMode::Ignore) {
+                Ok(other) => self.merge_file(&path, other),
+                Err(error) => self.set_error_message(error.to_string()),
+            },
+            Ok(false) => self.request_password(PasswordAction::MergeFile(path)),
+            Err(error) => self.set_error_message(error.to_string()),
+        }
+    }
+
+    /// Show rows of other file to review, before they are added to current file
+    ///
+    /// Each row is tagged with name of other file (such as `from:alice`), so it can be found after merge
+    fn merge_file(&mut self, path: &str, other: File) {
+        let source = file_name(std::path::Path::new(path));
+        let tag = source_tag(path);
+
+        let rows = other
+            .contents()
+            .rows
+            .iter()
+            .map(|row| {
+                let mut row = row.clone();
+                if !row.has_tag(&tag) {
+                    row.tags.push(tag.clone());
+                }
+                row
+            })
+            .collect::<Vec<_>>();
+
+        if rows.is_empty() {
+            self.set_error_message(format!("No rows were found in {source}"));
+            return;
+        }
+
+        // Rules are not applied, as rows of other file were already categorized
+        self.review_import(Import {
+            source,
+            merge: true,
+            ids: vec![None; rows.len()],
+            rows,
+            merged_ids: other.header().imported_ids.clone(),
+            ..Import::default()
+        });
+    }
+
+    /// Get rows and imported IDs before a change which can be undone
+    fn before_undoable_change(&self) -> (Csv, Vec<String>) {
+        (
+            self.file.contents().clone(),
+            self.file.header().imported_ids.clone(),
+        )
+    }
+
+    /// Allow change to be undone, after rows were changed
+    fn finish_undoable_change(&mut self, description: String, before: (Csv, Vec<String>)) {
+        let (before, ids_before) = before;
+        let imported_ids = self
+            .file
+            .header()
+            .imported_ids
+            .iter()
+            .filter(|id| !ids_before.contains(id))
+            .cloned()
+            .collect();
+
+        self.undo = Some(Undo {
+            description,
+            before,
+            imported_ids,
+            after: self.file.contents().clone(),
+        });
+    }
+
+    /// Get title of undo button
+    ///
+    /// `None` if there is nothing to undo, or rows were changed since last undoable change
+    pub fn undo_title(&self) -> Option<String> {
+        let undo = self.undo.as_ref()?;
+        (undo.after == *self.file.contents()).then(|| format!("Undo {}", undo.description))
+    }
+
+    /// Restore rows from before last import, merge, or application of rules
+    ///
+    /// Nothing happens if rows were changed since
+    pub fn undo(&mut self) {
+        if self.undo_title().is_none() {
+            return;
+        }
+        let Some(Undo {
+            description,
+            before,
+            imported_ids,
+            ..
+        }) = self.undo.take()
+        else {
+            return;
+        };
+
+        print_info!("Undo {description}");
+        *self.file.contents_mut() = before;
+        // Other changes to header are kept
+        self.file
+            .header_mut()
+            .imported_ids
+            .retain(|id| !imported_ids.contains(id));
+        self.file.mark_as_unsaved();
+    }
+
+    // * Handle file close
+
+    /// Returns `true` if file is not changed, or condition is overridden
+    pub fn file_can_close(&self) -> bool {
+        self.attempting_file_close
+            .check_condition(!self.file.is_changed())
+    }
+
+    /// Run close action if allowed
+    pub fn call_close_action(&mut self) {
+        // Cancel if file is not allowed to close (unsaved file)
+        if !self.file_can_close() {
+            return;
+        }
+
+        // If action was registered
+        if let Some(action) = &self.attempting_file_close.action() {
+            match action {
+                // These 2 actions were registered by methods in this file,
+                //      and call themselves again
+                // They must reset the close action, or it will loop
+                CloseFileAction::OpenFile => {
+                    self.file_open();
+                    self.reset_close_action();
+                }
+                CloseFileAction::NewFile => {
+                    self.file_new();
+                    self.reset_close_action();
+                }
+                CloseFileAction::ImportAsNewFile => {
+                    self.import_as_new_file();
+                    self.reset_close_action();
+                }
+
+                // This action was registered by the `on_close_event` method
+                // This cannot call `reset_close_action,
+                //      as window will not close on next frame
+                // This will not loop, as program will close before that
+                CloseFileAction::CloseWindow => self.close_window_on_next_frame = true,
+            }
+        }
+    }
+
+    /// Reset close action
+    pub fn reset_close_action(&mut self) {
+        self.attempting_file_close.reset_attempt();
+    }
+}
+
+impl BankImport {
+    /// Split file into cells again, after delimiter or header rows were changed
+    pub fn reread(&mut self) {
+        self.records = self
+            .profile
+            .read_records(&self.text)
+            .map_err(|error| error.to_string());
+    }
+}
+
+/// Get name of file at path, for messages
+fn file_name(path: &std::path::Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Get tag for rows merged from file at path, such as `from:alice` for `alice.mgx`
+///
+/// Whitespace in name of file is replaced with `-`, as tags cannot contain whitespace
+fn source_tag(path: &str) -> String {
+    let path = std::path::Path::new(path);
+    let stem = path
+        .file_stem()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy();
+    let name = stem.split_whitespace().collect::<Vec<_>>().join("-");
+    format!("from:{name}")
 }
@@ -3,23 +3,168 @@ mod methods;
 /// Render `App` with `eframe::App` implementation
 mod render;
 
-use std::sync::{Arc, Mutex};
+use std::{
+    ops::RangeInclusive,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-use crate::{Attempt, Channel, File};
+use chrono::{Datelike, Local, NaiveDate};
+
+use crate::{
+    csv::{cells::Record, BadRow, Csv, CsvRow, Header, Period},
+    export::ReportSettings,
+    file::{self, Backup, BackupSettings, DiskState, LockMode, LockOwner, Snapshot, SnapshotLock},
+    import::{bank::Profile, Transaction},
+    rules::{Change, Rule},
+    Attempt, Channel, File,
+};
 
 /// Possible messages between threads
 enum ConcurrentMessage {
-    /// Save has succeeded
-    FinishConcurrentSave,
+    /// Save has succeeded, with updated header, and state of file on disk
+    FinishConcurrentSave(Header, Option<DiskState>),
+    /// File on disk at path, which was changed by another program, has been compared with current file
+    ///
+    /// Has amount of rows only in file on disk, and only in current file, or `None` if file could not be opened
+    CompareExternalChange(String, Option<(usize, usize)>),
+    /// Backup at path has been opened, with contents, or error message if it could not be opened
+    OpenBackup(String, Result<Csv, String>),
 }
 
 /// Actions to allow after close attempt passes
 enum CloseFileAction {
     NewFile,
     OpenFile,
+    ImportAsNewFile,
     CloseWindow,
 }
 
+/// Actions to allow after password is entered
+#[derive(Clone)]
+enum PasswordAction {
+    /// Open file at path, with lock mode
+    OpenFile(String, LockMode),
+    /// Merge rows of file at path into current file
+    MergeFile(String),
+    /// Save file to path, with a new password
+    SaveFile(String),
+    /// Confirm current password, before changing it
+    ChangePasswordOld,
+    /// Change password of current file, from old password (`None` if legacy file)
+    ChangePasswordNew(Option<String>),
+    /// Restore unsaved changes from snapshot, with password of original file
+    RestoreSnapshot(Snapshot),
+}
+
+/// File which could not be opened, as some rows could not be parsed
+struct RecoverFile {
+    /// Path of file
+    path: String,
+    /// Password of file (`None` if legacy file)
+    password: Option<String>,
+    /// Lock mode to open file with
+    mode: LockMode,
+    /// Error of first bad row
+    error: String,
+}
+
+/// File on disk which was changed by another program, since it was opened or saved
+struct ExternalChange {
+    /// Path of file
+    path: String,
+    /// Amount of rows only in file on disk, and only in current file
+    ///
+    /// `None` if file on disk could not be opened
+    difference: Option<(usize, usize)>,
+    /// Whether file on disk is still being read in another thread
+    comparing: bool,
+}
+
+/// Rows read from another file, to add to current file or a new file
+#[derive(Default)]
+struct Import {
+    /// Name of file rows were read from
+    source: String,
+    /// Whether rows are merged from another MagicTax file, rather than imported
+    merge: bool,
+    rows: Vec<CsvRow>,
+    /// ID of transaction of each row, to skip it if imported again
+    ids: Vec<Option<String>>,
+    /// Transactions which were already imported into current file
+    ///
+    /// Added back if rows are imported as a new file
+    skipped: Vec<Transaction>,
+    /// IDs of transactions imported into merged file
+    ///
+    /// Rows of file do not have IDs, so these are only kept if every row is merged
+    merged_ids: Vec<String>,
+    /// Index of row in current file which each row is suspected to be a duplicate of
+    duplicates: Vec<Option<usize>>,
+    /// Whether each row will be imported
+    ///
+    /// Suspected duplicates start unchecked
+    selected: Vec<bool>,
+    /// Amount of rows changed by rules
+    changed_by_rules: usize,
+}
+
+impl Import {
+    /// Get rows which will be imported
+    fn selected_rows(&self) -> Vec<CsvRow> {
+        self.rows
+            .iter()
+            .zip(&self.selected)
+            .filter(|(_, selected)| **selected)
+            .map(|(row, _)| row.clone())
+            .collect()
+    }
+
+    /// Get IDs of transactions which will be imported
+    fn selected_ids(&self) -> Vec<String> {
+        let mut ids: Vec<_> = self
+            .ids
+            .iter()
+            .zip(&self.selected)
+            .filter_map(|(id, selected)| id.clone().filter(|_| *selected))
+            .collect();
+        if !self.selected.contains(&false) {
+            ids.extend(self.merged_ids.iter().cloned());
+        }
+        ids
+    }
+}
+
+/// Bank csv file being imported, with mapping of columns to choose
+struct BankImport {
+    /// Name of file
+    source: String,
+    /// Text of file
+    text: String,
+    /// Rows of file, after skipping header rows, or error message if file could not be split into cells
+    records: Result<Vec<Record>, String>,
+    /// Current mapping of columns
+    profile: Profile,
+    /// Profiles saved by user, to reuse for another file of the same bank
+    profiles: Vec<Profile>,
+}
+
+/// Unencrypted formats to export rows to
+#[derive(Clone, Copy)]
+enum ExportFormat {
+    Csv,
+    Qif,
+}
+
+/// File which is open in another program instance
+struct LockedFile {
+    /// Path of file
+    path: String,
+    /// Program instance which has file open
+    owner: LockOwner,
+}
+
 /// Main app state
 #[derive(Default)]
 pub struct App {
@@ -32,6 +177,43 @@ pub struct App {
     /// Attempt to close file (See `Attempt`)
     attempting_file_close: Attempt<CloseFileAction>,
 
+    /// Attempt to enter password (See `Attempt`)
+    attempting_password: Attempt<PasswordAction>,
+
+    /// Attempt to save over file which was changed by another program (See `Attempt`)
+    attempting_external_save: Attempt<ExternalChange>,
+
+    /// Attempt to import rows from another file (See `Attempt`)
+    attempting_import: Attempt<Import>,
+
+    /// Attempt to export unencrypted file (See `Attempt`)
+    attempting_unencrypted_export: Attempt<ExportFormat>,
+
+    /// Bank csv file being imported, for import wizard
+    ///
+    /// `None` if wizard is not open
+    bank_import: Option<BankImport>,
+
+    /// Attempt to open file which is open in another program instance (See `Attempt`)
+    attempting_locked_open: Attempt<LockedFile>,
+
+    /// Time when lock of current file was last refreshed
+    lock_refreshed: Option<Instant>,
+
+    /// Attempt to open file with bad rows quarantined (See `Attempt`)
+    attempting_recovery: Attempt<RecoverFile>,
+
+    /// Rows of current file which could not be parsed, to be fixed or discarded
+    ///
+    /// These are not saved with the file
+    quarantine: Vec<BadRow>,
+
+    /// Text input for password dialog
+    password_input: String,
+
+    /// Text input for password dialog, to confirm a new password
+    password_confirm_input: String,
+
     /// Whether program window should be closed on next frame render
     close_window_on_next_frame: bool,
 
@@ -50,12 +232,203 @@ pub struct App {
 
     /// Display any error message
     error_message: Arc<Mutex<Option<String>>>,
+
+    /// Only show rows with a date inside a range
+    date_filter: DateFilter,
+
+    /// Length of periods to show totals for
+    period: Period,
+
+    /// Backups of previous versions of file
+    backups: Backups,
+
+    /// Snapshots of unsaved changes, to recover after a crash
+    autosave: Autosave,
+
+    /// Rules to change rows automatically
+    rules: Rules,
+
+    /// Custom template and subtotals of report
+    report: Report,
+
+    /// Last import, merge, or application of rules, which can be undone in one step
+    ///
+    /// `None` if there is nothing to undo
+    undo: Option<Undo>,
+}
+
+/// Change to many rows at once, which can be undone in one step
+struct Undo {
+    /// Description of change, such as `merge of alice.mgx`
+    description: String,
+    /// Rows before change
+    before: Csv,
+    /// IDs which change added to header, which are removed again by undo
+    imported_ids: Vec<String>,
+    /// Rows after change
+    ///
+    /// Undo is only allowed if rows were not changed since, so other changes are not lost
+    after: Csv,
+}
+
+/// Time between refreshes of lock of current file
+///
+/// Must be shorter than time when locks become stale
+const LOCK_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Time between snapshots of unsaved changes
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Take snapshots of unsaved changes, and restore snapshots left by a crash
+struct Autosave {
+    /// Folder to save snapshots in
+    ///
+    /// `None` if user data folder is unknown, and autosave is disabled
+    folder: Option<PathBuf>,
+    /// Name of snapshot of this program instance
+    session: String,
+    /// Random key to encrypt snapshots of files without a password, saved next to snapshot
+    key: String,
+    /// Time of last snapshot
+    last_time: Option<Instant>,
+    /// Contents of last snapshot, to skip snapshots without changes
+    last_contents: Option<Csv>,
+    /// Snapshots left by program instances which did not close properly
+    ///
+    /// `None` if recovery folder was not checked yet
+    leftovers: Option<Vec<Snapshot>>,
+    /// Lock of snapshot of this instance, so other instances do not list it as a leftover
+    lock: Option<SnapshotLock>,
+    /// Amount of times snapshot was removed, locked while snapshot is written or removed
+    ///
+    /// Snapshot which was started before it was removed is not written, so it is not left behind
+    removals: Arc<Mutex<usize>>,
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Self {
+            folder: file::default_recovery_folder(),
+            session: file::session_name(),
+            key: file::session_key(),
+            last_time: None,
+            last_contents: None,
+            leftovers: None,
+            lock: None,
+            removals: Arc::default(),
+        }
+    }
+}
+
+/// Rules of user, and whether rules window is open
+///
+/// Rules of current file are in header of file
+#[derive(Default)]
+struct Rules {
+    /// Rules saved by user, used for every file
+    ///
+    /// `None` if rules were not read from config folder yet
+    user: Option<Vec<Rule>>,
+    /// Whether rules of user were changed since they were saved
+    changed: bool,
+    /// Whether rules window is open
+    open: bool,
+    /// Last preview of rules window
+    ///
+    /// `None` if not made yet
+    preview: Option<RulesPreview>,
+}
+
+/// Rows which rules would change, with rules and rows it was made from
+///
+/// Only made again when rules or rows change, not every frame
+struct RulesPreview {
+    /// Rules of user, then rules of file
+    rules: Vec<Rule>,
+    /// Rows of file
+    rows: Vec<CsvRow>,
+    /// Rows which would be changed, or error message if a rule is not valid
+    changes: Result<Vec<Change>, String>,
+}
+
+/// Custom template and subtotals of report, and whether report settings window is open
+#[derive(Default)]
+struct Report {
+    /// Custom template, style, and subtotals of user
+    ///
+    /// `None` if settings were not read from config folder yet
+    settings: Option<ReportSettings>,
+    /// Whether report settings were changed since they were saved
+    changed: bool,
+    /// Result of last check of template, or error message
+    ///
+    /// `None` if template was not checked since it was changed
+    check: Option<Result<(), String>>,
+    /// Whether report settings window is open
+    open: bool,
+}
+
+/// Keep and restore previous versions of file
+#[derive(Default)]
+struct Backups {
+    /// Backup settings of user, such as amount of backups to keep of each file
+    ///
+    /// `None` if settings were not read from config folder yet
+    settings: Option<BackupSettings>,
+    /// Whether backup settings were changed since they were saved
+    changed: bool,
+    /// Backups of current file, newest first, for *restore from backup* dialog
+    ///
+    /// `None` if dialog is not open
+    entries: Option<Vec<BackupEntry>>,
+}
+
+/// Backup of current file, with contents if it could be opened
+struct BackupEntry {
+    backup: Backup,
+    /// Contents of backup, or error message if it could not be opened
+    ///
+    /// `None` while backup is being opened in another thread
+    contents: Option<Result<Csv, String>>,
+}
+
+/// Show only rows with a date inside a range
+struct DateFilter {
+    /// Whether filter is active
+    enabled: bool,
+    /// First date to show
+    start: NaiveDate,
+    /// Last date to show
+    end: NaiveDate,
+}
+
+impl Default for DateFilter {
+    /// Current year
+    fn default() -> Self {
+        let year = Local::now().year();
+        Self {
+            enabled: false,
+            start: NaiveDate::from_ymd_opt(year, 1, 1).expect("Should be valid date"),
+            end: NaiveDate::from_ymd_opt(year, 12, 31).expect("Should be valid date"),
+        }
+    }
+}
+
+impl DateFilter {
+    /// Get range of dates to show
+    ///
+    /// `None` if filter is not active
+    fn range(&self) -> Option<RangeInclusive<NaiveDate>> {
+        self.enabled.then_some(self.start..=self.end)
+    }
 }
 
 #[derive(Clone, Copy, PartialEq)]
 enum RowElement {
+    Date,
     Value,
     Label,
+    Tags,
     InsertButton,
     RemoveButton,
 }
@@ -65,9 +438,11 @@ impl RowElement {
         use RowElement::*;
 
         match self {
-            Value => Value,
+            Date => Date,
+            Value => Date,
             Label => Value,
-            InsertButton => Label,
+            Tags => Label,
+            InsertButton => Tags,
             RemoveButton => InsertButton,
         }
     }
@@ -76,8 +451,10 @@ impl RowElement {
         use RowElement::*;
 
         match self {
+            Date => Value,
             Value => Label,
-            Label => InsertButton,
+            Label => Tags,
+            Tags => InsertButton,
             InsertButton => RemoveButton,
             RemoveButton => RemoveButton,
         }
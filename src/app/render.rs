@@ -1,12 +1,17 @@
-use std::f32::INFINITY;
-
-use eframe::{
-    egui,
-    emath::Align2,
-};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use eframe::{egui, emath::Align2};
 use egui::Grid;
+use egui_extras::DatePickerButton;
 
-use crate::{csv::CsvRow, app::RowElement, GLOBAL_WINDOW_SCALE, print_info};
+use crate::{
+    app::RowElement,
+    csv::{CsvRow, Period, Totals},
+    file::LockMode,
+    import::bank::{Role, DATE_FORMATS},
+    print_info,
+    rules::{MatchKind, Rule, Sign},
+    Money, GLOBAL_WINDOW_SCALE,
+};
 
 use super::{App, CloseFileAction, ConcurrentMessage};
 
@@ -22,7 +27,7 @@ impl eframe::App for App {
         }
 
         /// Focus this element, if it is new to the ui
-        /// 
+        ///
         /// For elements such as the default button in a dialog
         macro_rules! focus_if_new {
             ( $($tt:tt)* ) => {{
@@ -39,17 +44,31 @@ impl eframe::App for App {
 
         if let Ok(msg) = self.channel.receiver.try_recv() {
             match msg {
-                ConcurrentMessage::FinishConcurrentSave => {
+                ConcurrentMessage::FinishConcurrentSave(header, disk_state) => {
                     print_info!("Save finished!");
                     self.file.force_set_saved();
+                    self.file.set_header(header);
+                    self.file.set_disk_state(disk_state);
+                    self.remove_own_snapshot();
 
                     if self.attempting_file_close.is_attempting() {
                         self.call_close_action();
                     }
                 }
+                ConcurrentMessage::CompareExternalChange(path, difference) => {
+                    self.finish_external_compare(path, difference);
+                }
+                ConcurrentMessage::OpenBackup(path, contents) => {
+                    self.finish_open_backup(path, contents);
+                }
             }
         }
 
+        // * Autosave
+
+        self.autosave(ctx);
+        self.refresh_lock(ctx);
+
         // * Render main window
 
         // Whether the file is currently writing on a different thread
@@ -93,7 +112,8 @@ impl eframe::App for App {
 
                 // Add row at bottom
                 if ui.button("+").clicked() {
-                    self.file.contents_mut().rows.push(CsvRow::default());
+                    let row = new_row(self.file.contents().rows.last());
+                    self.file.contents_mut().rows.push(row);
                 }
 
                 ui.separator();
@@ -103,7 +123,7 @@ impl eframe::App for App {
                     self.file_save_or_save_as(ctx);
                 });
                 action_button_and_keybind!( "Save As", (CTRL + SHIFT + S), if true => {
-                    self.file_save_as(ctx);
+                    self.file_save_as();
                 });
                 action_button_and_keybind!( "Open", (CTRL + O), if true => {
                     self.file_open();
@@ -114,10 +134,65 @@ impl eframe::App for App {
                 action_button_and_keybind!( "Print", (CTRL + P), if true => {
                     self.file_export_html();
                 });
-                
+                action_button_and_keybind!( "Print PDF…", (ALT + P), if true => {
+                    self.file_export_pdf();
+                });
+                action_button_and_keybind!( "Report settings…", (CTRL + T), if true => {
+                    self.file_show_report_settings();
+                });
+                action_button_and_keybind!( "Import CSV…", (CTRL + I), if true => {
+                    self.file_import_csv();
+                });
+                action_button_and_keybind!( "Import bank CSV…", (CTRL + SHIFT + I), if true => {
+                    self.file_import_bank_csv();
+                });
+                action_button_and_keybind!( "Import statement…", (CTRL + SHIFT + O), if true => {
+                    self.file_import_statement();
+                });
+                action_button_and_keybind!( "Import QIF…", (CTRL + SHIFT + Q), if true => {
+                    self.file_import_qif();
+                });
+                action_button_and_keybind!( "Export CSV…", (CTRL + E), if !self.file.contents().rows.is_empty() => {
+                    self.file_export_csv();
+                });
+                action_button_and_keybind!( "Export QIF…", (CTRL + SHIFT + E), if !self.file.contents().rows.is_empty() => {
+                    self.file_export_qif();
+                });
+                action_button_and_keybind!( "Merge file…", (CTRL + M), if true => {
+                    self.file_merge();
+                });
+                // Not `CTRL + Z`, as it would be taken from text inputs
+                let undo_title = self.undo_title();
+                action_button_and_keybind!( undo_title.as_deref().unwrap_or("Undo"), (ALT + Backspace), if undo_title.is_some() => {
+                    self.undo();
+                });
+                action_button_and_keybind!( "Rules…", (CTRL + R), if true => {
+                    self.file_show_rules();
+                });
+                action_button_and_keybind!( "Change password", (CTRL + SHIFT + P), if self.file.path().is_some() => {
+                    self.file_change_password();
+                });
+                action_button_and_keybind!( "Restore from backup", (CTRL + SHIFT + R), if self.file.path().is_some() => {
+                    self.file_show_backups(ctx);
+                });
+
                 // Show filepath if file is registered
                 if let Some(path) = self.file.path() {
-                    ui.monospace(path);
+                    let header = self.file.header();
+                    let time = |time: Option<DateTime<Utc>>| match time {
+                        Some(time) => time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(),
+                        None => "unknown".to_string(),
+                    };
+                    ui.monospace(path).on_hover_text(format!(
+                        "Created: {}\nModified: {}\nFormat version: {}",
+                        time(header.created),
+                        time(header.modified),
+                        header.version,
+                    ));
+                }
+
+                if self.file.is_read_only() {
+                    ui.label("Read-only").on_hover_text("This file is open in another window. Use Save As to save a copy");
                 }
 
                 // Save state
@@ -136,6 +211,21 @@ impl eframe::App for App {
                 });
             });
 
+            // Sort and filter by date
+            ui.horizontal(|ui| {
+                if ui.button("Sort by date").clicked() {
+                    self.file.contents_mut().sort_by_date();
+                    self.file.mark_as_unsaved();
+                }
+
+                ui.separator();
+
+                ui.checkbox(&mut self.date_filter.enabled, "Only show dates from");
+                ui.add(DatePickerButton::new(&mut self.date_filter.start).id_source("filter-start"));
+                ui.label("to");
+                ui.add(DatePickerButton::new(&mut self.date_filter.end).id_source("filter-end"));
+            });
+
             ui.separator();
 
             // * Rows
@@ -143,10 +233,12 @@ impl eframe::App for App {
             if self.file.contents().rows.is_empty() {
                 ui.monospace("↑ Press [+] to add an entry");
             } else {
-                Grid::new("rows").num_columns(3).striped(true).show(ui, |ui|{
+                Grid::new("rows").num_columns(5).striped(true).show(ui, |ui|{
                     let mut focus_row_this_frame = self.focus_row_on_next_frame;
                     self.focus_row_on_next_frame = None;
 
+                    let date_range = self.date_filter.range();
+
                     for i in 0..self.file.contents().rows.len() {
                         /// Returns `true` if given index (offset from curren index) is still in bounds
                         macro_rules! row_exists {
@@ -169,8 +261,15 @@ impl eframe::App for App {
                             break;
                         }
 
+                        // Skip row if outside of date filter
+                        if let Some(range) = &date_range {
+                            if !self.file.contents().rows[i].is_in_range(range) {
+                                continue;
+                            }
+                        }
+
                         /// Get mutable reference to this row
-                        /// 
+                        ///
                         /// Returns from current function if index out of bounds
                         macro_rules! this_row {
                             () => {
@@ -193,7 +292,8 @@ impl eframe::App for App {
                                 // Add row below
                                 if $element.lost_focus() && keys!($ui: Enter) {
                                     // Insert row after focused one
-                                    self.file.contents_mut().rows.insert(i + 1, CsvRow::default());
+                                    let row = new_row(self.file.contents().rows.get(i));
+                                    self.file.contents_mut().rows.insert(i + 1, row);
                                     self.file.mark_as_unsaved();
                                     // Focus that row on next frame
                                     self.focus_row_on_next_frame = Some((i + 1, $kind));
@@ -229,6 +329,38 @@ impl eframe::App for App {
                             };
                         }
 
+                        // Editable date
+                        ui.horizontal(|ui|{
+                            let id_source = format!("date-{i}");
+
+                            let date_element = match this_row!().date.as_mut() {
+                                Some(date) => ui.add(DatePickerButton::new(date).id_source(&id_source)),
+                                // Rows saved before dates were added
+                                None => {
+                                    let button = ui.button("Add date");
+                                    if button.clicked() {
+                                        this_row!().date = Some(today());
+                                        self.file.mark_as_unsaved();
+                                    }
+                                    button
+                                }
+                            };
+                            handle_focus!(ui: date_element, RowElement::Date);
+
+                            // Mark as unsaved if date was changed
+                            if date_element.changed() {
+                                self.file.mark_as_unsaved();
+                            }
+
+                            // Remove date, such as if it is unknown
+                            if this_row!().date.is_some()
+                                && ui.small_button("x").on_hover_text("Remove date").clicked()
+                            {
+                                this_row!().date = None;
+                                self.file.mark_as_unsaved();
+                            }
+                        });
+
                         // Editable value
                         ui.horizontal(|ui|{
                             let value = &mut this_row!().value;
@@ -238,8 +370,8 @@ impl eframe::App for App {
                                 egui::DragValue::new(value)
                                     .prefix("$")
                                     .max_decimals(2)
-                                    .clamp_range(-INFINITY..=INFINITY)
-                                    .speed(0.01),
+                                    .speed(0.01)
+                                    .clamp_range(Money::MIN..=Money::MAX),
                             );
                             handle_focus!(ui: value_element, RowElement::Value);
 
@@ -260,6 +392,36 @@ impl eframe::App for App {
                             if label_element.changed() {
                                 self.file.mark_as_unsaved();
                             }
+                        });
+
+                        // Editable tags
+                        ui.horizontal(|ui|{
+                            // Text is kept between frames while editing,
+                            //      otherwise separators would be removed as they are typed
+                            let id = ui.make_persistent_id(("tags", i));
+                            let saved_text = this_row!().tags.join(" ");
+                            let mut text = ui
+                                .data_mut(|data| data.get_temp::<String>(id))
+                                .unwrap_or(saved_text);
+
+                            let tags_element = ui.add(
+                                egui::TextEdit::singleline(&mut text)
+                                    .hint_text("tags")
+                                    .desired_width(120.0),
+                            );
+                            handle_focus!(ui: tags_element, RowElement::Tags);
+
+                            // Mark as unsaved if tags were changed
+                            if tags_element.changed() {
+                                this_row!().tags = CsvRow::parse_tags(&text);
+                                self.file.mark_as_unsaved();
+                            }
+
+                            if tags_element.has_focus() {
+                                ui.data_mut(|data| data.insert_temp(id, text));
+                            } else {
+                                ui.data_mut(|data| data.remove::<String>(id));
+                            }
 
                             ui.separator();
                         });
@@ -271,7 +433,8 @@ impl eframe::App for App {
                                 let insert_button = ui.button("+");
                                 handle_focus!(ui: insert_button, RowElement::InsertButton);
                                 if insert_button.clicked() {
-                                    self.file.contents_mut().rows.insert(i + 1, CsvRow::default());
+                                    let row = new_row(self.file.contents().rows.get(i));
+                                    self.file.contents_mut().rows.insert(i + 1, row);
                                     self.file.mark_as_unsaved();
                                 }
 
@@ -296,15 +459,58 @@ impl eframe::App for App {
             // * Bottom of window
 
             let csv = self.file.contents();
-            let count = csv.count();
-            ui.heading(format!("Total: ${sum} ({count} item{s})", sum = csv.sum(), s = plurals(count)));
+            ui.heading(format!("Total: {}", describe_totals(&csv.totals())));
+
+            // Total of rows shown by date filter
+            if let Some(range) = self.date_filter.range() {
+                let totals: Totals = csv.rows_in_range(&range).collect();
+                ui.label(format!("Shown: {}", describe_totals(&totals)));
+            }
+
+            ui.collapsing("Totals by period", |ui| {
+                egui::ComboBox::from_id_source("period")
+                    .selected_text(self.period.name())
+                    .show_ui(ui, |ui| {
+                        for period in Period::ALL {
+                            ui.selectable_value(&mut self.period, period, period.name());
+                        }
+                    });
+
+                Grid::new("period-totals").num_columns(3).striped(true).show(ui, |ui| {
+                    for total in self.file.contents().totals_by_period(self.period) {
+                        ui.label(total.label(self.period));
+                        ui.monospace(format!("${}", total.total));
+                        ui.label(format!("{} item{}", total.count, plurals(total.count)));
+                        ui.end_row();
+                    }
+                });
+            });
+
+            ui.collapsing("Totals by tag", |ui| {
+                Grid::new("tag-totals").num_columns(4).striped(true).show(ui, |ui| {
+                    ui.strong("Tag");
+                    ui.strong("Income");
+                    ui.strong("Expense");
+                    ui.strong("Net");
+                    ui.end_row();
+
+                    for total in self.file.contents().totals_by_tag() {
+                        ui.label(total.label());
+                        ui.monospace(format!("${}", total.income));
+                        ui.monospace(format!("${}", total.expense));
+                        ui.monospace(format!("${}", total.net()));
+                        ui.end_row();
+                    }
+                });
+            });
         });
 
         // * Render popup windows
 
         // Attempting to close file
         // Create custom window dialog if necessary
-        if self.attempting_file_close.is_attempting() {
+        // Hidden while password is being entered, as saving may need a password
+        if self.attempting_file_close.is_attempting() && !self.attempting_password.is_attempting() {
             if concurrently_writing {
                 // Wait for file to finish writing
                 // This cannot be overridden with a button,
@@ -350,6 +556,827 @@ impl eframe::App for App {
             }
         }
 
+        // Password dialog
+        if self.attempting_password.is_attempting() {
+            let needs_confirmation = self.password_needs_confirmation();
+
+            dialog_window(self.password_dialog_title()).show(ctx, |ui| {
+                if needs_confirmation {
+                    ui.label("This password will be needed to open the file.");
+                }
+
+                // Password input
+                let password_element = focus_if_new!(
+                    ui.add(egui::TextEdit::singleline(&mut self.password_input).password(true))
+                );
+                let mut submitted = password_element.lost_focus() && keys!(ui: Enter);
+
+                // Confirm new password
+                if needs_confirmation {
+                    ui.label("Confirm password:");
+                    let confirm_element = ui.add(
+                        egui::TextEdit::singleline(&mut self.password_confirm_input).password(true),
+                    );
+                    submitted = submitted || (confirm_element.lost_focus() && keys!(ui: Enter));
+                }
+
+                // Actions
+                ui.horizontal(|ui| {
+                    // Cancel, without opening or saving
+                    if ui.button("Cancel").clicked() || keys!(ui: Escape) {
+                        self.cancel_password();
+                    }
+
+                    if ui.button("OK").clicked() || submitted {
+                        let password = std::mem::take(&mut self.password_input);
+                        let confirmation = std::mem::take(&mut self.password_confirm_input);
+                        self.submit_password(password, confirmation, ctx);
+                    }
+                });
+            });
+        }
+
+        // Unsaved changes were left by a crash
+        if let Some(snapshot) = self.leftover_snapshot() {
+            if !self.attempting_password.is_attempting() {
+                let time = snapshot.time.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+                let file = snapshot
+                    .file_path
+                    .as_deref()
+                    .unwrap_or("a file which was never saved");
+                let message = format!("MagicTax did not close properly. Unsaved changes to {file} were found, from {time}.");
+
+                dialog_window("Restore unsaved changes?").show(ctx, |ui| {
+                    ui.label(message);
+
+                    // Actions
+                    ui.horizontal(|ui| {
+                        if ui.button("Discard").clicked() {
+                            self.discard_leftover_snapshot();
+                        }
+
+                        if focus_if_new!(ui.button("Restore")).clicked() {
+                            self.restore_leftover_snapshot();
+                        }
+                    });
+                });
+            }
+        }
+
+        // Restore from backup dialog
+        if self.backups.entries.is_some() {
+            let mut restore = None;
+            let mut close = false;
+
+            dialog_window("Restore from backup").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Backups to keep:");
+                    let settings = self.backups.settings.get_or_insert_with(Default::default);
+                    let count_element =
+                        ui.add(egui::DragValue::new(&mut settings.count).clamp_range(0..=100));
+                    if count_element.changed() {
+                        self.backups.changed = true;
+                    }
+                    // Saved once editing is finished, not every frame while dragging
+                    if count_element.drag_released() || count_element.lost_focus() {
+                        self.save_backup_settings();
+                    }
+                });
+                ui.label("Restoring a backup replaces any unsaved changes.");
+
+                let entries = self.backups.entries.as_deref().unwrap_or_default();
+                if entries.is_empty() {
+                    ui.label("No backups yet. A backup is made each time the file is saved.");
+                }
+
+                Grid::new("backups").striped(true).show(ui, |ui| {
+                    for (index, entry) in entries.iter().enumerate() {
+                        ui.label(
+                            entry
+                                .backup
+                                .time
+                                .with_timezone(&Local)
+                                .format("%Y-%m-%d %H:%M:%S")
+                                .to_string(),
+                        );
+
+                        match &entry.contents {
+                            None => {
+                                ui.label("Opening…");
+                            }
+                            Some(Ok(contents)) => {
+                                let count = contents.count();
+                                ui.label(format!("{count} row{}", plurals(count)));
+                                ui.label(format!("Total: {}", contents.sum()));
+                                if ui.button("Restore").clicked() {
+                                    restore = Some(index);
+                                }
+                            }
+                            // Backup may use an old password
+                            Some(Err(error)) => {
+                                ui.label(error);
+                            }
+                        }
+
+                        ui.end_row();
+                    }
+                });
+
+                if focus_if_new!(ui.button("Close")).clicked() || keys!(ui: Escape) {
+                    close = true;
+                }
+            });
+
+            if let Some(index) = restore {
+                self.restore_backup(index);
+            }
+            if close {
+                self.close_backups();
+            }
+        }
+
+        // Rows are being imported
+        // Hidden while current file is being closed, to create a new file
+        if let Some(message) = self.import_message() {
+            if !self.attempting_file_close.is_attempting() {
+                dialog_window("Import").show(ctx, |ui| {
+                    ui.label(message);
+
+                    // Review rows, with suspected duplicates unchecked
+                    let mut any_selected = false;
+                    if let Some(import) = self.attempting_import.action_mut() {
+                        let existing = &self.file.contents().rows;
+
+                        egui::ScrollArea::vertical()
+                            .max_height(300.0)
+                            .show(ui, |ui| {
+                                Grid::new("import_review").striped(true).show(ui, |ui| {
+                                    for ((row, selected), duplicate) in import
+                                        .rows
+                                        .iter()
+                                        .zip(&mut import.selected)
+                                        .zip(&import.duplicates)
+                                    {
+                                        ui.checkbox(selected, "");
+                                        ui.label(
+                                            row.date
+                                                .map(|date| date.to_string())
+                                                .unwrap_or_default(),
+                                        );
+                                        ui.monospace(row.value.to_string());
+                                        ui.label(&row.label);
+
+                                        match duplicate.and_then(|index| existing.get(index)) {
+                                            Some(other) => ui
+                                                .colored_label(
+                                                    ui.visuals().warn_fg_color,
+                                                    "Duplicate?",
+                                                )
+                                                .on_hover_text(format!(
+                                                    "Similar to row {}: {} {} {}",
+                                                    duplicate.unwrap_or_default() + 1,
+                                                    other
+                                                        .date
+                                                        .map(|date| date.to_string())
+                                                        .unwrap_or_default(),
+                                                    other.value,
+                                                    other.label,
+                                                )),
+                                            None => ui.label(""),
+                                        };
+                                        ui.end_row();
+                                    }
+                                });
+                            });
+
+                        ui.horizontal(|ui| {
+                            if ui.button("Check all").clicked() {
+                                import
+                                    .selected
+                                    .iter_mut()
+                                    .for_each(|selected| *selected = true);
+                            }
+                            if ui.button("Uncheck duplicates").clicked() {
+                                for (selected, duplicate) in
+                                    import.selected.iter_mut().zip(&import.duplicates)
+                                {
+                                    *selected = duplicate.is_none();
+                                }
+                            }
+                        });
+
+                        any_selected = import.selected.contains(&true);
+                    }
+
+                    // Actions
+                    ui.horizontal(|ui| {
+                        if ui.button("Cancel").clicked() || keys!(ui: Escape) {
+                            self.cancel_import();
+                        }
+
+                        if ui
+                            .button("New file")
+                            .on_hover_text("Review rows again, in a new file")
+                            .clicked()
+                        {
+                            self.import_as_new_file();
+                        }
+
+                        if focus_if_new!(
+                            ui.add_enabled(any_selected, egui::Button::new("Add to this file"))
+                        )
+                        .clicked()
+                        {
+                            self.import_append();
+                        }
+                    });
+                });
+            }
+        }
+
+        // Choose columns of bank csv file to import
+        if let Some(import) = &mut self.bank_import {
+            let mut reread = false;
+            let mut use_profile = None;
+            let mut save_profile = false;
+            let mut delete_profile = false;
+            let mut finish = false;
+            let mut cancel = false;
+
+            egui::Window::new(format!("Import {}", import.source))
+                .id(egui::Id::new("bank_import"))
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    // Saved profiles
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_label("Profile")
+                            .selected_text(&import.profile.name)
+                            .show_ui(ui, |ui| {
+                                for (index, profile) in import.profiles.iter().enumerate() {
+                                    if ui
+                                        .selectable_label(
+                                            profile.name == import.profile.name,
+                                            &profile.name,
+                                        )
+                                        .clicked()
+                                    {
+                                        use_profile = Some(index);
+                                    }
+                                }
+                            });
+
+                        ui.add(
+                            egui::TextEdit::singleline(&mut import.profile.name)
+                                .hint_text("Profile name")
+                                .desired_width(120.0),
+                        );
+                        let name_is_saved = import
+                            .profiles
+                            .iter()
+                            .any(|profile| profile.name == import.profile.name);
+                        if ui
+                            .add_enabled(
+                                !import.profile.name.trim().is_empty(),
+                                egui::Button::new("Save profile"),
+                            )
+                            .clicked()
+                        {
+                            save_profile = true;
+                        }
+                        if ui
+                            .add_enabled(name_is_saved, egui::Button::new("Delete profile"))
+                            .clicked()
+                        {
+                            delete_profile = true;
+                        }
+                    });
+
+                    // How file is written
+                    ui.horizontal(|ui| {
+                        let delimiter_name = |delimiter| match delimiter {
+                            ',' => "Comma",
+                            ';' => "Semicolon",
+                            '\t' => "Tab",
+                            '|' => "Pipe",
+                            _ => "Other",
+                        };
+                        egui::ComboBox::from_label("Delimiter")
+                            .selected_text(delimiter_name(import.profile.delimiter))
+                            .show_ui(ui, |ui| {
+                                for delimiter in [',', ';', '\t', '|'] {
+                                    if ui
+                                        .selectable_value(
+                                            &mut import.profile.delimiter,
+                                            delimiter,
+                                            delimiter_name(delimiter),
+                                        )
+                                        .clicked()
+                                    {
+                                        reread = true;
+                                    }
+                                }
+                            });
+
+                        ui.label("Skip rows");
+                        if ui
+                            .add(
+                                egui::DragValue::new(&mut import.profile.skip_rows)
+                                    .clamp_range(0..=100),
+                            )
+                            .changed()
+                        {
+                            reread = true;
+                        }
+
+                        ui.checkbox(&mut import.profile.decimal_comma, "Decimal comma")
+                            .on_hover_text("Amounts are written like 1.234,56");
+                        ui.checkbox(&mut import.profile.invert_sign, "Invert sign")
+                            .on_hover_text("Bank shows spending as positive amounts");
+                    });
+
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_label("Date format")
+                            .selected_text(&import.profile.date_format)
+                            .show_ui(ui, |ui| {
+                                for format in DATE_FORMATS {
+                                    ui.selectable_value(
+                                        &mut import.profile.date_format,
+                                        format.to_string(),
+                                        *format,
+                                    );
+                                }
+                            });
+                        ui.add(
+                            egui::TextEdit::singleline(&mut import.profile.date_format)
+                                .code_editor()
+                                .desired_width(80.0),
+                        )
+                        .on_hover_text("Custom format, such as %d %b %Y");
+                    });
+
+                    ui.separator();
+
+                    let records = match &import.records {
+                        Ok(records) => records.as_slice(),
+                        Err(error) => {
+                            ui.label(format!("File could not be read: {error}"));
+                            &[]
+                        }
+                    };
+
+                    // First rows, with column roles and converted result
+                    let columns = records
+                        .iter()
+                        .take(PREVIEW_ROWS)
+                        .map(|record| record.cells.len())
+                        .max()
+                        .unwrap_or(0);
+                    egui::ScrollArea::both().max_height(300.0).show(ui, |ui| {
+                        Grid::new("bank_import_preview")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for column in 0..columns {
+                                    let mut role = import.profile.role(column);
+                                    egui::ComboBox::from_id_source(("bank_import_role", column))
+                                        .selected_text(role.name())
+                                        .width(90.0)
+                                        .show_ui(ui, |ui| {
+                                            for option in Role::ALL {
+                                                ui.selectable_value(
+                                                    &mut role,
+                                                    option,
+                                                    option.name(),
+                                                );
+                                            }
+                                        });
+                                    if role != import.profile.role(column) {
+                                        import.profile.set_role(column, role);
+                                    }
+                                }
+                                ui.strong("Result");
+                                ui.end_row();
+
+                                for record in records.iter().take(PREVIEW_ROWS) {
+                                    for column in 0..columns {
+                                        ui.label(
+                                            record
+                                                .cells
+                                                .get(column)
+                                                .map(String::as_str)
+                                                .unwrap_or_default(),
+                                        );
+                                    }
+                                    match import.profile.convert(&record.cells) {
+                                        Ok(row) => ui.label(format!(
+                                            "{}  {}  {}",
+                                            row.date
+                                                .map(|date| date.to_string())
+                                                .unwrap_or_default(),
+                                            row.value,
+                                            row.label,
+                                        )),
+                                        Err(error) => ui.colored_label(
+                                            ui.visuals().error_fg_color,
+                                            error.to_string(),
+                                        ),
+                                    };
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                    let valid = records
+                        .iter()
+                        .filter(|record| import.profile.convert(&record.cells).is_ok())
+                        .count();
+                    ui.label(format!(
+                        "{valid} of {} row{} can be imported",
+                        records.len(),
+                        plurals(records.len()),
+                    ));
+
+                    // Actions
+                    ui.horizontal(|ui| {
+                        if ui.button("Cancel").clicked() || keys!(ui: Escape) {
+                            cancel = true;
+                        }
+                        if ui
+                            .add_enabled(valid > 0, egui::Button::new("Import"))
+                            .clicked()
+                        {
+                            finish = true;
+                        }
+                    });
+                });
+
+            if reread {
+                import.reread();
+            }
+            if let Some(index) = use_profile {
+                self.bank_import_use_profile(index);
+            }
+            if save_profile {
+                self.bank_import_save_profile();
+            }
+            if delete_profile {
+                self.bank_import_delete_profile();
+            }
+            if finish {
+                self.finish_bank_import();
+            }
+            if cancel {
+                self.cancel_bank_import();
+            }
+        }
+
+        // Edit rules, and preview changes to current file
+        if self.rules.open {
+            let mut open = true;
+            let mut file_rules_changed = false;
+            let mut save_user = false;
+            let mut apply = false;
+            let preview = self.take_rules_preview();
+
+            egui::Window::new("Rules")
+                .open(&mut open)
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(500.0)
+                        .show(ui, |ui| {
+                            ui.heading("My rules");
+                            ui.label("Used for every file");
+                            if let Some(user) = &mut self.rules.user {
+                                if rules_editor(ui, "user_rules", user) {
+                                    self.rules.changed = true;
+                                }
+                            }
+                            if ui
+                                .add_enabled(self.rules.changed, egui::Button::new("Save my rules"))
+                                .clicked()
+                            {
+                                save_user = true;
+                            }
+
+                            ui.separator();
+                            ui.heading("Rules of this file");
+                            ui.label("Saved with this file, and used after my rules");
+                            file_rules_changed =
+                                rules_editor(ui, "file_rules", &mut self.file.header_mut().rules);
+
+                            ui.separator();
+                            ui.heading("Preview");
+                            match &preview.changes {
+                                Err(error) => {
+                                    ui.colored_label(ui.visuals().error_fg_color, error);
+                                }
+                                Ok(changes) if changes.is_empty() => {
+                                    ui.label("No rows would be changed");
+                                }
+                                Ok(changes) => {
+                                    let rows = &preview.rows;
+                                    let describe = |row: &CsvRow| {
+                                        format!(
+                                            "{}  {}  {}  {}",
+                                            row.date
+                                                .map(|date| date.to_string())
+                                                .unwrap_or_default(),
+                                            row.value,
+                                            row.label,
+                                            row.tags.join(" "),
+                                        )
+                                    };
+
+                                    Grid::new("rules_preview").striped(true).show(ui, |ui| {
+                                        for change in changes {
+                                            ui.label(format!("Row {}", change.index + 1));
+                                            ui.label(describe(&rows[change.index]));
+                                            ui.label("→");
+                                            ui.strong(describe(&change.row));
+                                            ui.end_row();
+                                        }
+                                    });
+
+                                    if ui
+                                        .button(format!(
+                                            "Apply to {} row{}",
+                                            changes.len(),
+                                            plurals(changes.len())
+                                        ))
+                                        .clicked()
+                                    {
+                                        apply = true;
+                                    }
+                                }
+                            }
+                        });
+                });
+
+            self.rules.preview = Some(preview);
+            if !open {
+                self.close_rules();
+            }
+            if file_rules_changed {
+                self.file.mark_as_unsaved();
+            }
+            if save_user {
+                self.save_user_rules();
+            }
+            if apply {
+                self.apply_rules();
+            }
+        }
+
+        // Choose custom template of html report, and subtotals of report
+        if self.report.open {
+            let mut open = true;
+            let mut choose_template = false;
+            let mut choose_style = false;
+            let mut reset = false;
+            let mut check = false;
+            let settings = self.report.settings.clone().unwrap_or_default();
+            let mut subtotals = settings.subtotals;
+            // Subtotals are saved once editing is finished
+            let mut finished = false;
+
+            egui::Window::new("Report settings")
+                .open(&mut open)
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    ui.heading("Template");
+                    ui.label(
+                        "Used when printing to html. See README for values passed to template.",
+                    );
+
+                    let describe = |path: &Option<std::path::PathBuf>| match path {
+                        Some(path) => path.display().to_string(),
+                        None => "Built-in".to_string(),
+                    };
+
+                    Grid::new("report_template").show(ui, |ui| {
+                        ui.label("Template");
+                        ui.label(describe(&settings.template));
+                        if ui.button("Choose…").clicked() {
+                            choose_template = true;
+                        }
+                        ui.end_row();
+
+                        ui.label("Style");
+                        ui.label(describe(&settings.style));
+                        if ui.button("Choose…").clicked() {
+                            choose_style = true;
+                        }
+                        ui.end_row();
+                    });
+
+                    ui.horizontal(|ui| {
+                        let is_custom = settings.template.is_some() || settings.style.is_some();
+                        if ui
+                            .add_enabled(is_custom, egui::Button::new("Use built-in"))
+                            .clicked()
+                        {
+                            reset = true;
+                        }
+                        if ui
+                            .button("Check")
+                            .on_hover_text("Read files again, and render a sample report")
+                            .clicked()
+                        {
+                            check = true;
+                        }
+                    });
+
+                    match &self.report.check {
+                        Some(Ok(())) => {
+                            ui.label("Template is valid");
+                        }
+                        Some(Err(error)) => {
+                            ui.colored_label(ui.visuals().error_fg_color, error);
+                        }
+                        None => (),
+                    }
+
+                    ui.separator();
+                    ui.heading("Subtotals");
+                    ui.label("Used when printing to html or pdf");
+
+                    ui.horizontal(|ui| {
+                        let mut enabled = subtotals.every.is_some();
+                        finished |= ui.checkbox(&mut enabled, "Subtotal every").changed();
+                        let mut every = subtotals.every.unwrap_or(10);
+                        let every_element = ui.add_enabled(
+                            enabled,
+                            egui::DragValue::new(&mut every).clamp_range(1..=1000),
+                        );
+                        finished |= every_element.drag_released() || every_element.lost_focus();
+                        ui.label("rows");
+                        subtotals.every = enabled.then_some(every);
+                    });
+                    finished |= ui
+                        .checkbox(&mut subtotals.per_section, "Total of each section")
+                        .changed();
+                });
+
+            if !open {
+                self.close_report_settings();
+            }
+            if subtotals != settings.subtotals {
+                self.edit_report_subtotals(subtotals);
+            }
+            if finished {
+                self.save_report_settings();
+            }
+            if choose_template {
+                self.choose_report_template();
+            }
+            if choose_style {
+                self.choose_report_style();
+            }
+            if reset {
+                self.reset_report_template();
+            }
+            if check {
+                self.check_report_template();
+            }
+        }
+
+        // Warn that csv or qif export is not encrypted
+        if let Some(message) = self.unencrypted_export_message() {
+            dialog_window("Export unencrypted file?").show(ctx, |ui| {
+                ui.label(message);
+
+                // Actions
+                ui.horizontal(|ui| {
+                    if focus_if_new!(ui.button("Cancel")).clicked() || keys!(ui: Escape) {
+                        self.cancel_unencrypted_export();
+                    }
+
+                    if ui.button("Export anyway").clicked() {
+                        self.confirm_unencrypted_export();
+                    }
+                });
+            });
+        }
+
+        // File was changed by another program
+        if let Some(message) = self.external_change_message() {
+            dialog_window("File was changed").show(ctx, |ui| {
+                ui.label(message);
+
+                // Actions
+                ui.horizontal(|ui| {
+                    if ui.button("Cancel").clicked() || keys!(ui: Escape) {
+                        self.cancel_external_change();
+                    }
+
+                    if ui
+                        .button("Reload")
+                        .on_hover_text("Discard your changes, and open the changed file")
+                        .clicked()
+                    {
+                        self.reload_external_change();
+                    }
+
+                    if ui.button("Save As").clicked() {
+                        self.save_as_external_change();
+                    }
+
+                    if focus_if_new!(ui.button("Overwrite")).clicked() {
+                        self.overwrite_external_change(ctx);
+                    }
+                });
+            });
+        }
+
+        // File is open in another program instance
+        if let Some(message) = self.locked_file_message() {
+            dialog_window("File is already open").show(ctx, |ui| {
+                ui.label(message);
+
+                // Actions
+                ui.horizontal(|ui| {
+                    if focus_if_new!(ui.button("Cancel")).clicked() || keys!(ui: Escape) {
+                        self.cancel_locked_open();
+                    }
+
+                    if ui.button("Open read-only").clicked() {
+                        self.open_locked_file(LockMode::ReadOnly);
+                    }
+
+                    if ui.button("Open anyway").clicked() {
+                        self.open_locked_file(LockMode::Ignore);
+                    }
+                });
+            });
+        }
+
+        // Some rows of file could not be parsed
+        if let Some(error) = self.recovery_error().map(str::to_string) {
+            dialog_window("Some rows could not be read").show(ctx, |ui| {
+                ui.label(error);
+                ui.label("Open the file without these rows? They can be fixed or discarded after opening.");
+
+                // Actions
+                ui.horizontal(|ui| {
+                    if ui.button("Cancel").clicked() || keys!(ui: Escape) {
+                        self.cancel_recovery();
+                    }
+
+                    if focus_if_new!(ui.button("Open with bad rows quarantined")).clicked() {
+                        self.recover_file();
+                    }
+                });
+            });
+        }
+
+        // Rows which could not be parsed
+        if !self.quarantine.is_empty() {
+            let mut retry = None;
+            let mut discard = None;
+
+            egui::Window::new("Quarantined rows")
+                .collapsible(true)
+                .show(ctx, |ui| {
+                    ui.label("These rows could not be read. They are not saved with the file, unless fixed.");
+
+                    Grid::new("quarantine").striped(true).show(ui, |ui| {
+                        for (index, bad_row) in self.quarantine.iter_mut().enumerate() {
+                            ui.vertical(|ui| {
+                                ui.label(format!("Line {}", bad_row.error.line));
+                                ui.small(bad_row.error.error.to_string());
+                            });
+
+                            // Edit row as written in file
+                            ui.add(
+                                egui::TextEdit::multiline(&mut bad_row.text)
+                                    .code_editor()
+                                    .desired_rows(1),
+                            );
+
+                            if ui.button("Retry").clicked() {
+                                retry = Some(index);
+                            }
+                            if ui.button("Discard").clicked() {
+                                discard = Some(index);
+                            }
+
+                            ui.end_row();
+                        }
+                    });
+
+                    if ui.button("Discard all").clicked() {
+                        self.quarantine.clear();
+                    }
+                });
+
+            if let Some(index) = retry {
+                self.retry_bad_row(index);
+            }
+            if let Some(index) = discard {
+                self.discard_bad_row(index);
+            }
+        }
+
         // Error message popup
         if let Some(error_msg) = self.get_error_message() {
             dialog_window("Error").show(ctx, |ui| {
@@ -378,21 +1405,184 @@ impl eframe::App for App {
         self.attempting_file_close
             .set_action(CloseFileAction::CloseWindow);
         self.focus_new_element_on_next_frame = true;
+
         // Returns true if file is allowed to close
-        self.file_can_close()
+        // Unsaved changes were saved or discarded, so snapshot is not needed
+        let can_close = self.file_can_close();
+        if can_close {
+            self.remove_own_snapshot();
+        }
+        can_close
+    }
+}
+
+/// Get today's date
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// Create new row, with same date as previous row, or today's date
+fn new_row(previous: Option<&CsvRow>) -> CsvRow {
+    CsvRow {
+        date: Some(previous.and_then(|row| row.date).unwrap_or_else(today)),
+        ..CsvRow::default()
+    }
+}
+
+/// Edit list of rules, and get whether any rule was changed
+fn rules_editor(ui: &mut egui::Ui, id: &str, rules: &mut Vec<Rule>) -> bool {
+    let mut changed = false;
+    let mut remove = None;
+
+    for (index, rule) in rules.iter_mut().enumerate() {
+        ui.push_id((id, index), |ui| {
+            ui.group(|ui| {
+                ui.horizontal(|ui| {
+                    changed |= ui
+                        .checkbox(&mut rule.enabled, "")
+                        .on_hover_text("Enabled")
+                        .changed();
+                    changed |= ui
+                        .add(
+                            egui::TextEdit::singleline(&mut rule.name)
+                                .hint_text("Name")
+                                .desired_width(160.0),
+                        )
+                        .changed();
+                    if ui.button("Remove").clicked() {
+                        remove = Some(index);
+                    }
+                });
+
+                // Match
+                ui.horizontal(|ui| {
+                    ui.label("Label");
+                    changed |= ui
+                        .radio_value(&mut rule.match_kind, MatchKind::Substring, "contains")
+                        .changed();
+                    changed |= ui
+                        .radio_value(&mut rule.match_kind, MatchKind::Regex, "matches regex")
+                        .changed();
+                    changed |= ui
+                        .add(
+                            egui::TextEdit::singleline(&mut rule.pattern)
+                                .code_editor()
+                                .desired_width(160.0),
+                        )
+                        .changed();
+                });
+                ui.horizontal(|ui| {
+                    changed |= optional_amount(ui, "Amount from", &mut rule.min_amount);
+                    changed |= optional_amount(ui, "to", &mut rule.max_amount);
+                });
+
+                // Actions
+                ui.horizontal(|ui| {
+                    ui.label("Add tags");
+
+                    // Text is kept between frames while editing, like tags of rows
+                    let id = ui.make_persistent_id("tags");
+                    let mut text = ui
+                        .data_mut(|data| data.get_temp::<String>(id))
+                        .unwrap_or_else(|| rule.add_tags.join(" "));
+
+                    let tags_element =
+                        ui.add(egui::TextEdit::singleline(&mut text).desired_width(120.0));
+                    if tags_element.changed() {
+                        rule.add_tags = CsvRow::parse_tags(&text);
+                        changed = true;
+                    }
+                    if tags_element.has_focus() {
+                        ui.data_mut(|data| data.insert_temp(id, text));
+                    } else {
+                        ui.data_mut(|data| data.remove::<String>(id));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let mut rewrite = rule.rewrite_label.is_some();
+                    if ui.checkbox(&mut rewrite, "Rewrite label").changed() {
+                        rule.rewrite_label = rewrite.then(String::new);
+                        changed = true;
+                    }
+                    if let Some(label) = &mut rule.rewrite_label {
+                        changed |= ui
+                            .text_edit_singleline(label)
+                            .on_hover_text(
+                                "For regex rules, $1 is replaced with the first group of the match",
+                            )
+                            .changed();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Sign");
+                    changed |= ui.radio_value(&mut rule.set_sign, None, "keep").changed();
+                    changed |= ui
+                        .radio_value(&mut rule.set_sign, Some(Sign::Positive), "make positive")
+                        .changed();
+                    changed |= ui
+                        .radio_value(&mut rule.set_sign, Some(Sign::Negative), "make negative")
+                        .changed();
+                });
+            });
+        });
     }
+
+    if let Some(index) = remove {
+        rules.remove(index);
+        changed = true;
+    }
+    if ui.button("Add rule").clicked() {
+        rules.push(Rule::default());
+        changed = true;
+    }
+
+    changed
 }
 
+/// Edit amount which may be missing, and get whether it was changed
+fn optional_amount(ui: &mut egui::Ui, label: &str, amount: &mut Option<Money>) -> bool {
+    let mut enabled = amount.is_some();
+    let mut changed = ui.checkbox(&mut enabled, label).changed();
+    if changed {
+        *amount = enabled.then_some(Money::ZERO);
+    }
+    if let Some(amount) = amount {
+        changed |= ui
+            .add(
+                egui::DragValue::new(amount)
+                    .speed(0.1)
+                    .clamp_range(Money::MIN..=Money::MAX),
+            )
+            .changed();
+    }
+    changed
+}
+
+/// Amount of rows to show in preview of bank import
+const PREVIEW_ROWS: usize = 10;
+
 /// Create a simple reusable popup dialog window
-fn dialog_window(title: &str) -> egui::Window {
+fn dialog_window(title: &str) -> egui::Window<'_> {
     egui::Window::new(title)
         .collapsible(false)
         .resizable(false)
         .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
 }
 
+/// Describe income, expense, net, and amount of rows, for bottom of window
+fn describe_totals(totals: &Totals) -> String {
+    format!(
+        "${net} net, ${income} income, ${expense} expense ({count} item{s})",
+        net = totals.net(),
+        income = totals.income,
+        expense = totals.expense,
+        count = totals.count,
+        s = plurals(totals.count),
+    )
+}
+
 ///todo comment
-fn plurals(value: usize) -> &'static str {
+pub(super) fn plurals(value: usize) -> &'static str {
     if value == 1 {
         ""
     } else {
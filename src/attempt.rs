@@ -38,6 +38,11 @@ impl<T> Attempt<T> {
         &self.action
     }
 
+    /// Mutable reference to action to run, to change it before it runs
+    pub fn action_mut(&mut self) -> &mut Option<T> {
+        &mut self.action
+    }
+
     /// Override any condition, allow action to run on next attempt
     pub fn override_condition(&mut self) {
         self.overridden = true;
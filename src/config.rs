@@ -0,0 +1,37 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Get path of file with name, in config folder of user
+///
+/// `None` if config folder is unknown
+pub fn path(name: &str) -> Option<PathBuf> {
+    dirs_next::config_dir().map(|dir| dir.join("magictax").join(name))
+}
+
+/// Read value from json file, such as saved rules
+///
+/// Default value (such as no rules) if file does not exist yet
+pub fn load<T: DeserializeOwned + Default>(path: &Path) -> io::Result<T> {
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(error) => Err(error),
+    }
+}
+
+/// Write value to json file, replacing any saved value
+///
+/// Folder of file is created if it does not exist
+pub fn save<T: Serialize + ?Sized>(path: &Path, value: &T) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let text = serde_json::to_string_pretty(value)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    fs::write(path, text)
+}
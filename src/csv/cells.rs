@@ -0,0 +1,179 @@
+use std::borrow::Cow;
+
+use super::{LineError, ParseError};
+
+/// Cells of one row, and where it starts in the file
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    /// Line number of start of row, starting at 1
+    pub line: usize,
+    /// Text of each cell, without quotes
+    pub cells: Vec<String>,
+    /// Original text of row in file, without line break at end
+    pub source: String,
+}
+
+/// Split text into records (rows) of cells, following RFC 4180
+///
+/// Cells may be quoted with `"`, to contain commas, quotes (as `""`), and line breaks
+///
+/// Unquoted cells are trimmed, and lines with only whitespace are skipped
+///
+/// Line numbers start at `first_line`
+pub fn read_records(text: &str, first_line: usize) -> Result<Vec<Record>, LineError> {
+    read_records_with_delimiter(text, first_line, ',')
+}
+
+/// Split text into records (rows) of cells, like `read_records`, with cells separated by `delimiter`
+pub fn read_records_with_delimiter(
+    text: &str,
+    first_line: usize,
+    delimiter: char,
+) -> Result<Vec<Record>, LineError> {
+    let mut records = Vec::new();
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    // Whether current cell was quoted (content is not trimmed)
+    let mut quoted = false;
+    // Whether currently inside quotes
+    let mut in_quotes = false;
+
+    // Current line number
+    let mut line = first_line;
+    // Line number of start of current record
+    let mut record_line = line;
+    // Line number of start of current quotes
+    let mut quote_line = line;
+    // Byte index of start of current record
+    let mut record_start = 0;
+
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((index, ch)) = chars.next() {
+        if in_quotes {
+            match ch {
+                // Escaped quote
+                '"' if chars.peek().map(|(_, ch)| *ch) == Some('"') => {
+                    chars.next();
+                    cell.push('"');
+                }
+                // End of quotes
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    cell.push(ch);
+                }
+                _ => cell.push(ch),
+            }
+            continue;
+        }
+
+        match ch {
+            // Start of quotes, ignoring any whitespace before it
+            '"' if !quoted && cell.trim().is_empty() => {
+                cell.clear();
+                quoted = true;
+                in_quotes = true;
+                quote_line = line;
+            }
+            _ if ch == delimiter => cells.push(take_cell(&mut cell, &mut quoted)),
+            '\n' => {
+                cells.push(take_cell(&mut cell, &mut quoted));
+                records.push(Record {
+                    line: record_line,
+                    cells: std::mem::take(&mut cells),
+                    source: source(&text[record_start..index]),
+                });
+                line += 1;
+                record_line = line;
+                record_start = index + 1;
+            }
+            // Part of line break
+            '\r' => (),
+            // Ignore whitespace after closing quote
+            _ if quoted && ch.is_whitespace() => (),
+            _ => cell.push(ch),
+        }
+    }
+
+    if in_quotes {
+        return Err(LineError {
+            line: quote_line,
+            column: cells.len() + 1,
+            // Only first line of cell, as it continues to end of file
+            text: cell.lines().next().unwrap_or_default().to_string(),
+            error: ParseError::UnclosedQuote,
+        });
+    }
+
+    // Last line may not end with a line break
+    if quoted || !cell.is_empty() || !cells.is_empty() {
+        cells.push(take_cell(&mut cell, &mut quoted));
+        records.push(Record {
+            line: record_line,
+            cells,
+            source: source(&text[record_start..]),
+        });
+    }
+
+    // Skip lines with only whitespace
+    records.retain(|record| !(record.cells.len() == 1 && record.cells[0].is_empty()));
+
+    Ok(records)
+}
+
+/// Split text of version 0 file (without a header) into records, at every comma and line break
+///
+/// Quotes have no meaning in version 0, so cells are only trimmed, as they were read before quoting was added
+///
+/// Lines with only whitespace are skipped
+pub fn read_legacy_records(text: &str, first_line: usize) -> Vec<Record> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| Record {
+            line: first_line + index,
+            cells: line
+                .split(',')
+                .map(|cell| cell.trim().to_string())
+                .collect(),
+            source: source(line),
+        })
+        .collect()
+}
+
+/// Get original text of row, without carriage return of line break at end
+fn source(text: &str) -> String {
+    text.strip_suffix('\r').unwrap_or(text).to_string()
+}
+
+/// Take content of cell, and reset for next cell
+///
+/// Unquoted cells are trimmed
+fn take_cell(cell: &mut String, quoted: &mut bool) -> String {
+    let content = std::mem::take(cell);
+    if std::mem::take(quoted) {
+        content
+    } else {
+        content.trim().to_string()
+    }
+}
+
+/// Quote cell if needed, so it is read the same way it was written
+///
+/// Cells are quoted if they contain a comma, quote, or line break,
+///     or have whitespace at start or end
+///
+/// Cells starting with `#` are also quoted, so they are not mistaken for a header line
+pub fn quote(cell: &str) -> Cow<'_, str> {
+    let needs_quotes = cell.contains([',', '"', '\n', '\r'])
+        || cell.starts_with('#')
+        || cell.starts_with(char::is_whitespace)
+        || cell.ends_with(char::is_whitespace);
+
+    if needs_quotes {
+        Cow::Owned(format!("\"{}\"", cell.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(cell)
+    }
+}
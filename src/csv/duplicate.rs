@@ -0,0 +1,110 @@
+use std::collections::HashSet;
+
+use super::CsvRow;
+
+/// Most days between dates of duplicate rows
+///
+/// Dates of the same transaction can differ, such as between a bank statement and a manual entry
+const MAX_DAYS_APART: i64 = 3;
+
+/// Least similarity of labels of duplicate rows, from 0 to 1 (See `label_similarity`)
+const MIN_LABEL_SIMILARITY: f64 = 0.5;
+
+/// Returns `true` if rows are most likely the same transaction
+///
+/// Values must be equal, dates must be close (or both missing), and labels must be similar
+pub fn is_duplicate(a: &CsvRow, b: &CsvRow) -> bool {
+    if a.value != b.value {
+        return false;
+    }
+
+    let dates_match = match (a.date, b.date) {
+        (Some(a), Some(b)) => (a - b).num_days().abs() <= MAX_DAYS_APART,
+        (None, None) => true,
+        _ => false,
+    };
+
+    dates_match && label_similarity(&a.label, &b.label) >= MIN_LABEL_SIMILARITY
+}
+
+/// Find existing row which each new row is a duplicate of
+///
+/// Each existing row is only matched once, so repeated transactions (such as 2 coffees on the same day)
+///     are only suspected if they are all already in existing rows
+pub fn find(existing: &[CsvRow], new: &[CsvRow]) -> Vec<Option<usize>> {
+    let mut matched = HashSet::new();
+
+    new.iter()
+        .map(|row| {
+            let index = (0..existing.len())
+                .find(|index| !matched.contains(index) && is_duplicate(row, &existing[*index]))?;
+            matched.insert(index);
+            Some(index)
+        })
+        .collect()
+}
+
+/// Get similarity of labels, from 0 (nothing in common) to 1 (same words)
+///
+/// Case, punctuation, and spacing are ignored
+///
+/// Labels where one contains all the words of the other (such as `GROCER` and `Grocer weekly shop`) are the same,
+///     as banks often add or remove details
+///
+/// Otherwise, similarity is the Sørensen–Dice coefficient of pairs of letters
+pub fn label_similarity(a: &str, b: &str) -> f64 {
+    let a = normalize(a);
+    let b = normalize(b);
+
+    // Missing labels give no information
+    if a.is_empty() || b.is_empty() {
+        return if a == b { 1.0 } else { MIN_LABEL_SIMILARITY };
+    }
+
+    let words_of = |text: &str| text.split(' ').map(str::to_string).collect::<HashSet<_>>();
+    let (words_a, words_b) = (words_of(&a), words_of(&b));
+    if words_a.is_subset(&words_b) || words_b.is_subset(&words_a) {
+        return 1.0;
+    }
+
+    let pairs_a = pairs(&a);
+    let pairs_b = pairs(&b);
+    if pairs_a.is_empty() || pairs_b.is_empty() {
+        return 0.0;
+    }
+
+    // Count each pair only as many times as it appears in both
+    let mut remaining = pairs_b.clone();
+    let mut common = 0;
+    for pair in &pairs_a {
+        if let Some(index) = remaining.iter().position(|other| other == pair) {
+            remaining.swap_remove(index);
+            common += 1;
+        }
+    }
+
+    2.0 * common as f64 / (pairs_a.len() + pairs_b.len()) as f64
+}
+
+/// Get lowercase words of label, without punctuation, separated by single spaces
+fn normalize(label: &str) -> String {
+    label
+        .to_lowercase()
+        .split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Get every pair of adjacent characters in each word
+fn pairs(text: &str) -> Vec<(char, char)> {
+    text.split(' ')
+        .flat_map(|word| {
+            let chars: Vec<_> = word.chars().collect();
+            chars
+                .windows(2)
+                .map(|pair| (pair[0], pair[1]))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
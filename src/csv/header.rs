@@ -0,0 +1,208 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+
+use super::{CsvRow, LineError, ParseError};
+use crate::rules::Rule;
+
+/// Current version of file format
+///
+/// Increase this when the format changes, and add a migration to `Header::migrate`
+pub const FORMAT_VERSION: u32 = 1;
+
+/// First line of file with a header, followed by format version
+const SIGNATURE: &str = "#!magictax ";
+
+/// Start of every other header line, followed by key and value
+const PREFIX: &str = "#";
+
+/// Keys of header lines which are read
+///
+/// Lines with other keys are skipped, such as keys from another version, unless they are valid rows
+const KEYS: [&str; 5] = ["app_version", "created", "modified", "imported", "rule"];
+
+/// Version and metadata at start of file
+///
+/// Files saved before headers were added are version 0, and have no header
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    /// Version of file format
+    pub version: u32,
+    /// Version of MagicTax which last saved file
+    ///
+    /// `None` if unknown (file was never saved)
+    pub app_version: Option<String>,
+    /// When file was first saved
+    ///
+    /// `None` if unknown (file was never saved, or saved before headers were added)
+    pub created: Option<DateTime<Utc>>,
+    /// When file was last saved
+    ///
+    /// `None` if unknown (file was never saved, or saved before headers were added)
+    pub modified: Option<DateTime<Utc>>,
+    /// IDs of transactions imported from bank statements, to skip them if imported again
+    pub imported_ids: Vec<String>,
+    /// Rules of this file, used with rules of user (See `Rule`)
+    pub rules: Vec<Rule>,
+    /// Values of rule lines which could not be read, such as from a newer version
+    ///
+    /// Written back unchanged when saved, so they are not lost
+    pub invalid_rules: Vec<String>,
+}
+
+impl Default for Header {
+    /// Header of new file, which was never saved
+    fn default() -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            app_version: None,
+            created: None,
+            modified: None,
+            imported_ids: Vec::new(),
+            rules: Vec::new(),
+            invalid_rules: Vec::new(),
+        }
+    }
+}
+
+impl Header {
+    /// Split header from start of file, and get rest of file, with line number of rest of file
+    ///
+    /// Returns header of version 0 if file has no header
+    ///
+    /// Returns `ParseError::UnsupportedVersion` if file was saved in a newer format
+    pub fn split(file: &str) -> Result<(Self, &str, usize), LineError> {
+        let Some(rest) = file.strip_prefix(SIGNATURE) else {
+            return Ok((
+                Self {
+                    version: 0,
+                    ..Self::default()
+                },
+                file,
+                1,
+            ));
+        };
+
+        let (version, mut rest) = rest.split_once('\n').unwrap_or((rest, ""));
+        let mut line_number = 1;
+
+        /// Create error on current line
+        macro_rules! error {
+            ( $error: expr, $text: expr ) => {
+                LineError {
+                    line: line_number,
+                    column: 1,
+                    text: $text.to_string(),
+                    error: $error,
+                }
+            };
+        }
+
+        let Ok(version) = version.trim().parse::<u32>() else {
+            return Err(error!(ParseError::InvalidHeader, version));
+        };
+
+        // Newer versions may have a different header
+        if version > FORMAT_VERSION {
+            return Err(error!(ParseError::UnsupportedVersion(version), version));
+        }
+
+        let mut header = Self {
+            version,
+            ..Self::default()
+        };
+
+        // Read header lines, until first row
+        while let Some(line) = rest.strip_prefix(PREFIX) {
+            let (line, next) = line.split_once('\n').unwrap_or((line, ""));
+            let line = line.trim();
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+
+            let text = &rest[..rest.len() - next.len()];
+
+            // Row which starts with `#`, such as in a file edited by hand
+            if !KEYS.contains(&key) && CsvRow::try_from(text.trim_end()).is_ok() {
+                break;
+            }
+
+            rest = next;
+            line_number += 1;
+
+            let time =
+                || parse_time(value).ok_or_else(|| error!(ParseError::InvalidHeader, text.trim()));
+
+            match key {
+                "app_version" => header.app_version = Some(value.to_string()),
+                "created" => header.created = Some(time()?),
+                "modified" => header.modified = Some(time()?),
+                "imported" => header.imported_ids.push(value.to_string()),
+                // Invalid rule is kept as text, so rest of file can still be opened
+                "rule" => match serde_json::from_str(value) {
+                    Ok(rule) => header.rules.push(rule),
+                    Err(_) => header.invalid_rules.push(value.to_string()),
+                },
+                // Unknown key, such as from another version
+                _ => (),
+            }
+        }
+
+        Ok((header, rest, line_number + 1))
+    }
+
+    /// Update header of older format version to current version
+    pub fn migrate(mut self) -> Self {
+        // Version 0 -> 1: Header was added
+        // Nothing is known about older files
+        if self.version == 0 {
+            self.version = 1;
+        }
+
+        self
+    }
+
+    /// Update header when file is saved
+    ///
+    /// Sets current format version and app version, and modified time (and created time, if unknown)
+    pub fn touch(&mut self) {
+        let now = Utc::now();
+        self.version = FORMAT_VERSION;
+        self.app_version = Some(env!("CARGO_PKG_VERSION").to_string());
+        self.created.get_or_insert(now);
+        self.modified = Some(now);
+    }
+}
+
+/// Parse time from header
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+impl Display for Header {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{SIGNATURE}{}", self.version)?;
+        if let Some(app_version) = &self.app_version {
+            writeln!(f, "{PREFIX}app_version {app_version}")?;
+        }
+        if let Some(created) = &self.created {
+            writeln!(f, "{PREFIX}created {}", created.to_rfc3339())?;
+        }
+        if let Some(modified) = &self.modified {
+            writeln!(f, "{PREFIX}modified {}", modified.to_rfc3339())?;
+        }
+        for id in &self.imported_ids {
+            writeln!(f, "{PREFIX}imported {id}")?;
+        }
+        // Rules are written as JSON, which is always one line
+        for rule in &self.rules {
+            let rule = serde_json::to_string(rule).map_err(|_| std::fmt::Error)?;
+            writeln!(f, "{PREFIX}rule {rule}")?;
+        }
+        for rule in &self.invalid_rules {
+            writeln!(f, "{PREFIX}rule {rule}")?;
+        }
+        Ok(())
+    }
+}
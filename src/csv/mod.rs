@@ -1,17 +1,53 @@
 #[cfg(test)]
 mod tests;
 
-use std::{error::Error, fmt::Display};
+/// Split text into cells, with quoting
+pub mod cells;
+/// Find rows which are most likely the same transaction
+mod duplicate;
+/// Version and metadata at start of file
+mod header;
+/// Group rows into periods of time
+mod period;
+/// Group rows by tag
+mod tag;
+/// Income and expense of rows
+mod total;
+
+use std::{error::Error, fmt::Display, ops::RangeInclusive};
+
+use chrono::NaiveDate;
+
+pub use self::{
+    header::Header,
+    period::{Period, PeriodTotal},
+    tag::{TagTotal, UNTAGGED_LABEL},
+    total::Totals,
+};
+use crate::money::Money;
+
+/// Format of dates in CSV file
+const DATE_FORMAT: &str = "%Y-%m-%d";
 
 /// Error parsing data from CSV file
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParseError {
     /// No number value was given
     MissingValue,
-    /// Value given is not a valid float
+    /// Value given is not a valid amount of money
     ValueNotNumber,
     /// Too many cells in CSV row
     TooManyCells,
+    /// Date given is not a valid `YYYY-MM-DD` date
+    InvalidDate,
+    /// Quoted cell was not closed before end of file
+    UnclosedQuote,
+    /// Row continues onto another line, without quotes
+    MultipleRows,
+    /// Header at start of file is not valid
+    InvalidHeader,
+    /// File was saved in a newer format version, which cannot be read
+    UnsupportedVersion(u32),
 }
 
 impl Display for ParseError {
@@ -20,12 +56,94 @@ impl Display for ParseError {
             Self::MissingValue => write!(f, "Missing value"),
             Self::ValueNotNumber => write!(f, "Value is not a number"),
             Self::TooManyCells => write!(f, "Too many cells in row"),
+            Self::InvalidDate => write!(f, "Date is not valid (Should be YYYY-MM-DD)"),
+            Self::UnclosedQuote => write!(f, "Quote was not closed"),
+            Self::MultipleRows => write!(f, "Line break outside of quotes"),
+            Self::InvalidHeader => write!(f, "Invalid file header"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "Unsupported file format version {version}")
+            }
         }
     }
 }
 
 impl Error for ParseError {}
 
+/// Error parsing CSV file, with position of error in file
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineError {
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Column (cell) number, starting at 1
+    pub column: usize,
+    /// Text of cell which caused error, or of whole row if cell is missing
+    pub text: String,
+    pub error: ParseError,
+}
+
+impl Display for LineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            line,
+            column,
+            text,
+            error,
+        } = self;
+        write!(f, "Line {line}, column {column}: {error} ({text:?})")
+    }
+}
+
+impl Error for LineError {}
+
+impl LineError {
+    /// Create error for a row which could not be parsed, at index of cell (starting at 0)
+    fn from_record(record: &cells::Record, (cell, error): (usize, ParseError)) -> Self {
+        Self {
+            line: record.line,
+            column: cell + 1,
+            text: match record.cells.get(cell) {
+                Some(cell) => cell.to_string(),
+                None => record.source.clone(),
+            },
+            error,
+        }
+    }
+}
+
+/// Row of file which could not be parsed, kept to be fixed or discarded
+#[derive(Debug, Clone, PartialEq)]
+pub struct BadRow {
+    /// Why row could not be parsed, and where it is in file
+    pub error: LineError,
+    /// Original text of whole row in file
+    pub text: String,
+}
+
+impl BadRow {
+    /// Parse text of row again, after it was edited
+    ///
+    /// Errors keep line number of original row
+    pub fn parse(&self) -> Result<CsvRow, LineError> {
+        let line = self.error.line;
+        let mut records = cells::read_records(&self.text, line)?.into_iter();
+
+        // Empty line has a single empty cell
+        let record = records.next().unwrap_or(cells::Record {
+            line,
+            cells: vec![String::new()],
+            source: String::new(),
+        });
+
+        // Check there are no more rows
+        if let Some(next) = records.next() {
+            return Err(LineError::from_record(&next, (0, ParseError::MultipleRows)));
+        }
+
+        CsvRow::from_cells(record.cells.clone())
+            .map_err(|error| LineError::from_record(&record, error))
+    }
+}
+
 /// Data parsed from CSV file
 ///
 ///todo: Rename
@@ -35,19 +153,10 @@ pub struct Csv {
 }
 
 impl TryFrom<&str> for Csv {
-    type Error = ParseError;
+    type Error = LineError;
 
     fn try_from(file: &str) -> Result<Self, Self::Error> {
-        let mut rows = Vec::new();
-
-        for line in file.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-            rows.push(line.try_into()?);
-        }
-
-        Ok(Self { rows })
+        Self::parse_rows(file, 1)
     }
 }
 
@@ -63,25 +172,175 @@ impl Display for Csv {
 
 impl Csv {
     /// Alias for `self.to_string()`
+    ///
+    /// Does not include a header
     pub fn encode(&self) -> String {
         self.to_string()
     }
 
-    /// Alias for `Csv::try_from(string)` or `string.try_into()`
-    pub fn decode(file: &str) -> Result<Self, ParseError> {
-        file.try_into()
+    /// Parse rows in current format, such as exported csv, with or without a header
+    ///
+    /// Header is ignored. Saved files should use `decode_with_header`, which reads files without a header as version 0
+    pub fn decode(file: &str) -> Result<Self, LineError> {
+        let (_, rows, first_line) = Header::split(file)?;
+        Self::parse_rows(rows, first_line)
     }
 
-    /// Get total of all values added
-    pub fn sum(&self) -> f32 {
-        let sum: f32 = self.rows.iter().map(|row| row.value).sum();
-        (sum * 100.0).round() / 100.0
+    /// Get string of header, followed by rows
+    pub fn encode_with_header(&self, header: &Header) -> String {
+        format!("{header}{self}")
+    }
+
+    /// Parse file, with or without a header
+    ///
+    /// Rows are parsed depending on format version in header, and migrated to current version
+    ///
+    /// Returns `ParseError::UnsupportedVersion` if file was saved in a newer format
+    pub fn decode_with_header(file: &str) -> Result<(Header, Self), LineError> {
+        let (header, rows, first_line) = Header::split(file)?;
+
+        if header.version > header::FORMAT_VERSION {
+            return Err(unsupported_version(header.version));
+        }
+
+        let mut csv = Self::default();
+        for record in read_versioned_records(rows, first_line, header.version)? {
+            match parse_versioned_record(&record, header.version) {
+                Ok(row) => csv.rows.push(row),
+                Err(error) => return Err(LineError::from_record(&record, error)),
+            }
+        }
+
+        Ok((header.migrate(), csv))
+    }
+
+    /// Parse file, with or without a header, skipping rows which cannot be parsed
+    ///
+    /// Skipped rows are returned, to be fixed or discarded
+    ///
+    /// Invalid headers and unclosed quotes still fail, as the rest of the file cannot be trusted
+    pub fn decode_with_recovery(file: &str) -> Result<(Header, Self, Vec<BadRow>), LineError> {
+        let (header, rows, first_line) = Header::split(file)?;
+
+        if header.version > header::FORMAT_VERSION {
+            return Err(unsupported_version(header.version));
+        }
+
+        let mut csv = Self::default();
+        let mut bad_rows = Vec::new();
+
+        for record in read_versioned_records(rows, first_line, header.version)? {
+            match parse_versioned_record(&record, header.version) {
+                Ok(row) => csv.rows.push(row),
+                Err(error) => bad_rows.push(BadRow {
+                    error: LineError::from_record(&record, error),
+                    text: record.source,
+                }),
+            }
+        }
+
+        Ok((header.migrate(), csv, bad_rows))
+    }
+
+    /// Parse rows, without a header
+    ///
+    /// Line numbers of errors start at `first_line`
+    fn parse_rows(text: &str, first_line: usize) -> Result<Self, LineError> {
+        let mut rows = Vec::new();
+
+        for record in cells::read_records(text, first_line)? {
+            match CsvRow::from_cells(record.cells.clone()) {
+                Ok(row) => rows.push(row),
+                Err(error) => return Err(LineError::from_record(&record, error)),
+            }
+        }
+
+        Ok(Self { rows })
     }
 
     /// Get total of all values added
+    pub fn sum(&self) -> Money {
+        self.rows.iter().map(|row| row.value).sum()
+    }
+
+    /// Get amount of rows
     pub fn count(&self) -> usize {
         self.rows.len()
     }
+
+    /// Get income, expense, and amount of all rows
+    pub fn totals(&self) -> Totals {
+        self.rows.iter().collect()
+    }
+
+    /// Get total of positive values
+    pub fn income(&self) -> Money {
+        self.totals().income
+    }
+
+    /// Get total of negative values, as a positive amount
+    pub fn expense(&self) -> Money {
+        self.totals().expense
+    }
+
+    /// Get income minus expense
+    ///
+    /// Same as `Csv::sum`
+    pub fn net(&self) -> Money {
+        self.totals().net()
+    }
+
+    /// Find row of `self` which each new row is most likely a duplicate of
+    ///
+    /// Compares value, date, and similarity of label (See `CsvRow::is_duplicate_of`)
+    pub fn find_duplicates(&self, rows: &[CsvRow]) -> Vec<Option<usize>> {
+        duplicate::find(&self.rows, rows)
+    }
+
+    /// Sort rows by date, oldest first
+    ///
+    /// Rows without a date are moved to the end
+    ///
+    /// Rows with the same date keep their order
+    pub fn sort_by_date(&mut self) {
+        self.rows.sort_by_key(|row| (row.date.is_none(), row.date));
+    }
+
+    /// Get rows with a date inside range (inclusive)
+    pub fn rows_in_range<'a>(
+        &'a self,
+        range: &'a RangeInclusive<NaiveDate>,
+    ) -> impl Iterator<Item = &'a CsvRow> {
+        self.rows.iter().filter(|row| row.is_in_range(range))
+    }
+
+    /// Get total of values in each period, oldest first
+    ///
+    /// Rows without a date are totalled last, with a `start` of `None`
+    pub fn totals_by_period(&self, period: Period) -> Vec<PeriodTotal> {
+        period::totals(&self.rows, period)
+    }
+
+    /// Get income and expense of rows with each tag, sorted by tag
+    ///
+    /// Rows with multiple tags are counted for every tag
+    ///
+    /// Rows without tags are totalled last, with a `tag` of `None`
+    pub fn totals_by_tag(&self) -> Vec<TagTotal> {
+        tag::totals(&self.rows)
+    }
+
+    /// Get every tag used by any row, sorted
+    pub fn tags(&self) -> Vec<&str> {
+        let mut tags: Vec<_> = self
+            .rows
+            .iter()
+            .flat_map(|row| row.tags.iter().map(String::as_str))
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        tags
+    }
 }
 
 /// Row parsed from CSV file
@@ -89,66 +348,202 @@ impl Csv {
 pub struct CsvRow {
     /// Descriptive label of entry
     pub label: String,
-    /// Number value of entry
-    pub value: f32,
-}
-
-// Manual implementation of serialize
-// impl Serialize for CsvRow {
-//     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-//     where
-//         S: serde::Serializer,
-//     {
-//         let mut state = serializer.serialize_struct("CsvRow", 2)?;
-//         // This must be passed as a string, to not mess up float decimals in json conversion
-//         state.serialize_field("value", &round_to_string(self.value))?;
-//         state.serialize_field("label", &self.label)?;
-//         state.end()
-//     }
-// }
+    /// Amount of money of entry
+    pub value: Money,
+    /// Date of transaction
+    ///
+    /// `None` for rows saved before dates were added
+    pub date: Option<NaiveDate>,
+    /// Tags to group entry by, without whitespace or separators
+    pub tags: Vec<String>,
+}
 
 impl Default for CsvRow {
     fn default() -> Self {
         Self {
             label: String::new(),
-            value: 0.0,
+            value: Money::ZERO,
+            date: None,
+            tags: Vec::new(),
         }
     }
 }
 
-impl TryFrom<&str> for CsvRow {
-    type Error = ParseError;
+impl CsvRow {
+    /// Returns `true` if row has a date inside range (inclusive)
+    pub fn is_in_range(&self, range: &RangeInclusive<NaiveDate>) -> bool {
+        self.date.is_some_and(|date| range.contains(&date))
+    }
 
-    fn try_from(line: &str) -> Result<Self, Self::Error> {
-        // Split into cells, at each comma
-        let mut cells = line.split(',');
+    /// Returns `true` if rows are most likely the same transaction
+    ///
+    /// Values must be equal, dates must be within a few days, and labels must be similar
+    pub fn is_duplicate_of(&self, other: &CsvRow) -> bool {
+        duplicate::is_duplicate(self, other)
+    }
+
+    /// Returns `true` if row has tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|other| other == tag)
+    }
 
-        // Get label - First cell, or entire line if no first cell (impossible)
-        let label = cells.next().unwrap_or(line).trim().to_string();
+    /// Get tags from text, separated by whitespace, commas, or semicolons
+    ///
+    /// Empty and repeated tags are removed
+    pub fn parse_tags(text: &str) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in text.split(|ch: char| ch.is_whitespace() || ch == ',' || ch == ';') {
+            if !tag.is_empty() && !tags.iter().any(|other| other == tag) {
+                tags.push(tag.to_string());
+            }
+        }
+        tags
+    }
+
+    /// Parse cells of row
+    ///
+    /// Returns index of cell (starting at 0) which caused error, with error
+    fn from_cells(cells: Vec<String>) -> Result<Self, (usize, ParseError)> {
+        let cell_count = cells.len();
+        let mut cells = cells.into_iter().enumerate();
+
+        // Get label - First cell, or empty if no first cell (impossible)
+        let label = cells.next().map(|(_, label)| label).unwrap_or_default();
 
         // Get value as string - Second cell
-        let Some(value) = cells.next() else {
-            return Err(ParseError::MissingValue);
+        let Some((index, value)) = cells.next() else {
+            return Err((cell_count, ParseError::MissingValue));
+        };
+
+        // Parse value as exact amount of money
+        let Ok(value) = value.parse() else {
+            return Err((index, ParseError::ValueNotNumber));
         };
 
-        // Parse value as float
-        let Ok(value) = value.trim().parse() else {
-            return Err(ParseError::ValueNotNumber);
+        // Get date - Third cell, which may be missing or empty
+        let date = match cells.next() {
+            Some((index, date)) if !date.is_empty() => {
+                let Ok(date) = NaiveDate::parse_from_str(&date, DATE_FORMAT) else {
+                    return Err((index, ParseError::InvalidDate));
+                };
+                Some(date)
+            }
+            _ => None,
         };
 
+        // Get tags - Fourth cell, separated by spaces
+        let tags = cells
+            .next()
+            .map(|(_, tags)| Self::parse_tags(&tags))
+            .unwrap_or_default();
+
         // Check there are no more cells
-        if cells.next().is_some() {
-            return Err(ParseError::TooManyCells);
+        if let Some((index, _)) = cells.next() {
+            return Err((index, ParseError::TooManyCells));
         }
 
-        Ok(Self { label, value })
+        Ok(Self {
+            label,
+            value,
+            date,
+            tags,
+        })
+    }
+}
+
+impl TryFrom<&str> for CsvRow {
+    type Error = ParseError;
+
+    /// Parse a single row, which may have line breaks inside quotes
+    fn try_from(line: &str) -> Result<Self, Self::Error> {
+        let mut records = cells::read_records(line, 1)
+            .map_err(|error| error.error)?
+            .into_iter();
+
+        // Empty line has a single empty cell
+        let cells = records
+            .next()
+            .map(|record| record.cells)
+            .unwrap_or_else(|| vec![String::new()]);
+
+        // Check there are no more rows
+        if records.next().is_some() {
+            return Err(ParseError::MultipleRows);
+        }
+
+        cells.try_into()
+    }
+}
+
+impl TryFrom<Vec<String>> for CsvRow {
+    type Error = ParseError;
+
+    fn try_from(cells: Vec<String>) -> Result<Self, Self::Error> {
+        Self::from_cells(cells).map_err(|(_, error)| error)
     }
 }
 
 impl Display for CsvRow {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let Self { label, value } = self;
+        let Self {
+            label,
+            value,
+            date,
+            tags,
+        } = self;
         // Return string of label and value, separated with a comma
-        write!(f, "{label},{value}")
+        // Label is quoted if it contains commas, quotes, or line breaks
+        write!(f, "{},{value}", cells::quote(label))?;
+        // Add date, if given, or if tags need to be added after it
+        if date.is_some() || !tags.is_empty() {
+            write!(f, ",")?;
+        }
+        if let Some(date) = date {
+            write!(f, "{}", date.format(DATE_FORMAT))?;
+        }
+        // Add tags, if any
+        if !tags.is_empty() {
+            write!(f, ",{}", tags.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Create error for a format version which cannot be read
+fn unsupported_version(version: u32) -> LineError {
+    LineError {
+        line: 1,
+        column: 1,
+        text: version.to_string(),
+        error: ParseError::UnsupportedVersion(version),
+    }
+}
+
+/// Split rows of file into records, depending on format version of file
+///
+/// Version 0 files were written before quoting was added, so cells are split at every comma
+fn read_versioned_records(
+    text: &str,
+    first_line: usize,
+    version: u32,
+) -> Result<Vec<cells::Record>, LineError> {
+    match version {
+        0 => Ok(cells::read_legacy_records(text, first_line)),
+        _ => cells::read_records(text, first_line),
+    }
+}
+
+/// Parse record as row, depending on format version of file
+///
+/// Version 0 rows only have a label and value
+///
+/// Returns index of cell (starting at 0) which caused error, with error
+fn parse_versioned_record(
+    record: &cells::Record,
+    version: u32,
+) -> Result<CsvRow, (usize, ParseError)> {
+    if version == 0 && record.cells.len() > 2 {
+        return Err((2, ParseError::TooManyCells));
     }
+    CsvRow::from_cells(record.cells.clone())
 }
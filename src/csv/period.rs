@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate};
+
+use super::CsvRow;
+use crate::money::Money;
+
+/// Length of time to group rows into, for totals
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Period {
+    Month,
+    #[default]
+    Quarter,
+    Year,
+}
+
+/// Total of all rows in one period
+#[derive(Debug, PartialEq)]
+pub struct PeriodTotal {
+    /// First day of period
+    ///
+    /// `None` for rows without a date
+    pub start: Option<NaiveDate>,
+    /// Total of all values in period
+    pub total: Money,
+    /// Amount of rows in period
+    pub count: usize,
+}
+
+impl Period {
+    /// Every period, from shortest to longest
+    pub const ALL: [Self; 3] = [Self::Month, Self::Quarter, Self::Year];
+
+    /// Get readable name of period length
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Month => "Month",
+            Self::Quarter => "Quarter",
+            Self::Year => "Year",
+        }
+    }
+
+    /// Get first day of period which contains date
+    pub fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        let month = match self {
+            Self::Month => date.month(),
+            // First month of quarter: 1, 4, 7, or 10
+            Self::Quarter => date.month0() / 3 * 3 + 1,
+            Self::Year => 1,
+        };
+        NaiveDate::from_ymd_opt(date.year(), month, 1).expect("First day of month is always valid")
+    }
+
+    /// Get readable name of period which contains date, such as `2023-05`, `2023 Q2`, or `2023`
+    pub fn label(&self, date: NaiveDate) -> String {
+        match self {
+            Self::Month => date.format("%Y-%m").to_string(),
+            Self::Quarter => format!("{} Q{}", date.year(), date.month0() / 3 + 1),
+            Self::Year => date.year().to_string(),
+        }
+    }
+}
+
+impl PeriodTotal {
+    /// Get readable name of period, or `Undated` for rows without a date
+    pub fn label(&self, period: Period) -> String {
+        match self.start {
+            Some(start) => period.label(start),
+            None => "Undated".to_string(),
+        }
+    }
+}
+
+/// Get total of values in each period, oldest first, then rows without a date
+pub fn totals(rows: &[CsvRow], period: Period) -> Vec<PeriodTotal> {
+    // Sort key puts rows without a date last
+    let mut periods: BTreeMap<(bool, Option<NaiveDate>), (Money, usize)> = BTreeMap::new();
+
+    for row in rows {
+        let start = row.date.map(|date| period.start_of(date));
+        let (total, count) = periods.entry((start.is_none(), start)).or_default();
+        *total += row.value;
+        *count += 1;
+    }
+
+    periods
+        .into_iter()
+        .map(|((_, start), (total, count))| PeriodTotal {
+            start,
+            total,
+            count,
+        })
+        .collect()
+}
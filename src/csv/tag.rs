@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+use super::CsvRow;
+use crate::money::Money;
+
+/// Label of rows without tags
+///
+/// Has a space, so it is never the same as a tag
+pub const UNTAGGED_LABEL: &str = "No tags";
+
+/// Income and expense of all rows with one tag
+#[derive(Debug, Default, PartialEq)]
+pub struct TagTotal {
+    /// Tag of rows
+    ///
+    /// `None` for rows without tags
+    pub tag: Option<String>,
+    /// Total of positive values
+    pub income: Money,
+    /// Total of negative values, as a positive amount
+    pub expense: Money,
+    /// Amount of rows with tag
+    pub count: usize,
+}
+
+impl TagTotal {
+    /// Get income minus expense
+    pub fn net(&self) -> Money {
+        self.income - self.expense
+    }
+
+    /// Get tag, or `UNTAGGED_LABEL` for rows without tags
+    pub fn label(&self) -> &str {
+        self.tag.as_deref().unwrap_or(UNTAGGED_LABEL)
+    }
+
+    /// Add value of row to total
+    fn add(&mut self, value: Money) {
+        if value.is_negative() {
+            self.expense += value.abs();
+        } else {
+            self.income += value;
+        }
+        self.count += 1;
+    }
+}
+
+/// Get income and expense of rows with each tag, sorted by tag, then rows without tags
+pub fn totals(rows: &[CsvRow]) -> Vec<TagTotal> {
+    // Sort key puts rows without tags last
+    let mut tags: BTreeMap<(bool, Option<&str>), TagTotal> = BTreeMap::new();
+
+    for row in rows {
+        if row.tags.is_empty() {
+            tags.entry((true, None)).or_default().add(row.value);
+        }
+        for tag in &row.tags {
+            tags.entry((false, Some(tag)))
+                .or_insert_with(|| TagTotal {
+                    tag: Some(tag.clone()),
+                    ..TagTotal::default()
+                })
+                .add(row.value);
+        }
+    }
+
+    tags.into_values().collect()
+}
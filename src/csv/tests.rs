@@ -16,15 +16,21 @@ fn parse_should_work() {
             rows: vec![
                 CsvRow {
                     label: "foo bar".to_string(),
-                    value: 123.5,
+                    value: Money::from_cents(12350),
+                    date: None,
+                    tags: Vec::new(),
                 },
                 CsvRow {
                     label: "something".to_string(),
-                    value: 0.0,
+                    value: Money::from_cents(0),
+                    date: None,
+                    tags: Vec::new(),
                 },
                 CsvRow {
                     label: "".to_string(),
-                    value: -1.0,
+                    value: Money::from_cents(-100),
+                    date: None,
+                    tags: Vec::new(),
                 }
             ]
         }
@@ -49,7 +55,15 @@ fn parse_should_fail() {
     let error = result.expect_err("Should be invalid due to value not a number");
     assert_eq!(error, ParseError::ValueNotNumber);
 
-    let result: Result<CsvRow, _> = "too many cells,123,unexpected cell".try_into();
+    let result: Result<CsvRow, _> = "invalid date,123,2023-02-30".try_into();
+    let error = result.expect_err("Should be invalid due to invalid date");
+    assert_eq!(error, ParseError::InvalidDate);
+
+    let result: Result<CsvRow, _> = "invalid date,123,yesterday".try_into();
+    let error = result.expect_err("Should be invalid due to invalid date");
+    assert_eq!(error, ParseError::InvalidDate);
+
+    let result: Result<CsvRow, _> = "too many cells,123,2023-01-01,tag,unexpected cell".try_into();
     let error = result.expect_err("Should be invalid due to too many cells");
     assert_eq!(error, ParseError::TooManyCells);
 }
@@ -58,21 +72,27 @@ fn parse_should_fail() {
 fn display_works() {
     let row = CsvRow {
         label: "foo bar".to_string(),
-        value: 123.5,
+        value: Money::from_cents(12350),
+        date: None,
+        tags: Vec::new(),
     }
     .to_string();
     assert_eq!(row, "foo bar,123.5");
 
     let row = CsvRow {
         label: "something".to_string(),
-        value: 0.0,
+        value: Money::from_cents(0),
+        date: None,
+        tags: Vec::new(),
     }
     .to_string();
     assert_eq!(row, "something,0");
 
     let row = CsvRow {
         label: "".to_string(),
-        value: -1.0,
+        value: Money::from_cents(-100),
+        date: None,
+        tags: Vec::new(),
     }
     .to_string();
     assert_eq!(row, ",-1");
@@ -81,15 +101,21 @@ fn display_works() {
         rows: vec![
             CsvRow {
                 label: "foo bar".to_string(),
-                value: 123.5,
+                value: Money::from_cents(12350),
+                date: None,
+                tags: Vec::new(),
             },
             CsvRow {
                 label: "something".to_string(),
-                value: 0.0,
+                value: Money::from_cents(0),
+                date: None,
+                tags: Vec::new(),
             },
             CsvRow {
                 label: "".to_string(),
-                value: -1.0,
+                value: Money::from_cents(-100),
+                date: None,
+                tags: Vec::new(),
             },
         ],
     };
@@ -98,3 +124,531 @@ fn display_works() {
 
     assert_eq!(file, "foo bar,123.5\nsomething,0\n,-1\n");
 }
+
+/// Create date from year, month, and day
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).expect("Should be valid date")
+}
+
+/// Create row with value in cents and date
+fn dated_row(label: &str, cents: i64, date: Option<NaiveDate>) -> CsvRow {
+    CsvRow {
+        label: label.to_string(),
+        value: Money::from_cents(cents),
+        date,
+        tags: Vec::new(),
+    }
+}
+
+#[test]
+fn dates_should_work() {
+    let file = "\
+    dated,12,2023-04-05
+    empty date,-3.5,
+    no date,1
+    ";
+
+    let csv = Csv::decode(file).expect("Should be valid");
+
+    assert_eq!(
+        csv.rows,
+        vec![
+            dated_row("dated", 1200, Some(date(2023, 4, 5))),
+            dated_row("empty date", -350, None),
+            dated_row("no date", 100, None),
+        ]
+    );
+
+    assert_eq!(
+        csv.encode(),
+        "dated,12,2023-04-05\nempty date,-3.5\nno date,1\n"
+    );
+}
+
+#[test]
+fn sort_and_filter_by_date_works() {
+    let mut csv = Csv {
+        rows: vec![
+            dated_row("undated", 100, None),
+            dated_row("march", 200, Some(date(2023, 3, 1))),
+            dated_row("january", 300, Some(date(2023, 1, 15))),
+            dated_row("also march", 400, Some(date(2023, 3, 1))),
+        ],
+    };
+
+    csv.sort_by_date();
+    let labels: Vec<_> = csv.rows.iter().map(|row| row.label.as_str()).collect();
+    assert_eq!(labels, ["january", "march", "also march", "undated"]);
+
+    let range = date(2023, 2, 1)..=date(2023, 3, 1);
+    let labels: Vec<_> = csv
+        .rows_in_range(&range)
+        .map(|row| row.label.as_str())
+        .collect();
+    assert_eq!(labels, ["march", "also march"]);
+}
+
+#[test]
+fn totals_by_period_works() {
+    let csv = Csv {
+        rows: vec![
+            dated_row("", 100, Some(date(2023, 1, 31))),
+            dated_row("", 200, Some(date(2023, 3, 1))),
+            dated_row("", -50, Some(date(2023, 4, 1))),
+            dated_row("", 1000, Some(date(2022, 12, 31))),
+            dated_row("", 7, None),
+        ],
+    };
+
+    let totals = csv.totals_by_period(Period::Quarter);
+    assert_eq!(
+        totals,
+        vec![
+            PeriodTotal {
+                start: Some(date(2022, 10, 1)),
+                total: Money::from_cents(1000),
+                count: 1,
+            },
+            PeriodTotal {
+                start: Some(date(2023, 1, 1)),
+                total: Money::from_cents(300),
+                count: 2,
+            },
+            PeriodTotal {
+                start: Some(date(2023, 4, 1)),
+                total: Money::from_cents(-50),
+                count: 1,
+            },
+            PeriodTotal {
+                start: None,
+                total: Money::from_cents(7),
+                count: 1,
+            },
+        ]
+    );
+
+    let labels: Vec<_> = totals
+        .iter()
+        .map(|total| total.label(Period::Quarter))
+        .collect();
+    assert_eq!(labels, ["2022 Q4", "2023 Q1", "2023 Q2", "Undated"]);
+
+    let labels: Vec<_> = csv
+        .totals_by_period(Period::Month)
+        .iter()
+        .map(|total| total.label(Period::Month))
+        .collect();
+    assert_eq!(
+        labels,
+        ["2022-12", "2023-01", "2023-03", "2023-04", "Undated"]
+    );
+
+    let totals = csv.totals_by_period(Period::Year);
+    assert_eq!(totals.len(), 3);
+    assert_eq!(totals[1].total, Money::from_cents(250));
+}
+
+/// Create row with value in cents and tags
+fn tagged_row(label: &str, cents: i64, tags: &[&str]) -> CsvRow {
+    CsvRow {
+        label: label.to_string(),
+        value: Money::from_cents(cents),
+        date: None,
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+    }
+}
+
+#[test]
+fn tags_should_work() {
+    let file = "\
+    dated,12,2023-04-05,food  work
+    undated,-3.5,,work
+    no tags,1,2023-04-05,
+    ";
+
+    let csv = Csv::decode(file).expect("Should be valid");
+
+    assert_eq!(csv.rows[0].tags, ["food", "work"]);
+    assert_eq!(csv.rows[0].date, Some(date(2023, 4, 5)));
+    assert_eq!(csv.rows[1].tags, ["work"]);
+    assert_eq!(csv.rows[1].date, None);
+    assert!(csv.rows[2].tags.is_empty());
+    assert_eq!(csv.tags(), ["food", "work"]);
+
+    assert_eq!(
+        csv.encode(),
+        "dated,12,2023-04-05,food work\nundated,-3.5,,work\nno tags,1,2023-04-05\n"
+    );
+
+    assert_eq!(
+        CsvRow::parse_tags(" a, b;c  a\t"),
+        ["a", "b", "c"],
+        "Should split at every separator, and remove repeated tags"
+    );
+}
+
+#[test]
+fn totals_by_tag_works() {
+    let csv = Csv {
+        rows: vec![
+            tagged_row("", 1000, &["work"]),
+            tagged_row("", -250, &["work", "car"]),
+            tagged_row("", -100, &["car"]),
+            tagged_row("", 5, &[]),
+        ],
+    };
+
+    let totals = csv.totals_by_tag();
+    assert_eq!(
+        totals,
+        vec![
+            TagTotal {
+                tag: Some("car".to_string()),
+                income: Money::ZERO,
+                expense: Money::from_cents(350),
+                count: 2,
+            },
+            TagTotal {
+                tag: Some("work".to_string()),
+                income: Money::from_cents(1000),
+                expense: Money::from_cents(250),
+                count: 2,
+            },
+            TagTotal {
+                tag: None,
+                income: Money::from_cents(5),
+                expense: Money::ZERO,
+                count: 1,
+            },
+        ]
+    );
+
+    assert_eq!(totals[0].net(), Money::from_cents(-350));
+    assert_eq!(totals[1].net(), Money::from_cents(750));
+    assert_eq!(totals[2].label(), UNTAGGED_LABEL);
+}
+
+#[test]
+fn totals_work() {
+    let csv = Csv {
+        rows: vec![
+            tagged_row("", 1000, &[]),
+            tagged_row("", -250, &[]),
+            tagged_row("", -100, &[]),
+            tagged_row("", 0, &[]),
+        ],
+    };
+
+    assert_eq!(
+        csv.totals(),
+        Totals {
+            income: Money::from_cents(1000),
+            expense: Money::from_cents(350),
+            count: 4,
+        }
+    );
+    assert_eq!(csv.income(), Money::from_cents(1000));
+    assert_eq!(csv.expense(), Money::from_cents(350));
+    assert_eq!(csv.net(), csv.sum());
+    assert_eq!(csv.count(), 4);
+
+    let totals: Totals = csv.rows[1..].iter().collect();
+    assert_eq!(totals.net(), Money::from_cents(-350));
+    assert_eq!(Csv::default().totals(), Totals::default());
+}
+
+#[test]
+fn quoted_cells_should_work() {
+    let file = "\
+    \"Smith, John invoice\",12
+    \"say \"\"hi\"\"\" , 3
+    \"two
+lines\",4,2023-01-01,tag
+    \"  padded  \",5
+    ";
+
+    let csv = Csv::decode(file).expect("Should be valid");
+    let labels: Vec<_> = csv.rows.iter().map(|row| row.label.as_str()).collect();
+    assert_eq!(
+        labels,
+        [
+            "Smith, John invoice",
+            "say \"hi\"",
+            "two\nlines",
+            "  padded  "
+        ]
+    );
+    assert_eq!(csv.rows[2].tags, ["tag"]);
+
+    assert_eq!(
+        csv.encode(),
+        "\"Smith, John invoice\",12\n\"say \"\"hi\"\"\",3\n\"two\nlines\",4,2023-01-01,tag\n\"  padded  \",5\n"
+    );
+
+    let result = Csv::decode("\"not closed,12\nfoo,1\n");
+    assert_eq!(result.map_err(|e| e.error), Err(ParseError::UnclosedQuote));
+
+    let result: Result<CsvRow, _> = "one,1\ntwo,2".try_into();
+    assert_eq!(result, Err(ParseError::MultipleRows));
+}
+
+#[test]
+fn any_label_survives_round_trip() {
+    // Characters which could be mistaken for CSV syntax
+    let alphabet: Vec<char> = "ab ,\"\n\r\t;'é💸".chars().collect();
+
+    // Simple deterministic pseudo-random generator
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut random = |max: usize| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed as usize % max
+    };
+
+    let mut rows = vec![
+        tagged_row("Smith, John invoice", 100, &[]),
+        tagged_row("", 0, &["tag"]),
+        tagged_row("\"", -1, &[]),
+        tagged_row("\"\"", 1, &[]),
+        tagged_row(",", 1, &[]),
+        tagged_row("\r\n", 1, &[]),
+        tagged_row(" ", 1, &[]),
+        tagged_row("trailing quote\"", 1, &[]),
+    ];
+    for _ in 0..500 {
+        let label: String = (0..random(12))
+            .map(|_| alphabet[random(alphabet.len())])
+            .collect();
+        rows.push(tagged_row(&label, random(100_000) as i64 - 50_000, &[]));
+    }
+    let csv = Csv { rows };
+
+    let decoded = Csv::decode(&csv.encode()).expect("Should be valid");
+    assert_eq!(decoded, csv);
+
+    for row in &csv.rows {
+        let decoded: CsvRow = row
+            .to_string()
+            .as_str()
+            .try_into()
+            .expect("Should be valid");
+        assert_eq!(&decoded, row);
+    }
+}
+
+#[test]
+fn header_should_work() {
+    let file = "\
+#!magictax 1
+#app_version 0.1.0
+#created 2023-06-01T10:00:00+00:00
+#modified 2023-06-02T12:30:00+02:00
+#unknown_key from another version
+#1 priority,12
+";
+
+    let (header, csv) = Csv::decode_with_header(file).expect("Should be valid");
+
+    assert_eq!(header.version, 1);
+    assert_eq!(header.app_version.as_deref(), Some("0.1.0"));
+    assert_eq!(
+        header.created.map(|time| time.to_rfc3339()).as_deref(),
+        Some("2023-06-01T10:00:00+00:00")
+    );
+    assert_eq!(
+        header.modified.map(|time| time.to_rfc3339()).as_deref(),
+        Some("2023-06-02T10:30:00+00:00")
+    );
+    // Unknown key is skipped, but valid row starting with `#` is not a header line
+    assert_eq!(csv.rows, [tagged_row("#1 priority", 1200, &[])]);
+    let (_, csv) =
+        Csv::decode_with_header("#!magictax 1\n#hash label,5\nok,1\n").expect("Should be valid");
+    assert_eq!(
+        csv.rows,
+        [
+            tagged_row("#hash label", 500, &[]),
+            tagged_row("ok", 100, &[])
+        ]
+    );
+
+    // Labels starting with `#` are quoted, so they are not read as header
+    let csv = Csv {
+        rows: vec![tagged_row("#1 priority", 1200, &[])],
+    };
+    let file = csv.encode_with_header(&header);
+    assert_eq!(
+        file,
+        "#!magictax 1\n#app_version 0.1.0\n#created 2023-06-01T10:00:00+00:00\n#modified 2023-06-02T10:30:00+00:00\n\"#1 priority\",12\n"
+    );
+    assert_eq!(Csv::decode_with_header(&file), Ok((header, csv)));
+}
+
+#[test]
+fn old_versions_should_migrate() {
+    // Version 0 has no header
+    let (header, csv) = Csv::decode_with_header("foo,1\nbar,2\n").expect("Should be valid");
+    assert_eq!(header.version, header::FORMAT_VERSION);
+    assert_eq!(header.created, None);
+    assert_eq!(csv.rows.len(), 2);
+
+    // Version 0 has no quoting, so quotes are part of label
+    let (_, csv) = Csv::decode_with_header("\"Quoted\" shop,5\n").expect("Should be valid");
+    assert_eq!(csv.rows, [tagged_row("\"Quoted\" shop", 500, &[])]);
+    let error = Csv::decode_with_header("a,1,2023-01-01\n").expect_err("Should fail");
+    assert_eq!(error.error, ParseError::TooManyCells);
+    assert_eq!((error.column, error.text.as_str()), (3, "2023-01-01"));
+
+    // Header is updated when saved
+    let mut header = header;
+    header.touch();
+    assert_eq!(
+        header.app_version.as_deref(),
+        Some(env!("CARGO_PKG_VERSION"))
+    );
+    assert!(header.created.is_some());
+    assert_eq!(header.created, header.modified);
+
+    // Created time does not change on next save
+    let created = header.created;
+    header.touch();
+    assert_eq!(header.created, created);
+}
+
+#[test]
+fn unsupported_versions_should_fail() {
+    let result = Csv::decode("#!magictax 99\n#new_key value\nfoo;1;new format\n");
+    assert_eq!(
+        result.map_err(|e| e.error),
+        Err(ParseError::UnsupportedVersion(99))
+    );
+
+    let result = Csv::decode("#!magictax one\nfoo,1\n");
+    assert_eq!(result.map_err(|e| e.error), Err(ParseError::InvalidHeader));
+
+    let result = Csv::decode("#!magictax 1\n#created yesterday\nfoo,1\n");
+    assert_eq!(
+        result,
+        Err(LineError {
+            line: 2,
+            column: 1,
+            text: "#created yesterday".to_string(),
+            error: ParseError::InvalidHeader,
+        })
+    );
+}
+
+#[test]
+fn errors_have_line_numbers() {
+    // Line numbers count header, and line breaks inside quotes
+    let file = "#!magictax 1\n#app_version 1.0\n\"two\nlines\",1\n\nfoo,bar\n";
+    assert_eq!(
+        Csv::decode(file),
+        Err(LineError {
+            line: 6,
+            column: 2,
+            text: "bar".to_string(),
+            error: ParseError::ValueNotNumber,
+        })
+    );
+
+    // Missing cell shows whole row
+    let error = Csv::decode("foo,1\nbar\n").expect_err("Should fail");
+    assert_eq!((error.line, error.column), (2, 2));
+    assert_eq!(error.text, "bar");
+
+    let error = Csv::decode("foo,1,2023-13-01\n").expect_err("Should fail");
+    assert_eq!((error.line, error.column), (1, 3));
+    assert_eq!(error.text, "2023-13-01");
+    assert_eq!(
+        error.to_string(),
+        "Line 1, column 3: Date is not valid (Should be YYYY-MM-DD) (\"2023-13-01\")"
+    );
+
+    // Unclosed quote shows line where quote started
+    let error = Csv::decode("foo,1\nbar,\"2\nbaz,3\n").expect_err("Should fail");
+    assert_eq!((error.line, error.column), (2, 2));
+    assert_eq!(error.error, ParseError::UnclosedQuote);
+}
+
+#[test]
+fn bad_rows_are_recovered() {
+    let file = "#!magictax 1\nfoo,1\nbar,ten\nbaz,3,,tag\n\"a, b\",1,,,extra\n";
+    let (header, csv, bad_rows) = Csv::decode_with_recovery(file).expect("Should recover");
+
+    assert_eq!(header.version, 1);
+    let labels: Vec<_> = csv.rows.iter().map(|row| row.label.as_str()).collect();
+    assert_eq!(labels, ["foo", "baz"]);
+
+    assert_eq!(bad_rows.len(), 2);
+    assert_eq!(bad_rows[0].error.line, 3);
+    assert_eq!(bad_rows[0].error.error, ParseError::ValueNotNumber);
+    assert_eq!(bad_rows[0].text, "bar,ten");
+    assert_eq!(bad_rows[1].error.line, 5);
+    assert_eq!(bad_rows[1].error.error, ParseError::TooManyCells);
+    assert_eq!(bad_rows[1].text, "\"a, b\",1,,,extra");
+    assert_eq!(bad_rows[1].error.column, 5);
+
+    // Text of bad row is kept as it was in file
+    let (_, _, spaced) =
+        Csv::decode_with_recovery("#!magictax 1\r\n  bar ,  ten\r\n").expect("Should recover");
+    assert_eq!(spaced[0].text, "  bar ,  ten");
+
+    // Text of bad row can be fixed and parsed again
+    let mut bad_row = bad_rows[1].clone();
+    assert_eq!(bad_row.parse(), Err(bad_row.error.clone()));
+    bad_row.text = bad_row.text.replace(",extra", "");
+    assert_eq!(bad_row.parse().expect("Should be valid").label, "a, b");
+    bad_row.text = "a,1\nb,2".to_string();
+    let error = bad_row.parse().expect_err("Should fail");
+    assert_eq!((error.line, error.error), (6, ParseError::MultipleRows));
+
+    // Valid file has no bad rows
+    let (_, csv, bad_rows) = Csv::decode_with_recovery("foo,1\n").expect("Should be valid");
+    assert_eq!(csv.count(), 1);
+    assert!(bad_rows.is_empty());
+
+    // Header errors cannot be recovered
+    assert!(Csv::decode_with_recovery("#!magictax 99\nfoo,1\n").is_err());
+    assert!(Csv::decode_with_recovery("#!magictax 1\n\"unclosed,1\n").is_err());
+}
+
+#[test]
+fn duplicates_are_found() {
+    let existing = Csv {
+        rows: vec![
+            dated_row("GROCER & SONS", -4520, Some(date(2023, 1, 3))),
+            dated_row("Coffee", -450, Some(date(2023, 1, 4))),
+            dated_row("Rent", -120000, Some(date(2023, 1, 1))),
+            dated_row("", 1000, None),
+        ],
+    };
+
+    // Labels are compared without case or punctuation, and extra details
+    assert!(dated_row("Grocer and Sons", -4520, Some(date(2023, 1, 4)))
+        .is_duplicate_of(&existing.rows[0]));
+    assert!(
+        dated_row("grocer sons - weekly shop", -4520, Some(date(2023, 1, 5)))
+            .is_duplicate_of(&existing.rows[0])
+    );
+    // Different value, date too far apart, or different label
+    assert!(!dated_row("GROCER & SONS", -4521, Some(date(2023, 1, 3)))
+        .is_duplicate_of(&existing.rows[0]));
+    assert!(!dated_row("GROCER & SONS", -4520, Some(date(2023, 1, 9)))
+        .is_duplicate_of(&existing.rows[0]));
+    assert!(!dated_row("Hardware store", -4520, Some(date(2023, 1, 3)))
+        .is_duplicate_of(&existing.rows[0]));
+
+    let new = [
+        dated_row("Coffee", -450, Some(date(2023, 1, 4))),
+        // Second coffee on same day is not in existing rows
+        dated_row("Coffee", -450, Some(date(2023, 1, 4))),
+        dated_row("RENT JANUARY", -120000, Some(date(2023, 1, 2))),
+        dated_row("Salary", 250000, Some(date(2023, 1, 5))),
+        dated_row("Cash", 1000, None),
+    ];
+    assert_eq!(
+        existing.find_duplicates(&new),
+        vec![Some(1), None, Some(2), None, Some(3)]
+    );
+}
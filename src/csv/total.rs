@@ -0,0 +1,40 @@
+use super::CsvRow;
+use crate::money::Money;
+
+/// Income, expense, and amount of a group of rows
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Totals {
+    /// Total of positive values
+    pub income: Money,
+    /// Total of negative values, as a positive amount
+    pub expense: Money,
+    /// Amount of rows
+    pub count: usize,
+}
+
+impl Totals {
+    /// Get income minus expense
+    pub fn net(&self) -> Money {
+        self.income - self.expense
+    }
+
+    /// Add value of row to totals
+    pub fn add(&mut self, value: Money) {
+        if value.is_negative() {
+            self.expense += value.abs();
+        } else {
+            self.income += value;
+        }
+        self.count += 1;
+    }
+}
+
+impl<'a> FromIterator<&'a CsvRow> for Totals {
+    fn from_iter<T: IntoIterator<Item = &'a CsvRow>>(rows: T) -> Self {
+        let mut totals = Self::default();
+        for row in rows {
+            totals.add(row.value);
+        }
+        totals
+    }
+}
@@ -0,0 +1,137 @@
+use std::{error::Error, fmt::Display, fs, io, path::Path, path::PathBuf};
+
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+
+use super::{report_data, Subtotals};
+use crate::{csv::Csv, csv::CsvRow, money::Money};
+
+/// Settings of report of user, saved in config folder
+///
+/// Built-in template or style is used, if path is `None`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportSettings {
+    /// Handlebars template of html report
+    pub template: Option<PathBuf>,
+    /// Css style, passed into template as `style`
+    pub style: Option<PathBuf>,
+    /// Subtotal rows of html and pdf report
+    pub subtotals: Subtotals,
+}
+
+/// Template and style to render html report with
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    /// Handlebars template
+    pub template: String,
+    /// Css style
+    pub style: String,
+}
+
+impl Default for Template {
+    /// Built-in template and style
+    fn default() -> Self {
+        Self {
+            template: include_str!("template/index.hbs").to_string(),
+            style: include_str!("template/style.css").to_string(),
+        }
+    }
+}
+
+/// Error reading or checking a custom template
+#[derive(Debug)]
+pub enum TemplateError {
+    /// File of template or style could not be read
+    Read(PathBuf, io::Error),
+    /// Template has invalid syntax
+    Invalid(Box<handlebars::TemplateError>),
+    /// Template could not be rendered, such as if it uses a value or helper which does not exist
+    Render(Box<handlebars::RenderError>),
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read(path, error) => write!(f, "Failed to read {}: {error}", path.display()),
+            Self::Invalid(error) => write!(f, "Template is not valid: {error}"),
+            Self::Render(error) => write!(f, "Template could not be rendered: {error}"),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+impl From<handlebars::TemplateError> for TemplateError {
+    fn from(error: handlebars::TemplateError) -> Self {
+        Self::Invalid(Box::new(error))
+    }
+}
+
+impl From<handlebars::RenderError> for TemplateError {
+    fn from(error: handlebars::RenderError) -> Self {
+        Self::Render(Box::new(error))
+    }
+}
+
+impl Template {
+    /// Read custom template and style of settings, using built-in template or style if path is `None`
+    ///
+    /// Template is checked before it is returned (See `Template::validate`)
+    pub fn read(settings: &ReportSettings) -> Result<Self, TemplateError> {
+        let read = |path: &Path| {
+            fs::read_to_string(path).map_err(|error| TemplateError::Read(path.to_owned(), error))
+        };
+
+        let mut template = Self::default();
+        if let Some(path) = &settings.template {
+            template.template = read(path)?;
+        }
+        if let Some(path) = &settings.style {
+            template.style = read(path)?;
+        }
+
+        template.validate()?;
+        Ok(template)
+    }
+
+    /// Check that template has valid syntax, and renders a sample report
+    ///
+    /// Sample report is rendered in strict mode, so values which do not exist (such as a typo) are errors
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        let mut hbs = Handlebars::new();
+        hbs.register_template_string("report", &self.template)?;
+
+        // Sample has a tagged and untagged row, and subtotals, so every value is used
+        let subtotals = Subtotals {
+            every: Some(1),
+            per_section: true,
+        };
+        let sample = Csv {
+            rows: vec![
+                CsvRow {
+                    label: "Sample income".to_string(),
+                    value: Money::from_cents(10000),
+                    date: None,
+                    tags: vec!["sample".to_string()],
+                },
+                CsvRow {
+                    label: "Sample expense".to_string(),
+                    value: Money::from_cents(-2500),
+                    date: None,
+                    tags: Vec::new(),
+                },
+            ],
+        };
+
+        hbs.set_strict_mode(true);
+        hbs.render(
+            "report",
+            &report_data(&sample, Some("sample.mgx"), &self.style, subtotals),
+        )?;
+        Ok(())
+    }
+}
+
+/// Name of file of report settings of user, in user config folder (See `config::path`)
+pub const SETTINGS_FILE: &str = "report.json";
@@ -1,37 +1,105 @@
 #[cfg(test)]
 mod tests;
 
+/// Settings of report, such as custom template of user
+mod custom;
+/// Write report as pdf document, without a browser
+mod pdf;
+pub use custom::{ReportSettings, Template, TemplateError, SETTINGS_FILE};
+pub use pdf::export_pdf;
+
 use chrono::Local;
 use handlebars::Handlebars;
-use serde::Serialize;
-use serde_json::json;
+use serde::{Deserialize, Serialize};
+
+use crate::csv::{Csv, CsvRow, Totals, UNTAGGED_LABEL};
+
+/// Rows to add to report tables, with totals of rows above them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Subtotals {
+    /// Add a subtotal after every amount of rows in each section, of rows since last subtotal
+    ///
+    /// `None` (or `0`) for no subtotals
+    pub every: Option<usize>,
+    /// Add a total at end of each section
+    pub per_section: bool,
+}
 
-use crate::{csv::Csv, round_to_string};
+impl Subtotals {
+    /// Returns `true` if a subtotal should be added after amount of rows since last subtotal
+    fn is_due(&self, count: usize) -> bool {
+        matches!(self.every, Some(every) if every > 0 && count >= every)
+    }
+}
 
-pub fn export_html(csv: &Csv) -> Result<String, handlebars::RenderError> {
-    // Get templates from files
-    let template = include_str!("template/index.hbs");
-    let style = include_str!("template/style.css");
+/// Convert csv to html report, with template and style
+///
+/// `file_name` is name of current file, or `None` if file is not saved yet
+///
+/// Template is checked before it is rendered, so errors are readable (See `Template::validate`)
+pub fn export_html(
+    csv: &Csv,
+    file_name: Option<&str>,
+    template: &Template,
+    subtotals: Subtotals,
+) -> Result<String, TemplateError> {
+    template.validate()?;
 
-    // Create json object to pass to template
-    let json = json!({
-        "style": style,
-        "table": csv_report(&csv),
-        "total": round_to_string(csv.sum()),
-        "date": get_today_date(),
-    });
+    // Create object to pass to template
+    let data = report_data(csv, file_name, &template.style, subtotals);
 
     // Create handlebars interface
     let mut hbs = Handlebars::new();
     hbs.set_strict_mode(false);
 
     // Render template with handlebars
-    let html = hbs.render_template(&template, &json)?;
+    let html = hbs.render_template(&template.template, &data)?;
 
     // Minify html
     Ok(minify(html))
 }
 
+/// Data passed into report template
+///
+/// This is documented in `README.md`, for custom templates, so any change must be documented there
+#[derive(Debug, Serialize)]
+struct ReportData {
+    /// Css style
+    style: String,
+    /// Name of file, such as `report.mgx`
+    file_name: Option<String>,
+    /// Today's date, as `YYYY-MM-DD`
+    date: String,
+    sections: Vec<ReportSection>,
+    /// Totals of each tag, or empty if no rows have tags
+    summary: Vec<ReportTag>,
+    /// Income, expense, net, and amount of all rows shown in report
+    totals: ReportTotals,
+    /// Sum of all rows (same as `totals.net`)
+    ///
+    /// Kept for templates written before `totals`
+    total: String,
+}
+
+/// Get data to pass into report template
+fn report_data(
+    csv: &Csv,
+    file_name: Option<&str>,
+    style: &str,
+    subtotals: Subtotals,
+) -> ReportData {
+    ReportData {
+        style: style.to_string(),
+        file_name: file_name.map(str::to_string),
+        date: get_today_date(),
+        sections: csv_sections(csv, subtotals),
+        summary: tag_summary(csv),
+        totals: ReportTotals::from(report_totals(csv)),
+        total: csv.sum().to_string(),
+    }
+}
+
 /// Get today's date as a string
 fn get_today_date() -> String {
     let today = Local::now();
@@ -42,41 +110,184 @@ fn get_today_date() -> String {
 /// Object passed into template, all values stringified
 #[derive(Debug, PartialEq, Serialize)]
 struct ReportRow {
+    date: Option<String>,
     name: Option<String>,
     income: Option<String>,
     expense: Option<String>,
+    /// Whether row is a subtotal of rows above it, rather than a row of file
+    subtotal: bool,
+}
+
+impl ReportRow {
+    /// Create subtotal row, with income and expense of totals
+    fn subtotal(name: &str, totals: &Totals) -> Self {
+        Self {
+            date: None,
+            name: Some(name.to_string()),
+            income: Some(totals.income.to_string()),
+            expense: Some(totals.expense.to_string()),
+            subtotal: true,
+        }
+    }
+}
+
+/// Totals of all rows, all values stringified
+#[derive(Debug, PartialEq, Serialize)]
+struct ReportTotals {
+    income: String,
+    expense: String,
+    net: String,
+    count: usize,
+}
+
+impl From<Totals> for ReportTotals {
+    fn from(totals: Totals) -> Self {
+        Self {
+            income: totals.income.to_string(),
+            expense: totals.expense.to_string(),
+            net: totals.net().to_string(),
+            count: totals.count,
+        }
+    }
+}
+
+/// Section of report, with all rows of one tag
+#[derive(Debug, PartialEq, Serialize)]
+struct ReportSection {
+    /// Heading of section
+    ///
+    /// `None` if no rows have tags, and the report has only one section
+    title: Option<String>,
+    /// Tag of rows in section
+    ///
+    /// `None` for rows without tags, so they are not mixed up with a tag with the same name as the heading
+    tag: Option<String>,
+    rows: Vec<ReportRow>,
+}
+
+/// Summary of one tag, all values stringified
+#[derive(Debug, PartialEq, Serialize)]
+struct ReportTag {
+    name: String,
+    income: String,
+    expense: String,
+    net: String,
+}
+
+/// Group rows into a section per tag, then a section of rows without tags
+///
+/// Rows with multiple tags are in multiple sections
+///
+/// Subtotal rows are added to each section (See `Subtotals`)
+fn csv_sections(csv: &Csv, subtotals: Subtotals) -> Vec<ReportSection> {
+    let tags = csv.tags();
+
+    // Report without tags has one section, without a title
+    if tags.is_empty() {
+        return vec![ReportSection {
+            title: None,
+            tag: None,
+            rows: csv_report(&csv.rows, subtotals),
+        }];
+    }
+
+    // Rows without tags are last, with key `None`
+    tags.into_iter()
+        .map(Some)
+        .chain([None])
+        .filter_map(|tag| {
+            let rows = csv_report(
+                csv.rows.iter().filter(|row| match tag {
+                    Some(tag) => row.has_tag(tag),
+                    None => row.tags.is_empty(),
+                }),
+                subtotals,
+            );
+            (!rows.is_empty()).then(|| ReportSection {
+                title: Some(tag.unwrap_or(UNTAGGED_LABEL).to_string()),
+                tag: tag.map(str::to_string),
+                rows,
+            })
+        })
+        .collect()
+}
+
+/// Convert totals of each tag to stringified values, for template
+///
+/// Empty if no rows have tags
+fn tag_summary(csv: &Csv) -> Vec<ReportTag> {
+    if csv.tags().is_empty() {
+        return Vec::new();
+    }
+
+    csv.totals_by_tag()
+        .iter()
+        .map(|total| ReportTag {
+            name: total.label().to_string(),
+            income: total.income.to_string(),
+            expense: total.expense.to_string(),
+            net: total.net().to_string(),
+        })
+        .collect()
+}
+
+/// Returns `true` if row is shown in report
+///
+/// Rows without a label or value are skipped, as they are empty
+fn is_shown(row: &CsvRow) -> bool {
+    !row.label.trim().is_empty() || !row.value.is_zero()
+}
+
+/// Get totals of rows shown in report, so amount of rows matches report (See `is_shown`)
+fn report_totals(csv: &Csv) -> Totals {
+    csv.rows.iter().filter(|row| is_shown(row)).collect()
 }
 
 /// Convert csv rows to stringified values, for template
-fn csv_report(csv: &Csv) -> Vec<ReportRow> {
+///
+/// Subtotal rows are added after every amount of rows, and a total at the end, if enabled
+fn csv_report<'a>(
+    rows: impl IntoIterator<Item = &'a CsvRow>,
+    subtotals: Subtotals,
+) -> Vec<ReportRow> {
     let mut report = Vec::new();
+    // Totals of rows since last subtotal, and of all rows
+    let mut group = Totals::default();
+    let mut all = Totals::default();
 
-    for row in &csv.rows {
+    for row in rows.into_iter().filter(|row| is_shown(row)) {
         let label = row.label.to_owned();
         let value = row.value;
 
-        let name = if !label.trim().is_empty() {
-            Some(label)
-        } else if value != 0.0 {
-            None
-        } else {
-            continue;
-        };
+        let name = (!label.trim().is_empty()).then_some(label);
 
         // Set income or expense, depending on sign of number value
-        let (income, expense) = if value > 0.0 {
-            (Some(round_to_string(value)), None)
-        } else if value < 0.0 {
-            (None, Some(round_to_string(0.0 - value)))
+        let (income, expense) = if value.is_positive() {
+            (Some(value.to_string()), None)
+        } else if value.is_negative() {
+            (None, Some(value.abs().to_string()))
         } else {
             (None, None)
         };
 
         report.push(ReportRow {
+            date: row.date.map(|date| date.format("%Y-%m-%d").to_string()),
             name,
             income,
             expense,
-        })
+            subtotal: false,
+        });
+
+        group.add(value);
+        all.add(value);
+        if subtotals.is_due(group.count) {
+            report.push(ReportRow::subtotal("Subtotal", &group));
+            group = Totals::default();
+        }
+    }
+
+    if subtotals.per_section && all.count > 0 {
+        report.push(ReportRow::subtotal("Total", &all));
     }
 
     report
@@ -95,7 +306,7 @@ fn minify(html: String) -> String {
     };
 
     // Minify bytes
-    let html = minify_html::minify(&html.as_bytes(), &config);
+    let html = minify_html::minify(html.as_bytes(), &config);
     // Convert back to string
     String::from_utf8_lossy(&html).to_string()
 }
@@ -0,0 +1,379 @@
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+
+use super::{csv_sections, get_today_date, report_totals, tag_summary, Subtotals};
+use crate::csv::Csv;
+
+/// Width of A4 page, in points
+const PAGE_WIDTH: f32 = 595.0;
+/// Height of A4 page, in points
+const PAGE_HEIGHT: f32 = 842.0;
+/// Space around content of page
+const MARGIN: f32 = 50.0;
+/// Space below content of page, for page number
+const FOOTER_HEIGHT: f32 = 20.0;
+
+/// Height of each table row
+const ROW_HEIGHT: f32 = 16.0;
+/// Font size of table cells
+const CELL_SIZE: f32 = 10.0;
+/// Space between text and edge of table cell
+const CELL_PADDING: f32 = 4.0;
+
+/// Name of regular font, in page resources
+const REGULAR: Name = Name(b"F1");
+/// Name of bold font, in page resources
+const BOLD: Name = Name(b"F2");
+
+/// Horizontal alignment of table column
+#[derive(Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Right,
+}
+
+/// Column of table, with width in points
+struct Column(&'static str, f32, Align);
+
+/// Columns of table of rows, matching html report
+const ROW_COLUMNS: [Column; 4] = [
+    Column("Date", 70.0, Align::Left),
+    Column("Item Name", 245.0, Align::Left),
+    Column("Income", 90.0, Align::Right),
+    Column("Expense", 90.0, Align::Right),
+];
+
+/// Columns of summary table, matching html report
+const SUMMARY_COLUMNS: [Column; 4] = [
+    Column("Tag", 225.0, Align::Left),
+    Column("Income", 90.0, Align::Right),
+    Column("Expense", 90.0, Align::Right),
+    Column("Net", 90.0, Align::Right),
+];
+
+/// Columns of table of totals of all rows
+const TOTAL_COLUMNS: [Column; 2] = [
+    Column("Totals", 225.0, Align::Left),
+    Column("Amount", 90.0, Align::Right),
+];
+
+/// Gray level of background of header rows
+const HEADER_SHADE: f32 = 0.9;
+/// Gray level of background of subtotal rows
+const SUBTOTAL_SHADE: f32 = 0.96;
+
+/// Convert csv to pdf document, with the same content as html report
+///
+/// Tables continue over multiple pages, with header row repeated on each page
+///
+/// Uses built-in Helvetica font of pdf readers, so no font is embedded
+pub fn export_pdf(csv: &Csv, subtotals: Subtotals) -> Vec<u8> {
+    let date = get_today_date();
+    let mut report = Report::new();
+
+    report.heading("MagicTax Report", 20.0);
+    report.heading(&date, 14.0);
+
+    for section in csv_sections(csv, subtotals) {
+        let rows = section
+            .rows
+            .into_iter()
+            .map(|row| {
+                let cells = [
+                    row.date.unwrap_or_default(),
+                    row.name.unwrap_or_default(),
+                    row.income.map(dollars).unwrap_or_default(),
+                    row.expense.map(dollars).unwrap_or_default(),
+                ];
+                (cells, row.subtotal)
+            })
+            .collect();
+        report.table(section.title.as_deref(), &ROW_COLUMNS, rows);
+    }
+
+    let summary = tag_summary(csv);
+    if !summary.is_empty() {
+        let rows = summary
+            .into_iter()
+            .map(|tag| {
+                let cells = [
+                    tag.name,
+                    dollars(tag.income),
+                    dollars(tag.expense),
+                    dollars(tag.net),
+                ];
+                (cells, false)
+            })
+            .collect();
+        report.table(Some("Summary"), &SUMMARY_COLUMNS, rows);
+    }
+
+    let totals = report_totals(csv);
+    let rows = vec![
+        (
+            [
+                "Total Income".to_string(),
+                dollars(totals.income.to_string()),
+            ],
+            false,
+        ),
+        (
+            [
+                "Total Expense".to_string(),
+                dollars(totals.expense.to_string()),
+            ],
+            false,
+        ),
+        (["Net".to_string(), dollars(totals.net().to_string())], true),
+        (["Entries".to_string(), totals.count.to_string()], false),
+    ];
+    report.table(None, &TOTAL_COLUMNS, rows);
+
+    report.finish(&format!("MagicTax Report {date}"))
+}
+
+/// Format amount with currency symbol, as in html report
+fn dollars(amount: String) -> String {
+    format!("${amount}")
+}
+
+/// Pages of report being laid out, from top to bottom
+struct Report {
+    /// Content of each page, excluding page number
+    pages: Vec<Content>,
+    /// Height of next element on current page, from bottom of page
+    y: f32,
+}
+
+impl Report {
+    /// Create report with one empty page
+    fn new() -> Self {
+        Self {
+            pages: vec![Content::new()],
+            y: PAGE_HEIGHT - MARGIN,
+        }
+    }
+
+    /// Current page
+    fn page(&mut self) -> &mut Content {
+        self.pages
+            .last_mut()
+            .expect("Should have at least one page")
+    }
+
+    /// Start new page, if there is not enough space for element of height on current page
+    ///
+    /// Returns `true` if a new page was started
+    fn reserve(&mut self, height: f32) -> bool {
+        if self.y - height >= MARGIN + FOOTER_HEIGHT {
+            return false;
+        }
+        self.pages.push(Content::new());
+        self.y = PAGE_HEIGHT - MARGIN;
+        true
+    }
+
+    /// Add bold heading of font size
+    fn heading(&mut self, text: &str, size: f32) {
+        let height = size * 1.6;
+        self.reserve(height);
+        let y = self.y - size;
+        show_text(self.page(), BOLD, size, MARGIN, y, text);
+        self.y -= height;
+    }
+
+    /// Add table with optional heading
+    ///
+    /// Each row has cells, and whether it is a subtotal row
+    ///
+    /// Header row is repeated at top of each page which table continues on
+    fn table<const N: usize>(
+        &mut self,
+        title: Option<&str>,
+        columns: &[Column; N],
+        rows: Vec<([String; N], bool)>,
+    ) {
+        // Keep heading with header row and first row
+        let title_height = if title.is_some() { 12.0 * 1.6 } else { 0.0 };
+        self.reserve(title_height + ROW_HEIGHT * 2.0);
+        if let Some(title) = title {
+            self.heading(title, 12.0);
+        }
+
+        let header: Vec<_> = columns.iter().map(|column| column.0.to_string()).collect();
+        self.table_row(columns, &header, Some(HEADER_SHADE));
+
+        for (cells, is_subtotal) in &rows {
+            if self.reserve(ROW_HEIGHT) {
+                self.table_row(columns, &header, Some(HEADER_SHADE));
+            }
+            let shade = is_subtotal.then_some(SUBTOTAL_SHADE);
+            self.table_row(columns, cells, shade);
+        }
+
+        // Space after table
+        self.y -= ROW_HEIGHT;
+    }
+
+    /// Add one row of table, with background of gray level, if any
+    fn table_row(&mut self, columns: &[Column], cells: &[String], shade: Option<f32>) {
+        let bottom = self.y - ROW_HEIGHT;
+        let width: f32 = columns.iter().map(|column| column.1).sum();
+        let page = self.page();
+
+        if let Some(shade) = shade {
+            page.set_fill_gray(shade);
+            page.rect(MARGIN, bottom, width, ROW_HEIGHT);
+            page.fill_nonzero();
+            page.set_fill_gray(0.0);
+        }
+
+        // Line below row
+        page.set_stroke_gray(0.75);
+        page.set_line_width(0.5);
+        page.move_to(MARGIN, bottom);
+        page.line_to(MARGIN + width, bottom);
+        page.stroke();
+
+        let mut x = MARGIN;
+        for (column, cell) in columns.iter().zip(cells) {
+            let Column(_, column_width, align) = *column;
+            let text = truncate(cell, column_width - CELL_PADDING * 2.0, CELL_SIZE);
+            let text_x = match align {
+                Align::Left => x + CELL_PADDING,
+                Align::Right => x + column_width - CELL_PADDING - text_width(&text, CELL_SIZE),
+            };
+            show_text(page, REGULAR, CELL_SIZE, text_x, bottom + 4.5, &text);
+            x += column_width;
+        }
+
+        self.y = bottom;
+    }
+
+    /// Write pdf document with all pages, and number of each page
+    fn finish(self, title: &str) -> Vec<u8> {
+        let catalog_id = Ref::new(1);
+        let page_tree_id = Ref::new(2);
+        let regular_id = Ref::new(3);
+        let bold_id = Ref::new(4);
+        let info_id = Ref::new(5);
+
+        // Each page has a page object and content stream
+        let count = self.pages.len();
+        let page_ids: Vec<_> = (0..count as i32).map(|i| Ref::new(6 + i * 2)).collect();
+
+        let mut pdf = Pdf::new();
+        pdf.catalog(catalog_id).pages(page_tree_id);
+        pdf.pages(page_tree_id)
+            .kids(page_ids.iter().copied())
+            .count(count as i32);
+
+        pdf.type1_font(regular_id)
+            .base_font(Name(b"Helvetica"))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+        pdf.type1_font(bold_id)
+            .base_font(Name(b"Helvetica-Bold"))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+
+        pdf.document_info(info_id)
+            .title(TextStr(title))
+            .producer(TextStr("MagicTax"));
+
+        for (number, (mut content, page_id)) in self.pages.into_iter().zip(page_ids).enumerate() {
+            let content_id = Ref::new(page_id.get() + 1);
+
+            let footer = format!("Page {} of {count}", number + 1);
+            let x = PAGE_WIDTH - MARGIN - text_width(&footer, 9.0);
+            show_text(&mut content, REGULAR, 9.0, x, MARGIN, &footer);
+
+            let mut page = pdf.page(page_id);
+            page.parent(page_tree_id)
+                .media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+                .contents(content_id);
+            let mut resources = page.resources();
+            resources
+                .fonts()
+                .pair(REGULAR, regular_id)
+                .pair(BOLD, bold_id);
+            resources.finish();
+            page.finish();
+
+            pdf.stream(content_id, &content.finish());
+        }
+
+        pdf.finish()
+    }
+}
+
+/// Add line of text to page, with bottom left of text at position
+fn show_text(page: &mut Content, font: Name, size: f32, x: f32, y: f32, text: &str) {
+    page.begin_text();
+    page.set_font(font, size);
+    page.next_line(x, y);
+    page.show(Str(&encode(text)));
+    page.end_text();
+}
+
+/// Shorten text with `…` to fit width, in regular font of size
+pub(super) fn truncate(text: &str, width: f32, size: f32) -> String {
+    if text_width(text, size) <= width {
+        return text.to_string();
+    }
+
+    let mut text = text.to_string();
+    while !text.is_empty() && text_width(&text, size) + text_width("…", size) > width {
+        text.pop();
+    }
+    format!("{}…", text.trim_end())
+}
+
+/// Get width of text in regular font of size
+pub(super) fn text_width(text: &str, size: f32) -> f32 {
+    let units: u32 = encode(text).into_iter().map(char_width).sum();
+    units as f32 * size / 1000.0
+}
+
+/// Convert text to WinAnsi encoding, as used by fonts of document
+///
+/// Characters which are not supported by encoding are replaced with `?`
+pub(super) fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|ch| match ch {
+            ' '..='~' => ch as u8,
+            '\u{a0}'..='\u{ff}' => ch as u32 as u8,
+            '€' => 0x80,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '–' => 0x96,
+            '—' => 0x97,
+            ch if ch.is_whitespace() => b' ',
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Widths of printable ascii characters in Helvetica, starting at space
+///
+/// In thousandths of font size, from font metrics of standard pdf fonts
+#[rustfmt::skip]
+const ASCII_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, // space to /
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, // 0 to ?
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778, // @ to O
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, // P to _
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, // ` to o
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // p to ~
+];
+
+/// Get width of WinAnsi character in Helvetica, in thousandths of font size
+///
+/// Other characters than printable ascii use an average width
+fn char_width(byte: u8) -> u32 {
+    match byte {
+        b' '..=b'~' => ASCII_WIDTHS[(byte - b' ') as usize] as u32,
+        0x85 | 0x97 => 1000,
+        _ => 556,
+    }
+}
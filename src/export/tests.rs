@@ -1,5 +1,7 @@
+use chrono::NaiveDate;
+
 use super::*;
-use crate::csv::CsvRow;
+use crate::{csv::CsvRow, money::Money};
 
 #[test]
 fn json_convert_works() {
@@ -7,64 +9,336 @@ fn json_convert_works() {
         rows: vec![
             CsvRow {
                 label: "income example".to_owned(),
-                value: 100.0,
+                value: Money::from_cents(10000),
+                date: NaiveDate::from_ymd_opt(2023, 1, 2),
+                tags: Vec::new(),
             },
             CsvRow {
                 label: "expense example".to_owned(),
-                value: -100.0,
+                value: Money::from_cents(-10000),
+                date: None,
+                tags: Vec::new(),
             },
             CsvRow {
                 label: "zero example".to_owned(),
-                value: 0.0,
+                value: Money::from_cents(0),
+                date: None,
+                tags: Vec::new(),
             },
             CsvRow {
                 label: "".to_owned(), // no label, without value
-                value: 0.0,
+                value: Money::from_cents(0),
+                date: None,
+                tags: Vec::new(),
             },
             CsvRow {
                 label: "".to_owned(), // no label, with value
-                value: 50.0,
+                value: Money::from_cents(5000),
+                date: None,
+                tags: Vec::new(),
             },
         ],
     };
 
-    let rows = csv_report(&csv);
+    let rows = csv_report(&csv.rows, Subtotals::default());
 
     for (i, row) in rows.into_iter().enumerate() {
         match i {
             0 => assert_eq!(
                 row,
                 ReportRow {
+                    date: Some("2023-01-02".to_owned()),
                     name: Some("income example".to_owned()),
                     income: Some("100".to_owned()),
                     expense: None,
+                    subtotal: false,
                 }
             ),
             1 => assert_eq!(
                 row,
                 ReportRow {
+                    date: None,
                     name: Some("expense example".to_owned()),
                     income: None,
                     expense: Some("100".to_owned()),
+                    subtotal: false,
                 }
             ),
             2 => assert_eq!(
                 row,
                 ReportRow {
+                    date: None,
                     name: Some("zero example".to_owned()),
                     income: None,
                     expense: None,
+                    subtotal: false,
                 }
             ),
             3 => assert_eq!(
                 row,
                 ReportRow {
+                    date: None,
                     name: None,
                     income: Some("50".to_owned()),
                     expense: None,
+                    subtotal: false,
                 }
             ),
             _ => panic!("row {i} should not exist"),
         }
     }
+
+    // Row without label or value is not counted, as it is not shown
+    assert_eq!(report_totals(&csv).count, 4);
+}
+
+#[test]
+fn sections_by_tag_work() {
+    let row = |label: &str, cents, tags: &[&str]| CsvRow {
+        label: label.to_owned(),
+        value: Money::from_cents(cents),
+        date: None,
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+    };
+
+    // No tags, single section without title
+    let csv = Csv {
+        rows: vec![row("a", 100, &[]), row("b", -100, &[])],
+    };
+    let sections = csv_sections(&csv, Subtotals::default());
+    assert_eq!(sections.len(), 1);
+    assert_eq!(sections[0].title, None);
+    assert_eq!(sections[0].rows.len(), 2);
+    assert!(tag_summary(&csv).is_empty());
+
+    // Section for each tag, then untagged
+    let csv = Csv {
+        rows: vec![
+            row("salary", 100000, &["work"]),
+            row("fuel", -4000, &["car", "work"]),
+            row("gift", 2000, &[]),
+        ],
+    };
+    let sections = csv_sections(&csv, Subtotals::default());
+    let titles: Vec<_> = sections
+        .iter()
+        .map(|section| section.title.as_deref())
+        .collect();
+    assert_eq!(titles, [Some("car"), Some("work"), Some(UNTAGGED_LABEL)]);
+    let names: Vec<_> = sections[1]
+        .rows
+        .iter()
+        .map(|row| row.name.as_deref())
+        .collect();
+    assert_eq!(names, [Some("salary"), Some("fuel")]);
+
+    assert_eq!(
+        tag_summary(&csv),
+        vec![
+            ReportTag {
+                name: "car".to_owned(),
+                income: "0".to_owned(),
+                expense: "40".to_owned(),
+                net: "-40".to_owned(),
+            },
+            ReportTag {
+                name: "work".to_owned(),
+                income: "1000".to_owned(),
+                expense: "40".to_owned(),
+                net: "960".to_owned(),
+            },
+            ReportTag {
+                name: UNTAGGED_LABEL.to_owned(),
+                income: "20".to_owned(),
+                expense: "0".to_owned(),
+                net: "20".to_owned(),
+            },
+        ]
+    );
+
+    // Rendered report has every section, and summary
+    let html = export_html(
+        &csv,
+        Some("report.mgx"),
+        &Template::default(),
+        Subtotals::default(),
+    )
+    .expect("Should render");
+    assert!(html.contains("report.mgx"));
+    assert!(html.contains("car"));
+    assert!(html.contains(UNTAGGED_LABEL));
+    assert!(html.contains("Summary"));
+    // Style is not escaped
+    assert!(!html.contains("&quot;"));
+
+    // Tag with same name as label of rows without tags is a separate section
+    let csv = Csv {
+        rows: vec![row("a", 100, &["Untagged"]), row("b", 200, &[])],
+    };
+    let tags: Vec<_> = csv_sections(&csv, Subtotals::default())
+        .into_iter()
+        .map(|section| (section.tag, section.rows.len()))
+        .collect();
+    assert_eq!(tags, [(Some("Untagged".to_string()), 1), (None, 1)]);
+}
+
+#[test]
+fn pdf_tables_continue_over_pages() {
+    let rows = (0..100)
+        .map(|i| CsvRow {
+            label: format!("item ({i})"),
+            value: Money::from_cents(i * 100 - 5000),
+            date: NaiveDate::from_ymd_opt(2023, 1, 2),
+            tags: Vec::new(),
+        })
+        .collect();
+    let pdf = export_pdf(&Csv { rows }, Subtotals::default());
+    let text = String::from_utf8_lossy(&pdf);
+
+    assert!(text.starts_with("%PDF-"));
+    assert!(text.contains("item (42)"));
+    assert!(text.contains("(Total Income)"));
+    assert!(text.contains("($1225)"));
+    assert!(text.contains("($1275)"));
+    assert!(text.contains("($-50)"));
+
+    // Header row is repeated on each page
+    let pages = text.matches("/Type /Page\n").count();
+    assert!(pages > 1);
+    assert_eq!(text.matches("(Item Name)").count(), pages);
+    assert!(text.contains(&format!("(Page {pages} of {pages})")));
+}
+
+#[test]
+fn pdf_text_is_shortened() {
+    assert_eq!(pdf::truncate("short", 100.0, 10.0), "short");
+
+    let long = "a very long label, which does not fit in the column";
+    let short = pdf::truncate(long, 100.0, 10.0);
+    assert!(short.ends_with('…'));
+    assert!(pdf::text_width(&short, 10.0) <= 100.0);
+
+    assert_eq!(pdf::encode("café €5 ✓"), b"caf\xe9 \x805 ?");
+}
+
+#[test]
+fn custom_templates_are_checked() {
+    let csv = Csv {
+        rows: vec![CsvRow {
+            label: "salary".to_owned(),
+            value: Money::from_cents(100000),
+            date: None,
+            tags: vec!["work".to_owned()],
+        }],
+    };
+    let custom = |template: &str| Template {
+        template: template.to_owned(),
+        style: "body > h1 { color: navy; font-family: \"Serif\"; }".to_owned(),
+    };
+
+    let html = export_html(
+        &csv,
+        Some("alice.mgx"),
+        &custom("<style>{{{style}}}</style><h1>{{file_name}}</h1>{{#each summary}}<p>{{name}}: {{net}}</p>{{/each}}"),
+        Subtotals::default(),
+    )
+    .expect("Should render");
+    assert!(html.contains("navy"));
+    assert!(!html.contains("&gt;") && !html.contains("&quot;"));
+    assert!(html.contains("alice.mgx"));
+    assert!(html.contains("work: 1000"));
+
+    // Invalid syntax
+    let result = export_html(
+        &csv,
+        None,
+        &custom("{{#each sections}}"),
+        Subtotals::default(),
+    );
+    assert!(matches!(result, Err(TemplateError::Invalid(_))));
+
+    // Value which does not exist
+    let result = export_html(&csv, None, &custom("{{totl}}"), Subtotals::default());
+    assert!(matches!(result, Err(TemplateError::Render(_))));
+
+    // Missing file
+    let settings = ReportSettings {
+        template: Some("/does/not/exist.hbs".into()),
+        ..ReportSettings::default()
+    };
+    assert!(matches!(
+        Template::read(&settings),
+        Err(TemplateError::Read(..))
+    ));
+    assert_eq!(
+        Template::read(&ReportSettings::default()).unwrap(),
+        Template::default()
+    );
+}
+
+#[test]
+fn subtotals_are_added() {
+    let csv = Csv {
+        rows: [1000, -200, 300, -400, 500]
+            .into_iter()
+            .map(|cents| CsvRow {
+                label: "item".to_owned(),
+                value: Money::from_cents(cents),
+                date: None,
+                tags: Vec::new(),
+            })
+            .collect(),
+    };
+    fn subtotals(rows: &[ReportRow]) -> Vec<(&str, &str, &str)> {
+        rows.iter()
+            .filter(|row| row.subtotal)
+            .map(|row| {
+                (
+                    row.name.as_deref().unwrap(),
+                    row.income.as_deref().unwrap(),
+                    row.expense.as_deref().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    // No subtotals by default
+    let rows = csv_report(&csv.rows, Subtotals::default());
+    assert_eq!(rows.len(), 5);
+
+    // Subtotal of each 2 rows, then total of section
+    let rows = csv_report(
+        &csv.rows,
+        Subtotals {
+            every: Some(2),
+            per_section: true,
+        },
+    );
+    assert_eq!(rows.len(), 8);
+    assert!(rows[2].subtotal && rows[5].subtotal && rows[7].subtotal);
+    assert_eq!(
+        subtotals(&rows),
+        [
+            ("Subtotal", "10", "2"),
+            ("Subtotal", "3", "4"),
+            ("Total", "18", "6"),
+        ]
+    );
+
+    // Zero is the same as no subtotals
+    let rows = csv_report(
+        &csv.rows,
+        Subtotals {
+            every: Some(0),
+            per_section: false,
+        },
+    );
+    assert_eq!(rows.len(), 5);
+
+    // Footer has separate income and expense
+    let html =
+        export_html(&csv, None, &Template::default(), Subtotals::default()).expect("Should render");
+    assert!(html.contains("$18"));
+    assert!(html.contains("$6"));
+    assert!(html.contains("$12"));
 }
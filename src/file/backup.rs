@@ -0,0 +1,144 @@
+use std::{fs, io, path::PathBuf};
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Default amount of backups to keep of each file
+pub const DEFAULT_COUNT: usize = 5;
+
+/// Name of file of backup settings of user, in user config folder (See `config::path`)
+pub const SETTINGS_FILE: &str = "backups.json";
+
+/// Backup settings of user, saved in config folder
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    /// Amount of backups to keep of each file
+    pub count: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            count: DEFAULT_COUNT,
+        }
+    }
+}
+
+/// Format of time in backup file names
+///
+/// Sorts oldest first, and has no characters which are invalid in file names
+const TIME_FORMAT: &str = "%Y-%m-%dT%H-%M-%S%.3f";
+
+/// Extension of backup files
+const EXTENSION: &str = "mgx";
+
+/// Previous version of a file, saved before it was replaced
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backup {
+    /// Path to backup file
+    pub path: String,
+    /// Time when file was replaced
+    pub time: DateTime<Utc>,
+}
+
+/// Get folder of backups of file, next to file
+fn folder(path: &str) -> PathBuf {
+    PathBuf::from(format!("{path}.backups"))
+}
+
+/// Copy file at path to backup folder
+///
+/// Nothing is done if file does not exist yet, or `count` is 0
+///
+/// Existing backups are never replaced. If a backup was made at the same time, a number is added after the time
+pub fn create(path: &str, count: usize) -> io::Result<()> {
+    if count == 0 || !PathBuf::from(path).is_file() {
+        return Ok(());
+    }
+
+    let folder = folder(path);
+    fs::create_dir_all(&folder)?;
+
+    let time = Utc::now().format(TIME_FORMAT).to_string();
+    let mut number = 0;
+    loop {
+        let name = match number {
+            0 => format!("{time}.{EXTENSION}"),
+            _ => format!("{time}_{number}.{EXTENSION}"),
+        };
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(folder.join(name))
+        {
+            Ok(mut backup) => {
+                io::copy(&mut fs::File::open(path)?, &mut backup)?;
+                return Ok(());
+            }
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => number += 1,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Remove oldest backups of file at path, to keep `count` backups
+///
+/// Nothing is done if `count` is 0, as backups are turned off, and existing backups are kept
+pub fn rotate(path: &str, count: usize) -> io::Result<()> {
+    if count == 0 {
+        return Ok(());
+    }
+    for backup in list(path)?.into_iter().skip(count) {
+        fs::remove_file(backup.path)?;
+    }
+    Ok(())
+}
+
+/// Get backups of file at path, newest first
+///
+/// Files in backup folder which are not backups are ignored
+pub fn list(path: &str) -> io::Result<Vec<Backup>> {
+    let entries = match fs::read_dir(folder(path)) {
+        Ok(entries) => entries,
+        // No backups were made yet
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+
+    let mut backups = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+
+        // Get time from file name
+        let Some(name) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(&format!(".{EXTENSION}")))
+        else {
+            continue;
+        };
+        // Backups made at the same time have a number after time (See `create`)
+        let (name, number) = match name.split_once('_') {
+            Some((name, number)) => match number.parse::<usize>() {
+                Ok(number) => (name, number),
+                Err(_) => continue,
+            },
+            None => (name, 0),
+        };
+        let Ok(time) = NaiveDateTime::parse_from_str(name, TIME_FORMAT) else {
+            continue;
+        };
+
+        backups.push((
+            Backup {
+                path: path.display().to_string(),
+                time: Utc.from_utc_datetime(&time),
+            },
+            number,
+        ));
+    }
+
+    backups.sort_by_key(|(backup, number)| std::cmp::Reverse((backup.time, *number)));
+    Ok(backups.into_iter().map(|(backup, _)| backup).collect())
+}
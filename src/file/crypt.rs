@@ -0,0 +1,85 @@
+use argon2::Argon2;
+use cocoon::{Cocoon, MiniCocoon};
+use rand::RngCore;
+
+use super::{FileError, FileResult};
+
+/// Prefix of every file encrypted with a password
+///
+/// Files without this prefix were encrypted with `LEGACY_KEY`
+const MAGIC: &[u8; 4] = b"MGX\x01";
+
+/// Length of random salt, stored after `MAGIC`
+const SALT_LEN: usize = 16;
+
+/// Length of key derived from password
+const KEY_LEN: usize = 32;
+
+/// Cryption key which every file used, before passwords were added
+///
+/// Only used to open old files. Files are never saved with this key
+const LEGACY_KEY: &str = "super-secure-encryption-key";
+
+/// Returns `true` if data was encrypted with `LEGACY_KEY`, and not a password
+pub fn is_legacy(data: &[u8]) -> bool {
+    !data.starts_with(MAGIC)
+}
+
+/// Encrypt data with password
+///
+/// Output contains `MAGIC`, a new random salt, and the encrypted container
+pub fn encrypt(data: Vec<u8>, password: &str) -> FileResult<Vec<u8>> {
+    let mut rng = rand::thread_rng();
+
+    // New salt every save, so the same password never gives the same key twice
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+    let mut seed = [0u8; 32];
+    rng.fill_bytes(&mut seed);
+
+    let key = derive_key(password, &salt)?;
+    let cocoon = MiniCocoon::from_key(&key, &seed);
+
+    let mut output = Vec::new();
+    output.extend_from_slice(MAGIC);
+    output.extend_from_slice(&salt);
+    cocoon.dump(data, &mut output).map_err(FileError::Crypto)?;
+
+    Ok(output)
+}
+
+/// Decrypt data which was encrypted with `encrypt`
+///
+/// Returns `FileError::LegacyKey` if data was not encrypted with a password
+pub fn decrypt(data: &[u8], password: &str) -> FileResult<Vec<u8>> {
+    if is_legacy(data) {
+        return Err(FileError::LegacyKey);
+    }
+
+    let data = &data[MAGIC.len()..];
+    if data.len() < SALT_LEN {
+        return Err(FileError::Crypto(cocoon::Error::TooShort));
+    }
+    let (salt, mut container) = data.split_at(SALT_LEN);
+
+    let key = derive_key(password, salt)?;
+    // Seed is only used for encryption
+    let cocoon = MiniCocoon::from_key(&key, &[0; 32]);
+
+    cocoon.parse(&mut container).map_err(FileError::Crypto)
+}
+
+/// Decrypt data which was encrypted with `LEGACY_KEY`
+pub fn decrypt_legacy(mut data: &[u8]) -> FileResult<Vec<u8>> {
+    let cocoon = Cocoon::new(LEGACY_KEY.as_bytes());
+    cocoon.parse(&mut data).map_err(FileError::Crypto)
+}
+
+/// Derive encryption key from password and salt, with Argon2id
+fn derive_key(password: &str, salt: &[u8]) -> FileResult<[u8; KEY_LEN]> {
+    let mut key = [0u8; KEY_LEN];
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(FileError::KeyDerivation)?;
+    Ok(key)
+}
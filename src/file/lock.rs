@@ -0,0 +1,220 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Time after last refresh, when lock is considered stale (left by a crash)
+///
+/// Lock should be refreshed more often than this, while file is open
+fn timeout() -> Duration {
+    Duration::minutes(5)
+}
+
+/// Lock file next to file, showing that it is open in a program instance
+///
+/// Lock file is removed when dropped
+#[derive(Debug)]
+pub struct Lock {
+    /// Path to lock file
+    path: PathBuf,
+}
+
+/// Program instance which has file open
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockOwner {
+    /// Process ID of program instance
+    ///
+    /// `None` if lock file is not valid, such as while it is being written
+    pub pid: Option<u32>,
+    /// Last time lock was refreshed
+    pub time: DateTime<Utc>,
+    /// Name of computer of program instance
+    ///
+    /// `None` if unknown, such as for lock files of older versions, or on platforms where it is not read
+    pub host: Option<String>,
+}
+
+impl LockOwner {
+    /// Owner of this program instance, now
+    fn current() -> Self {
+        Self {
+            pid: Some(process::id()),
+            time: Utc::now(),
+            host: host_name(),
+        }
+    }
+
+    /// Get contents of lock file
+    fn contents(&self) -> String {
+        let pid = self.pid.map(|pid| pid.to_string()).unwrap_or_default();
+        let host = self.host.as_deref().unwrap_or_default();
+        format!("{pid}\n{}\n{host}\n", self.time.to_rfc3339())
+    }
+
+    /// Parse owner from contents of lock file
+    ///
+    /// `None` if lock file is not valid (program may have crashed while writing it)
+    fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines().map(str::trim);
+        Some(Self {
+            pid: Some(lines.next()?.parse().ok()?),
+            time: DateTime::parse_from_rfc3339(lines.next()?)
+                .ok()?
+                .with_timezone(&Utc),
+            // Older versions did not write host
+            host: lines
+                .next()
+                .filter(|host| !host.is_empty())
+                .map(String::from),
+        })
+    }
+
+    /// Returns `true` if program instance most likely crashed
+    ///
+    /// Lock is stale if it was not refreshed in time
+    /// If instance is on this computer, lock is also stale if its process is not running
+    ///
+    /// Process ID may be reused by another process after a crash, so a running process does not keep lock
+    fn is_stale(&self) -> bool {
+        if let (Some(pid), Some(host)) = (self.pid, &self.host) {
+            if host_name().as_ref() == Some(host) && !is_running(pid) {
+                return true;
+            }
+        }
+        Utc::now() - self.time > timeout()
+    }
+}
+
+/// Error taking lock of file
+#[derive(Debug)]
+pub enum LockError {
+    /// File is open in another program instance
+    Locked(LockOwner),
+    Io(io::Error),
+}
+
+/// Get path of lock file, next to file
+fn lock_path(path: &str) -> PathBuf {
+    PathBuf::from(format!("{path}.lock"))
+}
+
+impl Lock {
+    /// Create lock file for file at path
+    ///
+    /// Stale lock files (left by a crash) are replaced
+    ///
+    /// Returns `LockError::Locked` if file is open in another program instance
+    pub fn acquire(path: &str) -> Result<Self, LockError> {
+        let path = lock_path(path);
+
+        // Try again once, after removing stale lock
+        for _ in 0..2 {
+            // Only create if lock file does not exist, so 2 instances cannot both succeed
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    file.write_all(LockOwner::current().contents().as_bytes())
+                        .map_err(LockError::Io)?;
+                    return Ok(Self { path });
+                }
+
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                    if let Some(owner) = read_owner(&path).map_err(LockError::Io)? {
+                        return Err(LockError::Locked(owner));
+                    }
+                    remove_stale(&path).map_err(LockError::Io)?;
+                }
+
+                Err(error) => return Err(LockError::Io(error)),
+            }
+        }
+
+        Err(LockError::Io(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "Lock file was created again while removing stale lock",
+        )))
+    }
+
+    /// Update time of lock, so it does not become stale
+    ///
+    /// Lock file is replaced in one step, so other instances never read a partly written lock
+    pub fn refresh(&self) -> io::Result<()> {
+        super::write_atomic(&self.path, LockOwner::current().contents().as_bytes())
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // Do not remove lock of another instance, if lock was replaced
+        if let Ok(Some(owner)) = read_owner(&self.path) {
+            if owner.pid != Some(process::id()) {
+                return;
+            }
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Get program instance which has file at path open
+///
+/// `None` if file is not locked, or lock is stale
+pub fn owner(path: &str) -> io::Result<Option<LockOwner>> {
+    read_owner(&lock_path(path))
+}
+
+/// Read owner of lock file
+///
+/// `None` if lock file does not exist, or is stale
+fn read_owner(path: &Path) -> io::Result<Option<LockOwner>> {
+    let owner = match fs::read_to_string(path) {
+        Ok(text) => match LockOwner::parse(&text) {
+            Some(owner) => owner,
+            // Lock file may still be being written by another instance
+            // Use time of file, so it only becomes stale if it is never written
+            None => LockOwner {
+                pid: None,
+                time: fs::metadata(path)?.modified()?.into(),
+                host: None,
+            },
+        },
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+
+    Ok(Some(owner).filter(|owner| !owner.is_stale()))
+}
+
+/// Remove lock file left by a crash
+fn remove_stale(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// Get name of this computer, to check whether owner of lock is on this computer
+///
+/// `None` if process IDs cannot be checked on this platform (See `is_running`)
+fn host_name() -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Returns `true` if process with ID is running on this computer
+///
+/// Only works on Linux (See `host_name`)
+fn is_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
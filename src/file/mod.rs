@@ -1,19 +1,84 @@
 #[cfg(test)]
 mod tests;
 
-use std::{error::Error, fmt::Display, fs, io};
-
-use cocoon::Cocoon;
-
-use crate::csv::{self, Csv};
+/// Keep previous versions of files
+mod backup;
+/// Encrypt and decrypt file data with a password
+mod crypt;
+/// Stop 2 program instances from editing the same file
+mod lock;
+/// Autosave unsaved files, to recover after a crash
+mod recovery;
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    error::Error,
+    fmt::Display,
+    fs,
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    path::Path,
+    sync::Arc,
+    time::SystemTime,
+};
+
+use self::lock::{Lock, LockError};
+pub use self::{
+    backup::{Backup, BackupSettings, SETTINGS_FILE as BACKUP_SETTINGS_FILE},
+    lock::LockOwner,
+    recovery::{
+        default_folder as default_recovery_folder, session_key, session_name, Snapshot,
+        SnapshotLock,
+    },
+};
+use crate::csv::{self, BadRow, Csv, Header};
 
 type FileResult<T> = Result<T, FileError>;
 
+/// How to open a file, which may be open in another program instance
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LockMode {
+    /// Lock file, or fail with `FileError::Locked` if it is open in another program instance
+    #[default]
+    Lock,
+    /// Do not lock file, and do not allow saving over it
+    ReadOnly,
+    /// Do not lock file, and allow saving over changes of other program instance
+    Ignore,
+}
+
 //todo comments
 #[derive(Debug)]
 pub enum FileError {
     Crypto(cocoon::Error),
-    CsvParse(csv::ParseError),
+    /// File was decrypted, but a row could not be parsed
+    ///
+    /// Open with `File::open_path_and_recover` to skip bad rows
+    CsvParse(csv::LineError),
+    /// Key could not be derived from password
+    KeyDerivation(argon2::Error),
+    /// File was encrypted with the old built-in key, not a password
+    ///
+    /// Open with `File::open_path_and_decrypt_legacy`
+    LegacyKey,
+    /// Previous version of file could not be backed up
+    Backup(io::Error),
+    /// File is open in another program instance
+    ///
+    /// Open with `LockMode::ReadOnly` or `LockMode::Ignore` to open anyway
+    Locked(LockOwner),
+    /// File has no password to encrypt it with yet
+    NoPassword,
+    /// File was opened read-only, and cannot be saved over
+    ReadOnly,
+    /// File is not registered on file system (was never saved)
+    Unregistered,
+    /// File was saved in a newer format version
+    UnsupportedVersion(u32),
+    /// File on disk was changed by another program, since it was opened or saved
+    ChangedOnDisk,
+    /// File could not be read or written
+    Io(io::Error),
 }
 
 impl Display for FileError {
@@ -23,12 +88,50 @@ impl Display for FileError {
         match self {
             FileError::CsvParse(error) => write!(f, "Failed to parse csv: {error}"),
 
+            FileError::KeyDerivation(error) => write!(f, "Failed to use password: {error}"),
+
+            FileError::LegacyKey => write!(
+                f,
+                "This file was saved with the old built-in key, and has no password"
+            ),
+
+            FileError::Backup(error) => write!(f, "Failed to back up previous version of file: {error}"),
+
+            FileError::Locked(owner) => {
+                write!(f, "This file is open in another MagicTax window")?;
+                if let Some(pid) = owner.pid {
+                    write!(f, " (process {pid})")?;
+                }
+                Ok(())
+            }
+
+            FileError::NoPassword => write!(f, "File has no password yet"),
+
+            FileError::ReadOnly => write!(
+                f,
+                "This file was opened read-only. Use Save As to save a copy"
+            ),
+
+            FileError::Unregistered => write!(f, "File has not been saved yet"),
+
+            FileError::ChangedOnDisk => write!(
+                f,
+                "This file was changed by another program. Save or reload it first"
+            ),
+
+            FileError::Io(error) => write!(f, "Failed to read or write file: {error}"),
+
+            FileError::UnsupportedVersion(version) => write!(
+                f,
+                "This file was saved with a newer version of MagicTax (file format version {version}). Please update MagicTax to open it"
+            ),
+
             FileError::Crypto(error) => write!(
                 f,
                 "{}",
                 match error {
                     Cryptography => {
-                        "Invalid password for file. This file is not accessible with this program"
+                        "Incorrect password for file"
                     }
 
                     UnrecognizedFormat => "Unrecognized file type or format",
@@ -68,8 +171,53 @@ pub struct File {
     path: Option<String>,
     /// Contents of file
     contents: Csv,
+    /// Format version and metadata of file
+    header: Header,
     /// Whether file is saved
     saved: bool,
+    /// Password to encrypt file with
+    ///
+    /// `None` if no password was given yet (new file, or legacy file)
+    password: Option<String>,
+    /// Lock file, while file is open
+    ///
+    /// Shared with clones, and removed when last clone is dropped
+    ///
+    /// `None` if file is not registered, or was opened without lock
+    lock: Option<Arc<Lock>>,
+    /// Whether file cannot be saved over, as it is open in another program instance
+    read_only: bool,
+    /// State of file on disk, when it was last opened or saved
+    ///
+    /// `None` if file was never opened or saved
+    disk_state: Option<DiskState>,
+}
+
+/// State of file on disk, to check if it was changed by another program
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiskState {
+    /// Modified time of file
+    modified: Option<SystemTime>,
+    /// Length of file, in bytes
+    len: u64,
+    /// Hash of encrypted data of file
+    hash: u64,
+}
+
+impl DiskState {
+    /// Get state of file at path, which contains data
+    fn new(path: &str, data: &[u8]) -> Self {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+
+        Self {
+            modified: fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+            len: data.len() as u64,
+            hash: hasher.finish(),
+        }
+    }
 }
 
 impl File {
@@ -124,6 +272,79 @@ impl File {
         self.saved = true;
     }
 
+    /// Get state of file on disk, when it was last opened or saved
+    pub fn disk_state(&self) -> Option<&DiskState> {
+        self.disk_state.as_ref()
+    }
+
+    /// Set state of file on disk
+    ///
+    /// This should only be run after saving a clone of this file with `save_to_path_encrypted`,
+    ///     which updated the state of the clone
+    pub fn set_disk_state(&mut self, disk_state: Option<DiskState>) {
+        self.disk_state = disk_state;
+    }
+
+    /// Returns `true` if file on disk was changed by another program, since it was opened or saved
+    ///
+    /// Returns `false` if file is not registered, or no longer exists
+    pub fn is_changed_on_disk(&self) -> FileResult<bool> {
+        let (Some(path), Some(state)) = (&self.path, &self.disk_state) else {
+            return Ok(false);
+        };
+
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            // Nothing to overwrite
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(error) => return Err(FileError::Crypto(cocoon::Error::Io(error))),
+        };
+
+        // Compare contents only if time or length changed
+        if state.modified.is_some()
+            && metadata.modified().ok() == state.modified
+            && metadata.len() == state.len
+        {
+            return Ok(false);
+        }
+        let data = read_path(path)?;
+        Ok(DiskState::new(path, &data).hash != state.hash)
+    }
+
+    /// Read file from disk again, discarding unsaved changes
+    ///
+    /// Path, password, and lock are kept
+    pub fn reload(&mut self) -> FileResult<()> {
+        let Some(path) = &self.path else {
+            return Err(FileError::Unregistered);
+        };
+
+        let file = Self::open_path_with_mode(path, self.password.as_deref(), LockMode::Ignore)?;
+        self.contents = file.contents;
+        self.header = file.header;
+        self.disk_state = file.disk_state;
+        self.saved = true;
+        Ok(())
+    }
+
+    /// Get format version and metadata as reference
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Get format version and metadata as mutable reference
+    pub fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
+    /// Set format version and metadata
+    ///
+    /// This should only be run after saving a clone of this file with `save_to_path_encrypted`,
+    ///     which updated the header of the clone
+    pub fn set_header(&mut self, header: Header) {
+        self.header = header;
+    }
+
     /// Get filepath as reference
     ///
     /// `None` if file is not registered on file system (was never saved)
@@ -132,73 +353,443 @@ impl File {
     }
 
     /// Set filepath
-    pub fn set_path(&mut self, path: impl Into<String>) {
-        self.path = Some(path.into())
+    ///
+    /// Locks file at new path, and allows saving over it
+    ///
+    /// Returns `FileError::Locked` if file at path is open in another program instance, and path is not changed
+    pub fn set_path(&mut self, path: impl Into<String>) -> FileResult<()> {
+        let path = path.into();
+        let same_path = self.path.as_ref() == Some(&path);
+
+        if !same_path || self.lock.is_none() {
+            let lock = take_lock(&path, LockMode::Lock)?;
+            if !same_path {
+                // State of old file does not apply to new file
+                self.disk_state = None;
+            }
+            self.lock = lock;
+        }
+        self.read_only = false;
+        self.path = Some(path);
+        Ok(())
+    }
+
+    /// Returns `true` if file was opened read-only, as it is open in another program instance
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Update lock file, so it does not become stale
+    ///
+    /// Should be run regularly while file is open
+    pub fn refresh_lock(&self) -> FileResult<()> {
+        match &self.lock {
+            Some(lock) => lock
+                .refresh()
+                .map_err(|error| FileError::Crypto(cocoon::Error::Io(error))),
+            None => Ok(()),
+        }
+    }
+
+    /// Get program instance which has file at path open
+    ///
+    /// `None` if file is not open anywhere else, or was left open by a crash
+    pub fn path_lock_owner(path: &str) -> FileResult<Option<LockOwner>> {
+        lock::owner(path).map_err(|error| FileError::Crypto(cocoon::Error::Io(error)))
+    }
+
+    /// Get password as reference
+    ///
+    /// `None` if no password was given yet (new file, or legacy file)
+    pub fn password(&self) -> Option<&String> {
+        self.password.as_ref()
+    }
+
+    /// Set password, used on next save
+    pub fn set_password(&mut self, password: impl Into<String>) {
+        self.password = Some(password.into())
     }
 
     /// Save encrypted file to given path
     ///
+    /// Key is derived from password, with a new random salt
+    ///
     /// Sets save state to saved
-    pub fn save_to_path_encrypted(&mut self, path: &str, key: &str) -> FileResult<()> {
-        // Create encryptor
-        let cocoon = Cocoon::new(key.as_bytes());
+    ///
+    /// Returns `FileError::ReadOnly` if file was opened read-only, and path is not changed
+    pub fn save_to_path_encrypted(&mut self, path: &str, password: &str) -> FileResult<()> {
+        if self.read_only && self.path.as_deref() == Some(path) {
+            return Err(FileError::ReadOnly);
+        }
 
         // Get content as bytes
-        let bytes = self.contents.encode().into_bytes().to_vec();
+        // Update version and modified time, which is only kept if file is written
+        let mut header = self.header.clone();
+        header.touch();
+        let bytes = self.contents.encode_with_header(&header).into_bytes();
 
-        // Open file (creates new if not already existing)
-        let mut file = fs::File::create(path).map_err(|error| {
+        // Encrypt data
+        let data = crypt::encrypt(bytes, password)?;
+
+        // Write encrypted data to file (creates new if not already existing)
+        // Old file is only replaced once new file is fully written
+        write_atomic(path, &data).map_err(|error| {
             // Return an IO error if failed
             FileError::Crypto(cocoon::Error::Io(error))
         })?;
 
-        // Write encrypted data to file
-        cocoon
-            .dump(bytes, &mut file)
-            .map_err(FileError::Crypto)?;
-
+        self.header = header;
         self.saved = true;
+        self.disk_state = Some(DiskState::new(path, &data));
         Ok(())
     }
 
-    /// Open encrypted file from given path
+    /// Change password of saved file
     ///
-    /// Returns saved `File` with contents and associated path
-    pub fn open_path_and_decrypt(path: impl Into<String>, key: &str) -> FileResult<Self> {
-        let path = path.into();
+    /// Decrypts file on disk with old password (or old built-in key, if `None`),
+    ///     and encrypts it again with new password
+    ///
+    /// Unsaved changes are not written, and save state does not change
+    ///
+    /// Previous version is backed up like when saving (See `File::backup_path`), keeping `backup_count` backups
+    ///
+    /// Returns `FileError::ReadOnly` if file was opened read-only, `FileError::Locked` if it is open in another program instance,
+    ///     and `FileError::ChangedOnDisk` if it was changed by another program
+    pub fn change_password(
+        &mut self,
+        old_password: Option<&str>,
+        new_password: &str,
+        backup_count: usize,
+    ) -> FileResult<()> {
+        let Some(path) = self.path.clone() else {
+            return Err(FileError::Unregistered);
+        };
+
+        // Other instance would save with old password
+        if self.read_only {
+            return Err(FileError::ReadOnly);
+        }
+        if self.lock.is_none() {
+            if let Some(owner) = Self::path_lock_owner(&path)? {
+                return Err(FileError::Locked(owner));
+            }
+        }
+        if self.is_changed_on_disk()? {
+            return Err(FileError::ChangedOnDisk);
+        }
 
-        // Create decryptor
-        let cocoon = Cocoon::new(key.as_bytes());
+        // Decrypt with old secret
+        let data = fs::read(&path).map_err(FileError::Io)?;
+        let bytes = match old_password {
+            Some(old_password) => crypt::decrypt(&data, old_password)?,
+            None => crypt::decrypt_legacy(&data)?,
+        };
 
-        // Open existing file
-        let mut file = fs::File::open(&path).map_err(|error| {
-            // Return an IO error if failed
-            FileError::Crypto(cocoon::Error::Io(error))
-        })?;
+        // Encrypt with new password, and replace file
+        let data = crypt::encrypt(bytes, new_password)?;
+        Self::backup_path(&path, backup_count)?;
+        write_atomic(&path, &data).map_err(FileError::Io)?;
 
-        // Decrypt data (bytes) from file
-        let bytes = cocoon
-            .parse(&mut file)
-            .map_err( FileError::Crypto)?;
-
-        // Convert bytes to string
-        // This may fail, if bytes do not form a valid utf8 string
-        let contents = String::from_utf8(bytes).map_err(|error| {
-            // Bytes-to-string conversion failed
-            // Return IO error of 'Invalid Data'
-            FileError::Crypto(cocoon::Error::from(io::Error::new(
-                io::ErrorKind::InvalidData,
-                error,
-            )))
-        })?;
+        self.disk_state = Some(DiskState::new(&path, &data));
+        self.password = Some(new_password.to_string());
+
+        // Oldest backups are only removed once file is written
+        Self::rotate_backups(&path, backup_count)
+    }
+
+    /// Returns `true` if file at given path was encrypted with the old built-in key
+    ///
+    /// Use `open_path_and_decrypt_legacy` to open these files
+    pub fn path_uses_legacy_key(path: &str) -> FileResult<bool> {
+        let data = read_path(path)?;
+        Ok(crypt::is_legacy(&data))
+    }
 
-        // Parse contents from CSV format
-        let contents = Csv::decode(&contents).map_err(FileError::CsvParse)?;
+    /// Open encrypted file from given path, with password, and lock it
+    ///
+    /// Returns saved `File` with contents, associated path, and password
+    ///
+    /// Returns `FileError::LegacyKey` if file was encrypted with the old built-in key
+    ///
+    /// Returns `FileError::Locked` if file is open in another program instance
+    pub fn open_path_and_decrypt(path: impl Into<String>, password: &str) -> FileResult<Self> {
+        Self::open_path_with_mode(path, Some(password), LockMode::Lock)
+    }
+
+    /// Open file from given path, which was encrypted with the old built-in key, and lock it
+    ///
+    /// Returned file has no password, so a new one must be set before saving
+    pub fn open_path_and_decrypt_legacy(path: impl Into<String>) -> FileResult<Self> {
+        Self::open_path_with_mode(path, None, LockMode::Lock)
+    }
+
+    /// Open file from given path, with password (or old built-in key, if `None`)
+    ///
+    /// Lock mode decides what happens if file is open in another program instance
+    pub fn open_path_with_mode(
+        path: impl Into<String>,
+        password: Option<&str>,
+        mode: LockMode,
+    ) -> FileResult<Self> {
+        let path = path.into();
+        let lock = take_lock(&path, mode)?;
+
+        // Decrypt data (bytes) from file
+        let data = read_path(&path)?;
+        let bytes = match password {
+            Some(password) => crypt::decrypt(&data, password)?,
+            None => crypt::decrypt_legacy(&data)?,
+        };
+        let (header, contents) = parse_bytes(bytes)?;
 
         Ok(Self {
             contents,
+            header,
+            disk_state: Some(DiskState::new(&path, &data)),
             path: Some(path),
             saved: true,
+            password: password.map(str::to_string),
+            lock,
+            read_only: mode == LockMode::ReadOnly,
         })
     }
+
+    /// Copy file at path to its backup folder
+    ///
+    /// Should be run before saving over file, then `File::rotate_backups` after save succeeds
+    ///
+    /// Nothing is done if file does not exist yet, or `count` is 0
+    pub fn backup_path(path: &str, count: usize) -> FileResult<()> {
+        backup::create(path, count).map_err(FileError::Backup)
+    }
+
+    /// Remove oldest backups of file at path, keeping only the newest `count` backups
+    ///
+    /// Should be run after saving over file, so no backups are lost if save fails
+    ///
+    /// Nothing is done if `count` is 0
+    pub fn rotate_backups(path: &str, count: usize) -> FileResult<()> {
+        backup::rotate(path, count).map_err(FileError::Backup)
+    }
+
+    /// Get backups of file at path, newest first
+    pub fn list_backups(path: &str) -> FileResult<Vec<Backup>> {
+        backup::list(path).map_err(FileError::Backup)
+    }
+
+    /// Open backup of file, with password (or old built-in key, if `None`)
+    ///
+    /// Returned file is unregistered, so it cannot be saved over backup by accident
+    pub fn open_backup(backup: &Backup, password: Option<&str>) -> FileResult<Self> {
+        let mut file = Self::open_path_with_mode(&backup.path, password, LockMode::Ignore)?;
+        file.path = None;
+        file.password = None;
+        Ok(file)
+    }
+
+    /// Encrypt file for a snapshot, to save with `File::write_snapshot`
+    ///
+    /// Encrypting is slow, so it is separate from writing, which can then be synchronized with removing the snapshot
+    ///
+    /// Files without a password yet are encrypted with `session_key` (See `recovery::session_key`)
+    pub fn encrypt_snapshot(&self, session_key: &str) -> FileResult<Vec<u8>> {
+        let key = self.password.as_deref().unwrap_or(session_key);
+        let bytes = self.contents.encode_with_header(&self.header).into_bytes();
+        crypt::encrypt(bytes, key)
+    }
+
+    /// Save encrypted snapshot of file in recovery folder, replacing previous snapshot with same name
+    ///
+    /// If file has no password, `session_key` is saved next to snapshot, so it can be restored without one
+    ///
+    /// Unlike saving, this does not change save state or header
+    pub fn write_snapshot(
+        &self,
+        folder: &Path,
+        name: &str,
+        data: &[u8],
+        session_key: &str,
+    ) -> FileResult<()> {
+        let path = recovery::snapshot_path(folder, name);
+        let key = self.password.is_none().then_some(session_key);
+        fs::create_dir_all(folder)
+            .and_then(|()| write_atomic(&path, data))
+            .and_then(|()| recovery::write_file_path(&path, self.path.as_deref()))
+            .and_then(|()| recovery::write_key(&path, key))
+            .map_err(|error| FileError::Crypto(cocoon::Error::Io(error)))
+    }
+
+    /// Delete snapshot with name from recovery folder, if it exists
+    ///
+    /// Should be run when file is saved or closed
+    pub fn remove_snapshot(folder: &Path, name: &str) -> FileResult<()> {
+        recovery::remove(&recovery::snapshot_path(folder, name))
+            .map_err(|error| FileError::Crypto(cocoon::Error::Io(error)))
+    }
+
+    /// Get snapshots left in recovery folder, newest first, except snapshot of this program instance
+    pub fn list_snapshots(folder: &Path, except: &str) -> FileResult<Vec<Snapshot>> {
+        recovery::list(folder, except).map_err(|error| FileError::Crypto(cocoon::Error::Io(error)))
+    }
+
+    /// Open snapshot from recovery folder, with password of original file
+    ///
+    /// Snapshot of file without a password is opened with its key, and `password` should be `None`
+    ///
+    /// Returned file has path of original file, and is unsaved
+    ///
+    /// Returns `FileError::NoPassword` if snapshot needs a password, and none is given
+    pub fn open_snapshot(snapshot: &Snapshot, password: Option<&str>) -> FileResult<Self> {
+        let Some(key) = password.or(snapshot.key.as_deref()) else {
+            return Err(FileError::NoPassword);
+        };
+        let data = fs::read(&snapshot.path)
+            .map_err(|error| FileError::Crypto(cocoon::Error::Io(error)))?;
+        let bytes = crypt::decrypt(&data, key)?;
+        let (header, contents) = parse_bytes(bytes)?;
+
+        // Original file is locked when restored snapshot is saved
+        Ok(Self {
+            contents,
+            header,
+            path: snapshot.file_path.clone(),
+            saved: false,
+            password: password.map(str::to_string),
+            lock: None,
+            read_only: false,
+            disk_state: None,
+        })
+    }
+
+    /// Open file from given path, skipping rows which cannot be parsed
+    ///
+    /// Decrypts with password, or with the old built-in key if `None`
+    ///
+    /// Returns file with valid rows, and the rows which were skipped
+    ///
+    /// Returned file is unsaved if any rows were skipped, so they are not lost by accident
+    pub fn open_path_and_recover(
+        path: impl Into<String>,
+        password: Option<&str>,
+        mode: LockMode,
+    ) -> FileResult<(Self, Vec<BadRow>)> {
+        let path = path.into();
+        let lock = take_lock(&path, mode)?;
+
+        // Decrypt data (bytes) from file
+        let data = read_path(&path)?;
+        let bytes = match password {
+            Some(password) => crypt::decrypt(&data, password)?,
+            None => crypt::decrypt_legacy(&data)?,
+        };
+
+        // Parse contents, keeping bad rows
+        let (header, contents, bad_rows) =
+            Csv::decode_with_recovery(&bytes_to_string(bytes)?).map_err(parse_error)?;
+
+        let file = Self {
+            contents,
+            header,
+            disk_state: Some(DiskState::new(&path, &data)),
+            path: Some(path),
+            saved: bad_rows.is_empty(),
+            password: password.map(str::to_string),
+            lock,
+            read_only: mode == LockMode::ReadOnly,
+        };
+        Ok((file, bad_rows))
+    }
+}
+
+/// Lock file at path, depending on lock mode
+///
+/// File is opened without lock if lock file cannot be created, such as in a read-only folder
+fn take_lock(path: &str, mode: LockMode) -> FileResult<Option<Arc<Lock>>> {
+    if mode != LockMode::Lock {
+        return Ok(None);
+    }
+
+    match Lock::acquire(path) {
+        Ok(lock) => Ok(Some(Arc::new(lock))),
+        Err(LockError::Locked(owner)) => Err(FileError::Locked(owner)),
+        Err(LockError::Io(error)) => {
+            eprintln!("[ERROR] Failed to lock file: {error}");
+            Ok(None)
+        }
+    }
+}
+
+/// Read encrypted data from file
+fn read_path(path: &str) -> FileResult<Vec<u8>> {
+    fs::read(path).map_err(|error| {
+        // Return an IO error if failed
+        FileError::Crypto(cocoon::Error::Io(error))
+    })
+}
+
+/// Write data to file, without leaving a partly written file if writing fails
+///
+/// Writes to a temporary file next to the target, then renames it over the target
+fn write_atomic(path: impl AsRef<Path>, data: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+
+    let result = (|| {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(data)?;
+        // Make sure data is on disk before replacing target
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+
+    // Do not leave temporary file behind
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+
+    // Make sure rename is on disk
+    // Directories cannot be opened on some platforms, so this may fail
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        if let Ok(dir) = fs::File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse decrypted bytes as file contents
+///
+/// Older format versions are migrated to current version
+fn parse_bytes(bytes: Vec<u8>) -> FileResult<(Header, Csv)> {
+    // Parse contents from CSV format
+    Csv::decode_with_header(&bytes_to_string(bytes)?).map_err(parse_error)
+}
+
+/// Convert decrypted bytes to string
+fn bytes_to_string(bytes: Vec<u8>) -> FileResult<String> {
+    // This may fail, if bytes do not form a valid utf8 string
+    String::from_utf8(bytes).map_err(|error| {
+        // Bytes-to-string conversion failed
+        // Return IO error of 'Invalid Data'
+        FileError::Crypto(cocoon::Error::from(io::Error::new(
+            io::ErrorKind::InvalidData,
+            error,
+        )))
+    })
+}
+
+/// Convert error from parsing file contents
+fn parse_error(error: csv::LineError) -> FileError {
+    match error.error {
+        csv::ParseError::UnsupportedVersion(version) => FileError::UnsupportedVersion(version),
+        _ => FileError::CsvParse(error),
+    }
 }
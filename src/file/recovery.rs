@@ -0,0 +1,170 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+
+use super::lock::{self, Lock, LockError};
+
+/// Extension of snapshot files
+const EXTENSION: &str = "mgx";
+
+/// Extension of files with path of original file of snapshot
+const PATH_EXTENSION: &str = "path";
+
+/// Extension of files with key of snapshot, for files without a password
+const KEY_EXTENSION: &str = "key";
+
+/// Autosaved copy of unsaved file, left behind if program did not close properly
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// Path to encrypted snapshot file
+    pub path: PathBuf,
+    /// Path of file which snapshot was taken of
+    ///
+    /// `None` if file was never saved
+    pub file_path: Option<String>,
+    /// Key which snapshot was encrypted with, as file had no password
+    ///
+    /// `None` if snapshot was encrypted with password of file
+    pub key: Option<String>,
+    /// Time when snapshot was taken
+    pub time: DateTime<Utc>,
+}
+
+impl Snapshot {
+    /// Delete snapshot, after it was restored or discarded
+    pub fn remove(&self) -> io::Result<()> {
+        remove(&self.path)
+    }
+}
+
+/// Lock of snapshot of this program instance, while program is running
+///
+/// Snapshots which are locked are not listed as leftovers by other instances (See `list`)
+#[derive(Debug)]
+pub struct SnapshotLock(Lock);
+
+impl SnapshotLock {
+    /// Lock snapshot with name in folder, whether it is saved yet or not
+    pub fn acquire(folder: &Path, name: &str) -> io::Result<Self> {
+        fs::create_dir_all(folder)?;
+        match Lock::acquire(&snapshot_path(folder, name).to_string_lossy()) {
+            Ok(lock) => Ok(Self(lock)),
+            Err(LockError::Locked(_)) => Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "Snapshot is locked by another program instance",
+            )),
+            Err(LockError::Io(error)) => Err(error),
+        }
+    }
+
+    /// Update time of lock, so it does not become stale
+    pub fn refresh(&self) -> io::Result<()> {
+        self.0.refresh()
+    }
+}
+
+/// Get default folder for snapshots, in user data folder
+pub fn default_folder() -> Option<PathBuf> {
+    dirs_next::data_dir().map(|dir| dir.join("magictax").join("recovery"))
+}
+
+/// Get unique name for snapshots of this program instance
+///
+/// Names of different instances do not overlap, even if a process ID is reused
+pub fn session_name() -> String {
+    format!("{}-{}", Utc::now().timestamp_millis(), process::id())
+}
+
+/// Get new random key for snapshots of files without a password, for this program instance
+pub fn session_key() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Get path of encrypted snapshot file with name, in folder
+pub fn snapshot_path(folder: &Path, name: &str) -> PathBuf {
+    folder.join(format!("{name}.{EXTENSION}"))
+}
+
+/// Save path of original file, next to snapshot
+pub fn write_file_path(snapshot_path: &Path, file_path: Option<&str>) -> io::Result<()> {
+    fs::write(
+        snapshot_path.with_extension(PATH_EXTENSION),
+        file_path.unwrap_or_default(),
+    )
+}
+
+/// Save key of snapshot next to it, or remove old key if snapshot is encrypted with a password
+pub fn write_key(snapshot_path: &Path, key: Option<&str>) -> io::Result<()> {
+    let path = snapshot_path.with_extension(KEY_EXTENSION);
+    match key {
+        Some(key) => fs::write(path, key),
+        None => remove_if_exists(&path),
+    }
+}
+
+/// Get every snapshot in folder, newest first
+///
+/// Snapshot with name `except` (of current instance), and snapshots of other running instances, are not included
+pub fn list(folder: &Path, except: &str) -> io::Result<Vec<Snapshot>> {
+    let entries = match fs::read_dir(folder) {
+        Ok(entries) => entries,
+        // No snapshots were taken yet
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+
+    let mut snapshots = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+
+        if path.extension().and_then(|ext| ext.to_str()) != Some(EXTENSION)
+            || path.file_stem().and_then(|name| name.to_str()) == Some(except)
+        {
+            continue;
+        }
+        // Snapshot of another instance which is still running
+        if lock::owner(&path.to_string_lossy())?.is_some() {
+            continue;
+        }
+
+        // Original file may be unknown, if program crashed while saving snapshot
+        let file_path = fs::read_to_string(path.with_extension(PATH_EXTENSION))
+            .ok()
+            .filter(|file_path| !file_path.is_empty());
+        let key = fs::read_to_string(path.with_extension(KEY_EXTENSION)).ok();
+
+        let time = fs::metadata(&path)?.modified()?.into();
+
+        snapshots.push(Snapshot {
+            path,
+            file_path,
+            key,
+            time,
+        });
+    }
+
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.time));
+    Ok(snapshots)
+}
+
+/// Delete snapshot file, path of original file, and key, if they exist
+pub fn remove(snapshot_path: &Path) -> io::Result<()> {
+    remove_if_exists(snapshot_path)?;
+    remove_if_exists(&snapshot_path.with_extension(PATH_EXTENSION))?;
+    remove_if_exists(&snapshot_path.with_extension(KEY_EXTENSION))
+}
+
+/// Remove file, if it exists
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
@@ -1,4 +1,7 @@
+use std::path::Path;
+
 use super::*;
+use crate::csv::CsvRow;
 
 #[test]
 fn check_save_state() {
@@ -16,7 +19,12 @@ fn check_save_state() {
     let file = File {
         path: None,
         contents: Csv::default(),
+        header: Header::default(),
         saved: false,
+        password: None,
+        lock: None,
+        read_only: false,
+        disk_state: None,
     };
     assert_eq!(file.is_registered_and_saved(), false);
     assert_eq!(file.is_changed(), false);
@@ -24,7 +32,12 @@ fn check_save_state() {
     let file = File {
         path: None,
         contents: Csv::default(),
+        header: Header::default(),
         saved: true,
+        password: None,
+        lock: None,
+        read_only: false,
+        disk_state: None,
     };
     assert_eq!(file.is_registered_and_saved(), false);
     assert_eq!(file.is_changed(), false);
@@ -33,7 +46,12 @@ fn check_save_state() {
     let file = File {
         path: None,
         contents: Csv::decode("foo,123\nbar,456").expect("Should not fail"),
+        header: Header::default(),
         saved: false,
+        password: None,
+        lock: None,
+        read_only: false,
+        disk_state: None,
     };
     assert_eq!(file.is_registered_and_saved(), false);
     assert_eq!(file.is_changed(), true);
@@ -41,7 +59,12 @@ fn check_save_state() {
     let file = File {
         path: None,
         contents: Csv::decode("foo,123\nbar,456").expect("Should not fail"),
+        header: Header::default(),
         saved: true,
+        password: None,
+        lock: None,
+        read_only: false,
+        disk_state: None,
     };
     assert_eq!(file.is_registered_and_saved(), false);
     assert_eq!(file.is_changed(), true);
@@ -55,7 +78,12 @@ fn check_save_state() {
     let file = File {
         path: Some(String::from("some/path")),
         contents: Csv::default(),
+        header: Header::default(),
         saved: false,
+        password: None,
+        lock: None,
+        read_only: false,
+        disk_state: None,
     };
     assert_eq!(file.is_registered_and_saved(), false);
     assert_eq!(file.is_changed(), true);
@@ -63,7 +91,12 @@ fn check_save_state() {
     let file = File {
         path: Some(String::from("some/path")),
         contents: Csv::decode("foo,123\nbar,456").expect("Should not fail"),
+        header: Header::default(),
         saved: false,
+        password: None,
+        lock: None,
+        read_only: false,
+        disk_state: None,
     };
     assert_eq!(file.is_registered_and_saved(), false);
     assert_eq!(file.is_changed(), true);
@@ -72,7 +105,12 @@ fn check_save_state() {
     let file = File {
         path: Some(String::from("some/path")),
         contents: Csv::default(),
+        header: Header::default(),
         saved: true,
+        password: None,
+        lock: None,
+        read_only: false,
+        disk_state: None,
     };
     assert_eq!(file.is_registered_and_saved(), true);
     assert_eq!(file.is_changed(), false);
@@ -80,8 +118,509 @@ fn check_save_state() {
     let file = File {
         path: Some(String::from("some/path")),
         contents: Csv::decode("foo,123\nbar,456").expect("Should not fail"),
+        header: Header::default(),
         saved: true,
+        password: None,
+        lock: None,
+        read_only: false,
+        disk_state: None,
     };
     assert_eq!(file.is_registered_and_saved(), true);
     assert_eq!(file.is_changed(), false);
 }
+
+/// Get unique path in temporary directory
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("magictax-test-{}-{name}.mgx", std::process::id()))
+        .display()
+        .to_string()
+}
+
+#[test]
+fn encrypted_round_trip_works() {
+    let path = temp_path("round-trip");
+
+    let mut file = File::default();
+    file.contents = Csv::decode("foo,123\nbar,456").expect("Should not fail");
+    file.save_to_path_encrypted(&path, "correct horse")
+        .expect("Should save");
+
+    assert_eq!(
+        File::path_uses_legacy_key(&path).expect("Should read"),
+        false
+    );
+
+    let opened = File::open_path_and_decrypt(&path, "correct horse").expect("Should open");
+    assert_eq!(opened.contents, file.contents);
+    assert_eq!(opened.password(), Some(&"correct horse".to_string()));
+    assert_eq!(opened.is_registered_and_saved(), true);
+    // Release lock
+    drop(opened);
+
+    let error = File::open_path_and_decrypt(&path, "wrong password")
+        .err()
+        .expect("Should fail with wrong password");
+    assert!(matches!(
+        error,
+        FileError::Crypto(cocoon::Error::Cryptography)
+    ));
+
+    fs::remove_file(path).expect("Should remove test file");
+}
+
+#[test]
+fn legacy_key_works() {
+    let path = temp_path("legacy");
+
+    // Encrypt the same way as before passwords were added
+    let cocoon = cocoon::Cocoon::new(b"super-secure-encryption-key");
+    let mut output = fs::File::create(&path).expect("Should create file");
+    cocoon
+        .dump(b"foo,123\n".to_vec(), &mut output)
+        .expect("Should encrypt");
+    drop(output);
+
+    assert_eq!(
+        File::path_uses_legacy_key(&path).expect("Should read"),
+        true
+    );
+
+    let error = File::open_path_and_decrypt(&path, "any password")
+        .err()
+        .expect("Should fail without legacy path");
+    assert!(matches!(error, FileError::LegacyKey));
+
+    let opened = File::open_path_and_decrypt_legacy(&path).expect("Should open");
+    assert_eq!(
+        opened.contents,
+        Csv::decode("foo,123").expect("Should not fail")
+    );
+    assert_eq!(opened.password(), None);
+
+    fs::remove_file(path).expect("Should remove test file");
+}
+
+#[test]
+fn change_password_works() {
+    let path = temp_path("change-password");
+
+    let mut file = File {
+        contents: Csv::decode("foo,123").expect("Should not fail"),
+        path: Some(path.clone()),
+        ..File::default()
+    };
+    file.save_to_path_encrypted(&path, "old")
+        .expect("Should save");
+
+    // Wrong old password should not change file
+    file.change_password(Some("wrong"), "new", 2)
+        .expect_err("Should fail with wrong password");
+    File::open_path_and_decrypt(&path, "old").expect("Should still open with old password");
+
+    file.change_password(Some("old"), "new", 2)
+        .expect("Should change password");
+    assert_eq!(file.password(), Some(&"new".to_string()));
+
+    assert!(
+        File::open_path_and_decrypt(&path, "old").is_err(),
+        "Should not open with old password"
+    );
+    let opened = File::open_path_and_decrypt(&path, "new").expect("Should open with new password");
+    assert_eq!(opened.contents, file.contents);
+    assert!(!Path::new(&format!("{path}.tmp")).exists());
+    // Previous version is backed up
+    assert_eq!(File::list_backups(&path).expect("Should list").len(), 1);
+
+    // File open in another instance should not change
+    let mut read_only = File::open_path_with_mode(&path, Some("new"), LockMode::ReadOnly)
+        .expect("Should open read-only");
+    let error = read_only
+        .change_password(Some("new"), "other", 2)
+        .expect_err("Should fail for read-only file");
+    assert!(matches!(error, FileError::ReadOnly));
+    let mut ignored = File::open_path_with_mode(&path, Some("new"), LockMode::Ignore)
+        .expect("Should open without lock");
+    let error = ignored
+        .change_password(Some("new"), "other", 2)
+        .expect_err("Should fail while locked");
+    assert!(matches!(error, FileError::Locked(_)));
+    drop(opened);
+
+    // File changed by another program should not change
+    ignored
+        .save_to_path_encrypted(&path, "new")
+        .expect("Should save");
+    let error = file
+        .change_password(Some("new"), "other", 2)
+        .expect_err("Should fail if changed on disk");
+    assert!(matches!(error, FileError::ChangedOnDisk));
+
+    // Unregistered file has nothing to change
+    let error = File::default()
+        .change_password(None, "new", 2)
+        .expect_err("Should fail for unregistered file");
+    assert!(matches!(error, FileError::Unregistered));
+
+    fs::remove_file(&path).expect("Should remove test file");
+    fs::remove_dir_all(format!("{path}.backups")).expect("Should remove backups");
+}
+
+#[test]
+fn header_is_saved() {
+    let path = temp_path("header");
+
+    let mut file = File {
+        contents: Csv::decode("foo,123").expect("Should not fail"),
+        ..File::default()
+    };
+    file.save_to_path_encrypted(&path, "password")
+        .expect("Should save");
+
+    let opened = File::open_path_and_decrypt(&path, "password").expect("Should open");
+    assert_eq!(opened.header(), file.header());
+    assert_eq!(opened.header().version, Header::default().version);
+    assert!(opened.header().created.is_some());
+    // Release lock
+    drop(opened);
+
+    // Newer format version cannot be opened
+    let data =
+        crypt::encrypt(b"#!magictax 99\nfoo,1\n".to_vec(), "password").expect("Should encrypt");
+    fs::write(&path, data).expect("Should write");
+    let Err(error) = File::open_path_and_decrypt(&path, "password") else {
+        panic!("Should fail with newer version");
+    };
+    assert!(matches!(error, FileError::UnsupportedVersion(99)));
+
+    // Header is not changed if file could not be written
+    let header = file.header().clone();
+    file.save_to_path_encrypted(&format!("{path}.missing/file.mgx"), "password")
+        .expect_err("Should fail to write");
+    assert_eq!(file.header(), &header);
+
+    fs::remove_file(path).expect("Should remove test file");
+}
+
+#[test]
+fn bad_rows_can_be_recovered() {
+    let path = temp_path("recover");
+
+    let data =
+        crypt::encrypt(b"foo,1\nbar,ten\nbaz,3\n".to_vec(), "password").expect("Should encrypt");
+    fs::write(&path, data).expect("Should write");
+
+    let Err(error) = File::open_path_and_decrypt(&path, "password") else {
+        panic!("Should fail with bad row");
+    };
+    let FileError::CsvParse(error) = error else {
+        panic!("Should be a parse error");
+    };
+    assert_eq!(error.line, 2);
+
+    let (file, bad_rows) = File::open_path_and_recover(&path, Some("password"), LockMode::Lock)
+        .expect("Should recover");
+    assert_eq!(file.contents().count(), 2);
+    assert_eq!(bad_rows.len(), 1);
+    assert_eq!(bad_rows[0].text, "bar,ten");
+    // Not saved, so bad rows are not lost without asking
+    assert!(file.is_changed());
+    assert_eq!(file.password(), Some(&"password".to_string()));
+
+    assert!(File::open_path_and_recover(&path, Some("wrong"), LockMode::Ignore).is_err());
+
+    fs::remove_file(path).expect("Should remove test file");
+}
+
+#[test]
+fn failed_save_keeps_original() {
+    let path = temp_path("atomic");
+    let temp = format!("{path}.tmp");
+
+    let mut file = File {
+        contents: Csv::decode("foo,123").expect("Should not fail"),
+        ..File::default()
+    };
+    file.save_to_path_encrypted(&path, "password")
+        .expect("Should save");
+    assert!(!Path::new(&temp).exists());
+    let original = fs::read(&path).expect("Should read");
+
+    // Temporary file cannot be created, as a directory is in the way
+    fs::create_dir(&temp).expect("Should create directory");
+    file.contents_mut().rows.clear();
+    file.saved = false;
+    assert!(file.save_to_path_encrypted(&path, "password").is_err());
+    assert!(!file.saved);
+    fs::remove_dir(&temp).expect("Should remove directory");
+
+    // Original file is unchanged
+    assert_eq!(fs::read(&path).expect("Should read"), original);
+    let opened = File::open_path_and_decrypt(&path, "password").expect("Should open");
+    assert_eq!(opened.contents().count(), 1);
+
+    // Target cannot be replaced, as it is a directory
+    let dir_path = temp_path("atomic-dir");
+    fs::create_dir(&dir_path).expect("Should create directory");
+    assert!(file.save_to_path_encrypted(&dir_path, "password").is_err());
+    assert!(Path::new(&dir_path).is_dir());
+    assert!(!Path::new(&format!("{dir_path}.tmp")).exists());
+    fs::remove_dir(&dir_path).expect("Should remove directory");
+
+    fs::remove_file(path).expect("Should remove test file");
+}
+
+#[test]
+fn backups_are_rotated() {
+    let path = temp_path("backup");
+
+    // No backup of file which does not exist yet
+    File::backup_path(&path, 2).expect("Should do nothing");
+    assert!(File::list_backups(&path).expect("Should list").is_empty());
+
+    let mut file = File::default();
+    for value in 1..=4 {
+        file.contents_mut().rows = Csv::decode(&format!("foo,{value}"))
+            .expect("Should not fail")
+            .rows;
+        File::backup_path(&path, 2).expect("Should back up");
+        file.save_to_path_encrypted(&path, "password")
+            .expect("Should save");
+        File::rotate_backups(&path, 2).expect("Should rotate");
+    }
+
+    // Newest 2 previous versions are kept
+    let backups = File::list_backups(&path).expect("Should list");
+    assert_eq!(backups.len(), 2);
+    assert!(backups[0].time >= backups[1].time);
+    let totals: Vec<_> = backups
+        .iter()
+        .map(|backup| {
+            File::open_backup(backup, Some("password"))
+                .expect("Should open")
+                .contents()
+                .sum()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(totals, ["3", "2"]);
+
+    let opened = File::open_backup(&backups[0], Some("password")).expect("Should open");
+    assert_eq!(opened.path(), None);
+    assert!(File::open_backup(&backups[0], Some("wrong")).is_err());
+
+    // Count of 0 makes no backups, and keeps existing backups
+    File::backup_path(&path, 0).expect("Should do nothing");
+    File::rotate_backups(&path, 0).expect("Should do nothing");
+    assert_eq!(File::list_backups(&path).expect("Should list").len(), 2);
+
+    // Backups made at the same time do not replace each other
+    for _ in 0..3 {
+        File::backup_path(&path, 10).expect("Should back up");
+    }
+    assert_eq!(File::list_backups(&path).expect("Should list").len(), 5);
+
+    fs::remove_dir_all(format!("{path}.backups")).expect("Should remove backups");
+    fs::remove_file(path).expect("Should remove test file");
+}
+
+#[test]
+fn snapshots_can_be_restored() {
+    let folder =
+        std::env::temp_dir().join(format!("magictax-test-{}-recovery", std::process::id()));
+    let path = temp_path("snapshot");
+
+    let mut file = File {
+        contents: Csv::decode("foo,1").expect("Should not fail"),
+        path: Some(path.clone()),
+        ..File::default()
+    };
+
+    // File without password is encrypted with key, kept next to snapshot
+    let data = file.encrypt_snapshot("key").expect("Should encrypt");
+    file.write_snapshot(&folder, "crashed", &data, "key")
+        .expect("Should save");
+    let snapshots = File::list_snapshots(&folder, "").expect("Should list");
+    assert_eq!(snapshots[0].key.as_deref(), Some("key"));
+    let restored = File::open_snapshot(&snapshots[0], None).expect("Should open");
+    assert_eq!(restored.contents(), file.contents());
+    assert_eq!(restored.password(), None);
+
+    file.set_password("password");
+    let data = file.encrypt_snapshot("key").expect("Should encrypt");
+    for name in ["crashed", "current", "running"] {
+        file.write_snapshot(&folder, name, &data, "key")
+            .expect("Should save");
+    }
+
+    // Snapshots of this instance, and of other running instances, are not leftovers
+    let lock = SnapshotLock::acquire(&folder, "running").expect("Should lock");
+    let snapshots = File::list_snapshots(&folder, "current").expect("Should list");
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].file_path, Some(path.clone()));
+    // Key is removed once file has a password
+    assert_eq!(snapshots[0].key, None);
+    assert!(matches!(
+        File::open_snapshot(&snapshots[0], None),
+        Err(FileError::NoPassword)
+    ));
+
+    let restored = File::open_snapshot(&snapshots[0], Some("password")).expect("Should open");
+    assert_eq!(restored.contents(), file.contents());
+    assert_eq!(restored.path(), Some(&path));
+    assert!(restored.is_changed());
+    assert!(File::open_snapshot(&snapshots[0], Some("wrong")).is_err());
+
+    snapshots[0].remove().expect("Should remove");
+    File::remove_snapshot(&folder, "current").expect("Should remove");
+    assert!(File::list_snapshots(&folder, "")
+        .expect("Should list")
+        .is_empty());
+
+    // Snapshot is a leftover once instance closes
+    drop(lock);
+    assert_eq!(
+        File::list_snapshots(&folder, "")
+            .expect("Should list")
+            .len(),
+        1
+    );
+    File::remove_snapshot(&folder, "running").expect("Should remove");
+    // Removing again does nothing
+    File::remove_snapshot(&folder, "current").expect("Should do nothing");
+
+    fs::remove_dir_all(folder).expect("Should remove test folder");
+}
+
+#[test]
+fn open_files_are_locked() {
+    let path = temp_path("lock");
+    let lock_path = format!("{path}.lock");
+
+    let mut file = File {
+        contents: Csv::decode("foo,1").expect("Should not fail"),
+        ..File::default()
+    };
+    file.save_to_path_encrypted(&path, "password")
+        .expect("Should save");
+    assert_eq!(File::path_lock_owner(&path).expect("Should read"), None);
+
+    let opened = File::open_path_and_decrypt(&path, "password").expect("Should open");
+    assert!(Path::new(&lock_path).exists());
+    let owner = File::path_lock_owner(&path)
+        .expect("Should read")
+        .expect("Should be locked");
+    assert_eq!(owner.pid, Some(std::process::id()));
+
+    // Cannot open again with lock
+    let Err(error) = File::open_path_and_decrypt(&path, "password") else {
+        panic!("Should fail while locked");
+    };
+    assert!(matches!(error, FileError::Locked(_)));
+
+    // Read-only file cannot be saved over, but can be saved somewhere else
+    let mut read_only = File::open_path_with_mode(&path, Some("password"), LockMode::ReadOnly)
+        .expect("Should open read-only");
+    assert!(read_only.is_read_only());
+    assert!(matches!(
+        read_only.save_to_path_encrypted(&path, "password"),
+        Err(FileError::ReadOnly)
+    ));
+    let copy_path = temp_path("lock-copy");
+    assert!(matches!(
+        read_only.set_path(&path),
+        Err(FileError::Locked(_))
+    ));
+    read_only.set_path(&copy_path).expect("Should lock copy");
+    assert!(!read_only.is_read_only());
+    read_only
+        .save_to_path_encrypted(&copy_path, "password")
+        .expect("Should save copy");
+    assert!(Path::new(&format!("{copy_path}.lock")).exists());
+    drop(read_only);
+    assert!(!Path::new(&format!("{copy_path}.lock")).exists());
+
+    // Ignoring lock allows saving
+    let mut ignored = File::open_path_with_mode(&path, Some("password"), LockMode::Ignore)
+        .expect("Should open anyway");
+    ignored
+        .save_to_path_encrypted(&path, "password")
+        .expect("Should save");
+    drop(ignored);
+
+    // Lock is removed when last clone is dropped
+    let clone = opened.clone();
+    drop(opened);
+    assert!(Path::new(&lock_path).exists());
+    drop(clone);
+    assert!(!Path::new(&lock_path).exists());
+
+    // Stale lock, left by a crash, is replaced
+    fs::write(&lock_path, "1\n2000-01-01T00:00:00+00:00\n").expect("Should write");
+    assert_eq!(File::path_lock_owner(&path).expect("Should read"), None);
+
+    // Lock of process on this computer which is not running is stale, even if it was refreshed
+    if cfg!(target_os = "linux") {
+        let host = fs::read_to_string("/proc/sys/kernel/hostname").expect("Should read");
+        let time = chrono::Utc::now().to_rfc3339();
+        fs::write(&lock_path, format!("{}\n{time}\n{host}", u32::MAX)).expect("Should write");
+        assert_eq!(File::path_lock_owner(&path).expect("Should read"), None);
+
+        // Process ID may be reused by another process, so lock which was not refreshed is stale
+        let pid = std::process::id();
+        fs::write(
+            &lock_path,
+            format!("{pid}\n2000-01-01T00:00:00+00:00\n{host}"),
+        )
+        .expect("Should write");
+        assert_eq!(File::path_lock_owner(&path).expect("Should read"), None);
+    }
+
+    let opened = File::open_path_and_decrypt(&path, "password").expect("Should open");
+    assert!(opened.refresh_lock().is_ok());
+    drop(opened);
+    assert!(!Path::new(&lock_path).exists());
+
+    fs::remove_file(copy_path).expect("Should remove test file");
+    fs::remove_file(path).expect("Should remove test file");
+}
+
+#[test]
+fn external_changes_are_detected() {
+    let path = temp_path("external");
+
+    let mut file = File {
+        contents: Csv::decode("foo,1").expect("Should not fail"),
+        ..File::default()
+    };
+    // Unregistered file has nothing to compare
+    assert!(!file.is_changed_on_disk().expect("Should check"));
+
+    file.set_path(&path).expect("Should lock");
+    file.set_password("password");
+    file.save_to_path_encrypted(&path, "password")
+        .expect("Should save");
+    assert!(!file.is_changed_on_disk().expect("Should check"));
+
+    // Another program saves over file
+    let mut other =
+        File::open_path_with_mode(&path, Some("password"), LockMode::Ignore).expect("Should open");
+    assert!(!other.is_changed_on_disk().expect("Should check"));
+    other.contents_mut().rows.clear();
+    other
+        .save_to_path_encrypted(&path, "password")
+        .expect("Should save");
+    assert!(file.is_changed_on_disk().expect("Should check"));
+    assert!(!other.is_changed_on_disk().expect("Should check"));
+
+    // Reload gets changes of other program
+    file.contents_mut().rows.push(CsvRow::default());
+    file.reload().expect("Should reload");
+    assert_eq!(file.contents().count(), 0);
+    assert!(file.is_registered_and_saved());
+    assert!(!file.is_changed_on_disk().expect("Should check"));
+
+    fs::remove_file(path).expect("Should remove test file");
+    // File which no longer exists is not changed
+    assert!(!file.is_changed_on_disk().expect("Should check"));
+}
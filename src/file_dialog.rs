@@ -14,6 +14,50 @@ pub fn html() -> rfd::FileDialog {
         .set_file_name("magictax-report.html")
 }
 
+/// Create simple file open/save dialog with `rfd`, for pdf files
+pub fn pdf() -> rfd::FileDialog {
+    any_filetype()
+        .add_filter("PDF", &["pdf"])
+        .set_file_name("magictax-report.pdf")
+}
+
+/// Create simple file open dialog with `rfd`, for handlebars templates of html report
+pub fn handlebars() -> rfd::FileDialog {
+    any_filetype().add_filter("Handlebars template", &["hbs", "handlebars", "html"])
+}
+
+/// Create simple file open dialog with `rfd`, for css styles of html report
+pub fn css() -> rfd::FileDialog {
+    any_filetype().add_filter("CSS", &["css"])
+}
+
+/// Create simple file open/save dialog with `rfd`, for plaintext csv files
+pub fn csv() -> rfd::FileDialog {
+    any_filetype()
+        .add_filter("CSV (Unencrypted)", &["csv"])
+        .set_file_name("magictax-export.csv")
+}
+
+/// Create simple file open dialog with `rfd`, for csv files exported by a bank
+pub fn bank_csv() -> rfd::FileDialog {
+    any_filetype().add_filter("Bank statement (CSV)", &["csv", "txt"])
+}
+
+/// Create simple file open dialog with `rfd`, for bank statements (OFX, QFX, camt.053, or MT940)
+pub fn statement() -> rfd::FileDialog {
+    any_filetype().add_filter(
+        "Bank statement (OFX, QFX, camt.053, MT940)",
+        &["ofx", "qfx", "xml", "sta", "940", "mt940", "txt"],
+    )
+}
+
+/// Create simple file open/save dialog with `rfd`, for plaintext QIF files
+pub fn qif() -> rfd::FileDialog {
+    any_filetype()
+        .add_filter("QIF (Unencrypted)", &["qif"])
+        .set_file_name("magictax-export.qif")
+}
+
 /// Get default directory to open file open/save dialogs in
 fn get_start_dir() -> Option<PathBuf> {
     if let Some(dir) = dirs_next::document_dir() {
@@ -0,0 +1,269 @@
+use std::fmt::Display;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::parse_amount;
+use crate::{
+    csv::{
+        cells::{self, Record},
+        CsvRow, LineError,
+    },
+    money::Money,
+};
+
+/// Common formats of dates in bank csv files, as `chrono` format strings
+pub const DATE_FORMATS: &[&str] = &[
+    "%Y-%m-%d", "%d/%m/%Y", "%m/%d/%Y", "%d.%m.%Y", "%d-%m-%Y", "%Y/%m/%d", "%Y%m%d",
+];
+
+/// Which columns of a bank csv file to read each value from, and how to read them
+///
+/// Columns start at 0
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    /// Name to save profile as
+    pub name: String,
+    /// Character between cells
+    pub delimiter: char,
+    /// Amount of rows to skip at start of file, such as headers
+    pub skip_rows: usize,
+    /// Column of date
+    pub date_column: Option<usize>,
+    /// Format of date, as `chrono` format string
+    pub date_format: String,
+    /// Columns of label, joined with spaces (such as payee and description)
+    pub label_columns: Vec<usize>,
+    /// Column of signed amount
+    ///
+    /// If `None`, debit and credit columns are used instead
+    pub amount_column: Option<usize>,
+    /// Column of money going out, for banks which split amount into 2 columns
+    pub debit_column: Option<usize>,
+    /// Column of money coming in, for banks which split amount into 2 columns
+    pub credit_column: Option<usize>,
+    /// Whether `,` is the decimal separator (such as `1.234,56`)
+    pub decimal_comma: bool,
+    /// Whether to flip sign of amount, for banks which show spending as positive
+    pub invert_sign: bool,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            delimiter: ',',
+            skip_rows: 0,
+            date_column: None,
+            date_format: DATE_FORMATS[0].to_string(),
+            label_columns: Vec::new(),
+            amount_column: None,
+            debit_column: None,
+            credit_column: None,
+            decimal_comma: false,
+            invert_sign: false,
+        }
+    }
+}
+
+/// What a column of a bank csv file is used for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Ignore,
+    Date,
+    Label,
+    Amount,
+    Debit,
+    Credit,
+}
+
+impl Role {
+    /// Every role, in order to show
+    pub const ALL: [Self; 6] = [
+        Self::Ignore,
+        Self::Date,
+        Self::Label,
+        Self::Amount,
+        Self::Debit,
+        Self::Credit,
+    ];
+
+    /// Get name to show to user
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ignore => "Ignore",
+            Self::Date => "Date",
+            Self::Label => "Label",
+            Self::Amount => "Amount",
+            Self::Debit => "Debit (out)",
+            Self::Credit => "Credit (in)",
+        }
+    }
+}
+
+/// Error reading row of bank csv file
+#[derive(Debug, PartialEq)]
+pub enum ImportError {
+    /// No column is mapped to amount, or to debit or credit
+    NoAmountColumn,
+    /// Row does not have a mapped column
+    MissingColumn(usize),
+    /// Date does not match format of profile
+    InvalidDate(String),
+    /// Amount is not a valid amount of money
+    InvalidAmount(String),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoAmountColumn => write!(f, "No column is chosen for the amount"),
+            Self::MissingColumn(column) => write!(f, "Missing column {}", column + 1),
+            Self::InvalidDate(date) => write!(f, "Date {date:?} does not match format"),
+            Self::InvalidAmount(amount) => write!(f, "Amount {amount:?} is not a number"),
+        }
+    }
+}
+
+impl Profile {
+    /// Get role of column
+    pub fn role(&self, column: usize) -> Role {
+        if self.date_column == Some(column) {
+            Role::Date
+        } else if self.amount_column == Some(column) {
+            Role::Amount
+        } else if self.debit_column == Some(column) {
+            Role::Debit
+        } else if self.credit_column == Some(column) {
+            Role::Credit
+        } else if self.label_columns.contains(&column) {
+            Role::Label
+        } else {
+            Role::Ignore
+        }
+    }
+
+    /// Set role of column
+    ///
+    /// Any other column with the same role (except label) is ignored
+    pub fn set_role(&mut self, column: usize, role: Role) {
+        // Remove current role of column
+        for option in [
+            &mut self.date_column,
+            &mut self.amount_column,
+            &mut self.debit_column,
+            &mut self.credit_column,
+        ] {
+            if *option == Some(column) {
+                *option = None;
+            }
+        }
+        self.label_columns.retain(|other| *other != column);
+
+        match role {
+            Role::Ignore => (),
+            Role::Date => self.date_column = Some(column),
+            Role::Label => {
+                self.label_columns.push(column);
+                self.label_columns.sort_unstable();
+            }
+            Role::Amount => self.amount_column = Some(column),
+            Role::Debit => self.debit_column = Some(column),
+            Role::Credit => self.credit_column = Some(column),
+        }
+    }
+
+    /// Split file into rows of cells, skipping rows at start
+    pub fn read_records(&self, text: &str) -> Result<Vec<Record>, LineError> {
+        let mut records = cells::read_records_with_delimiter(text, 1, self.delimiter)?;
+        records.drain(..self.skip_rows.min(records.len()));
+        Ok(records)
+    }
+
+    /// Convert row of bank csv file to row, with mapping of profile
+    pub fn convert(&self, cells: &[String]) -> Result<CsvRow, ImportError> {
+        let cell = |column: usize| {
+            cells
+                .get(column)
+                .map(String::as_str)
+                .ok_or(ImportError::MissingColumn(column))
+        };
+
+        let date = match self.date_column {
+            Some(column) => {
+                let date = cell(column)?;
+                Some(
+                    NaiveDate::parse_from_str(date, &self.date_format)
+                        .map_err(|_| ImportError::InvalidDate(date.to_string()))?,
+                )
+            }
+            None => None,
+        };
+
+        let mut label_cells = Vec::new();
+        for column in &self.label_columns {
+            let text = cell(*column)?;
+            if !text.is_empty() {
+                label_cells.push(text);
+            }
+        }
+
+        let amount = |column: usize| {
+            let text = cell(column)?;
+            // Empty debit or credit cell means nothing moved
+            if text.is_empty() && self.amount_column.is_none() {
+                return Ok(Money::ZERO);
+            }
+            parse_amount(text, self.decimal_comma)
+                .ok_or_else(|| ImportError::InvalidAmount(text.to_string()))
+        };
+
+        let mut value = match (self.amount_column, self.debit_column, self.credit_column) {
+            (Some(column), _, _) => amount(column)?,
+            (None, None, None) => return Err(ImportError::NoAmountColumn),
+            // Debit is money going out, whichever sign the bank uses
+            (None, debit, credit) => {
+                let debit = debit.map(amount).transpose()?.unwrap_or_default();
+                let credit = credit.map(amount).transpose()?.unwrap_or_default();
+                credit.abs() - debit.abs()
+            }
+        };
+        if self.invert_sign {
+            value = -value;
+        }
+
+        Ok(CsvRow {
+            label: label_cells.join(" "),
+            value,
+            date,
+            tags: Vec::new(),
+        })
+    }
+
+    /// Guess delimiter and header rows of file, so mapping only needs columns
+    pub fn guess(text: &str) -> Self {
+        let first_line = text.lines().next().unwrap_or_default();
+
+        // Most common delimiter in first line
+        let delimiter = [',', ';', '\t', '|']
+            .into_iter()
+            .max_by_key(|delimiter| first_line.matches(*delimiter).count())
+            .unwrap_or(',');
+
+        // Header row has no numbers
+        let skip_rows = if first_line.contains(|ch: char| ch.is_ascii_digit()) {
+            0
+        } else {
+            1
+        };
+
+        Self {
+            delimiter,
+            skip_rows,
+            ..Self::default()
+        }
+    }
+}
+
+/// Name of file of saved profiles, in user config folder (See `config::path`)
+pub const PROFILES_FILE: &str = "bank-profiles.json";
@@ -0,0 +1,201 @@
+use std::fmt::Display;
+
+use chrono::NaiveDate;
+
+use super::{
+    join_label,
+    markup::{tokens, unescape, Token},
+    parse_amount, Transaction,
+};
+use crate::csv::CsvRow;
+
+/// Error reading camt.053 file
+#[derive(Debug, PartialEq)]
+pub enum CamtError {
+    /// File has no `<BkToCstmrStmt>` element
+    NotCamt,
+    /// Entry does not have a required element
+    MissingElement(&'static str),
+    /// Date is not written as `YYYY-MM-DD`
+    InvalidDate(String),
+    /// Amount is not a valid amount of money
+    InvalidAmount(String),
+    /// Credit or debit indicator is not `CRDT` or `DBIT`
+    InvalidIndicator(String),
+}
+
+impl Display for CamtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotCamt => write!(f, "File is not a camt.053 statement"),
+            Self::MissingElement(name) => write!(f, "Entry has no <{name}>"),
+            Self::InvalidDate(date) => write!(f, "Invalid date {date:?}"),
+            Self::InvalidAmount(amount) => write!(f, "Invalid amount {amount:?}"),
+            Self::InvalidIndicator(indicator) => {
+                write!(f, "Invalid credit or debit indicator {indicator:?}")
+            }
+        }
+    }
+}
+
+/// Values of one `<Ntry>` element
+#[derive(Default)]
+struct Entry {
+    amount: Option<String>,
+    indicator: Option<String>,
+    status: Option<String>,
+    booking_date: Option<String>,
+    value_date: Option<String>,
+    reference: Option<String>,
+    servicer_reference: Option<String>,
+    /// Name of debtor (payer) and creditor (payee)
+    debtor: Option<String>,
+    creditor: Option<String>,
+    /// Unstructured remittance information, such as payment description
+    remittance: Vec<String>,
+    additional_info: Option<String>,
+}
+
+/// Read entries of every statement in camt.053 file
+///
+/// Amounts are negative if debit (`<CdtDbtInd>DBIT`), and positive if credit
+///
+/// Pending entries are skipped, as they may still change
+///
+/// Transaction IDs are the account IBAN and reference of bank (`<AcctSvcrRef>`, or `<NtryRef>`)
+pub fn parse(text: &str) -> Result<Vec<Transaction>, CamtError> {
+    let tokens = tokens(text);
+    if !tokens.contains(&Token::Start("BkToCstmrStmt")) {
+        return Err(CamtError::NotCamt);
+    }
+
+    let mut transactions = Vec::new();
+    let mut path: Vec<&str> = Vec::new();
+    let mut account = None;
+    let mut entry: Option<Entry> = None;
+
+    for token in tokens {
+        match token {
+            Token::Start(name) => {
+                if name == "Ntry" {
+                    entry = Some(Entry::default());
+                }
+                path.push(name);
+            }
+
+            Token::End(name) => {
+                if name == "Ntry" {
+                    if let Some(entry) = entry.take() {
+                        transactions.extend(convert(entry, account.as_deref())?);
+                    }
+                }
+                path.pop();
+            }
+
+            Token::Text(text) => {
+                let text = unescape(text);
+
+                // Elements inside entry
+                let Some(start) = path.iter().rposition(|name| *name == "Ntry") else {
+                    // Account of statement
+                    if path.ends_with(&["Stmt", "Acct", "Id", "IBAN"])
+                        || path.ends_with(&["Stmt", "Acct", "Id", "Othr", "Id"])
+                    {
+                        account = Some(text);
+                    }
+                    continue;
+                };
+                let Some(entry) = &mut entry else {
+                    continue;
+                };
+
+                let inner = &path[start + 1..];
+                match inner {
+                    ["Amt"] => entry.amount = Some(text),
+                    ["CdtDbtInd"] => entry.indicator = Some(text),
+                    // Status is a code element in newer versions
+                    ["Sts"] | ["Sts", "Cd"] => entry.status = Some(text),
+                    ["BookgDt", "Dt" | "DtTm"] => entry.booking_date = Some(text),
+                    ["ValDt", "Dt" | "DtTm"] => entry.value_date = Some(text),
+                    ["NtryRef"] => entry.reference = Some(text),
+                    ["AcctSvcrRef"] => entry.servicer_reference = Some(text),
+                    ["AddtlNtryInf"] => entry.additional_info = Some(text),
+                    [.., "RmtInf", "Ustrd"] => entry.remittance.push(text),
+                    // Name of party is nested deeper in newer versions (`Cdtr/Pty/Nm`)
+                    [.., "Nm"] => {
+                        let Some(party) = inner
+                            .iter()
+                            .position(|name| *name == "RltdPties")
+                            .and_then(|index| inner.get(index + 1))
+                        else {
+                            continue;
+                        };
+                        match *party {
+                            "Dbtr" => entry.debtor.get_or_insert(text),
+                            "Cdtr" => entry.creditor.get_or_insert(text),
+                            _ => continue,
+                        };
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    Ok(transactions)
+}
+
+/// Convert values of `<Ntry>` element to transaction
+///
+/// `None` if entry is pending
+fn convert(entry: Entry, account: Option<&str>) -> Result<Option<Transaction>, CamtError> {
+    if matches!(entry.status.as_deref(), Some("PDNG" | "INFO")) {
+        return Ok(None);
+    }
+
+    // Time is ignored (`2023-01-05T10:30:00`)
+    let date = entry
+        .booking_date
+        .or(entry.value_date)
+        .ok_or(CamtError::MissingElement("BookgDt"))?;
+    let date = date
+        .get(..10)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .ok_or(CamtError::InvalidDate(date))?;
+
+    let amount = entry.amount.ok_or(CamtError::MissingElement("Amt"))?;
+    let value = parse_amount(&amount, false).ok_or(CamtError::InvalidAmount(amount))?;
+    let value = match entry.indicator.as_deref() {
+        Some("CRDT") => value.abs(),
+        Some("DBIT") => -value.abs(),
+        Some(indicator) => return Err(CamtError::InvalidIndicator(indicator.to_string())),
+        None => return Err(CamtError::MissingElement("CdtDbtInd")),
+    };
+
+    // Other party is creditor of debits, and debtor of credits
+    let party = if value.is_negative() {
+        entry.creditor
+    } else {
+        entry.debtor
+    };
+    let memo = if entry.remittance.is_empty() {
+        entry.additional_info
+    } else {
+        Some(entry.remittance.join(" "))
+    };
+
+    let id = entry.servicer_reference.or(entry.reference);
+
+    Ok(Some(Transaction {
+        id: id.map(|id| match account {
+            Some(account) => format!("{account}/{id}"),
+            None => id,
+        }),
+        row: CsvRow {
+            label: join_label(party.unwrap_or_default(), memo),
+            value,
+            date: Some(date),
+            tags: Vec::new(),
+        },
+    }))
+}
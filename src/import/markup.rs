@@ -0,0 +1,115 @@
+/// Part of markup
+#[derive(Debug, PartialEq)]
+pub enum Token<'a> {
+    /// Start of element, such as `<TRNAMT>`
+    Start(&'a str),
+    /// End of element, such as `</STMTTRN>`
+    End(&'a str),
+    /// Text between tags, trimmed
+    Text(&'a str),
+}
+
+/// Split markup into tags and text
+///
+/// Works for both SGML (OFX 1.x, where end tags of values are optional) and XML (OFX 2.x, camt.053)
+///
+/// Attributes and namespace prefixes of tags are removed (`<camt:Amt Ccy="EUR">` is `Amt`)
+///
+/// Processing instructions (`<?OFX ...?>`) and comments are skipped
+pub fn tokens(mut text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+
+    while !text.is_empty() {
+        let Some(start) = text.find('<') else {
+            push_text(&mut tokens, text);
+            break;
+        };
+        push_text(&mut tokens, &text[..start]);
+        text = &text[start..];
+
+        // Comments may contain `>`
+        if let Some(rest) = text.strip_prefix("<!--") {
+            text = rest.split_once("-->").map_or("", |(_, rest)| rest);
+            continue;
+        }
+
+        let Some(end) = text.find('>') else {
+            break;
+        };
+        let tag = text[1..end].trim();
+        text = &text[end + 1..];
+
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        match tag.strip_prefix('/') {
+            Some(tag) => tokens.push(Token::End(name(tag))),
+            // Empty elements (`<NAME/>`) have no text
+            None => match tag.strip_suffix('/') {
+                Some(tag) => {
+                    tokens.push(Token::Start(name(tag)));
+                    tokens.push(Token::End(name(tag)));
+                }
+                None => tokens.push(Token::Start(name(tag))),
+            },
+        }
+    }
+
+    tokens
+}
+
+/// Get name of element from text of tag, without attributes or namespace prefix
+fn name(tag: &str) -> &str {
+    let name = tag.split_whitespace().next().unwrap_or_default();
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Add text token, if text is not only whitespace
+fn push_text<'a>(tokens: &mut Vec<Token<'a>>, text: &'a str) {
+    let text = text.trim();
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+}
+
+/// Replace XML entities (such as `&amp;`) with characters
+pub fn unescape(text: &str) -> String {
+    let mut output = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..].split_once(';').map(|(entity, _)| entity);
+        let ch = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#')?.parse().ok(),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+
+        match (ch, entity) {
+            (Some(ch), Some(entity)) => {
+                output.push(ch);
+                rest = &rest[entity.len() + 2..];
+            }
+            // Not an entity, such as `&` written without escaping in SGML
+            _ => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
@@ -0,0 +1,166 @@
+#[cfg(test)]
+mod tests;
+
+/// Read csv files exported by banks, with a mapping of columns
+pub mod bank;
+/// Read ISO 20022 camt.053 bank statements
+pub mod camt;
+/// Split XML and SGML markup into tags and text
+mod markup;
+/// Read SWIFT MT940 bank statements
+pub mod mt940;
+/// Read OFX and QFX bank statements
+pub mod ofx;
+
+use std::{collections::HashSet, fmt::Display};
+
+use self::{camt::CamtError, mt940::Mt940Error, ofx::OfxError};
+use crate::{csv::CsvRow, money::Money};
+
+/// Transaction read from bank statement
+#[derive(Debug, PartialEq)]
+pub struct Transaction {
+    /// ID given by bank, to skip transaction if it is imported again
+    ///
+    /// `None` if statement has no ID for transaction
+    pub id: Option<String>,
+    pub row: CsvRow,
+}
+
+/// Error reading bank statement
+#[derive(Debug, PartialEq)]
+pub enum StatementError {
+    /// File is not in any supported format
+    UnknownFormat,
+    Ofx(OfxError),
+    Camt(CamtError),
+    Mt940(Mt940Error),
+}
+
+impl Display for StatementError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownFormat => {
+                write!(f, "File is not an OFX, QFX, camt.053, or MT940 statement")
+            }
+            Self::Ofx(error) => write!(f, "{error}"),
+            Self::Camt(error) => write!(f, "{error}"),
+            Self::Mt940(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+/// Read transactions from bank statement, in any supported format
+///
+/// Format is found from contents of file, as banks use different file extensions
+pub fn parse_statement(text: &str) -> Result<Vec<Transaction>, StatementError> {
+    if text.contains("BkToCstmrStmt") {
+        camt::parse(text).map_err(StatementError::Camt)
+    } else if text.contains("<OFX>") {
+        ofx::parse(text).map_err(StatementError::Ofx)
+    } else if text.contains(":61:") {
+        mt940::parse(text).map_err(StatementError::Mt940)
+    } else {
+        Err(StatementError::UnknownFormat)
+    }
+}
+
+/// Remove transactions which were already imported, or appear twice in statement
+///
+/// Returns amount of transactions removed
+pub fn skip_imported(transactions: &mut Vec<Transaction>, imported: &[String]) -> usize {
+    let mut seen: HashSet<String> = imported.iter().cloned().collect();
+    let count = transactions.len();
+
+    transactions.retain(|transaction| match &transaction.id {
+        // `false` if id was already seen
+        Some(id) => seen.insert(id.clone()),
+        None => true,
+    });
+
+    count - transactions.len()
+}
+
+/// Join name (payee) and memo of transaction into one label
+///
+/// Memo is often the same as name, or a longer version of it, so it is only added if it is different
+pub fn join_label(name: String, memo: Option<String>) -> String {
+    match memo {
+        Some(memo) if name.is_empty() => memo,
+        Some(memo) if !name.contains(&memo) && !memo.contains(&name) => format!("{name} {memo}"),
+        Some(memo) if memo.len() > name.len() => memo,
+        _ => name,
+    }
+}
+
+/// Parse amount of money, written the way a bank might write it
+///
+/// Allows currency symbols, thousands separators, spaces,
+///     and negative amounts as `-12.30`, `12.30-`, or `(12.30)`
+///
+/// If `decimal_comma` is `true`, `,` is the decimal separator and `.` separates thousands (`1.234,56`)
+///
+/// `None` if text has no digits, or is not a valid amount
+pub fn parse_amount(text: &str, decimal_comma: bool) -> Option<Money> {
+    let text = text.trim();
+
+    // Negative amounts in parentheses, or with a trailing minus
+    let (negative, text) = if let Some(inner) = text
+        .strip_prefix('(')
+        .and_then(|text| text.strip_suffix(')'))
+    {
+        (true, inner)
+    } else if let Some(inner) = text.strip_suffix('-') {
+        (true, inner)
+    } else {
+        (false, text)
+    };
+
+    let (decimal, thousands) = if decimal_comma {
+        (',', '.')
+    } else {
+        ('.', ',')
+    };
+
+    // Keep only sign, digits, and decimal point
+    let mut number = String::new();
+    for ch in text.chars() {
+        match ch {
+            '0'..='9' | '-' | '+' => number.push(ch),
+            _ if ch == decimal => number.push('.'),
+            // Thousands separators, spaces, and currency symbols
+            _ if ch == thousands || ch.is_whitespace() || ch == '\'' => (),
+            _ if ch.is_alphabetic() || "$€£¥".contains(ch) => (),
+            _ => return None,
+        }
+    }
+
+    let value: Money = number.parse().ok()?;
+    Some(if negative { -value } else { value })
+}
+
+/// Characters of bytes `0x80` to `0x9F` in Windows-1252
+///
+/// Other bytes above `0x7F` are the same as Latin-1 (Unicode code points of the same value)
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Read text of imported file (such as a bank csv file, or a statement), removing byte order mark
+///
+/// Many banks and older programs write Windows-1252 (or Latin-1) instead of UTF-8, so files which are not valid UTF-8 are read as Windows-1252
+pub fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes
+            .iter()
+            .map(|&byte| match byte {
+                0x80..=0x9F => WINDOWS_1252_HIGH[usize::from(byte - 0x80)],
+                _ => char::from(byte),
+            })
+            .collect(),
+    }
+}
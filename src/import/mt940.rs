@@ -0,0 +1,230 @@
+use std::fmt::Display;
+
+use chrono::NaiveDate;
+
+use super::{join_label, parse_amount, Transaction};
+use crate::{csv::CsvRow, money::Money};
+
+/// Error reading MT940 file
+#[derive(Debug, PartialEq)]
+pub enum Mt940Error {
+    /// File has no statement lines (`:61:`)
+    NotMt940,
+    /// Statement line (`:61:`) is not valid
+    InvalidStatementLine(String),
+}
+
+impl Display for Mt940Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotMt940 => write!(f, "File is not an MT940 statement"),
+            Self::InvalidStatementLine(line) => write!(f, "Invalid statement line {line:?}"),
+        }
+    }
+}
+
+/// Field of MT940 message, such as `:61:` (statement line)
+struct Field<'a> {
+    /// Tag of field, without colons, such as `61` or `60F`
+    tag: &'a str,
+    /// Lines of value of field
+    lines: Vec<&'a str>,
+}
+
+/// Split MT940 file into fields
+///
+/// Values of fields may continue on following lines, until the next tag
+fn fields(text: &str) -> Vec<Field<'_>> {
+    let mut fields: Vec<Field> = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim_end();
+
+        // SWIFT header blocks (`{1:...}{4:`), and end of message (`-` or `-}`)
+        // Other lines starting with `-` are continuations of information
+        if line.starts_with('{') || line == "-" || line.starts_with("-}") {
+            continue;
+        }
+
+        let tag = line
+            .strip_prefix(':')
+            .and_then(|line| line.split_once(':'))
+            .filter(|(tag, _)| is_tag(tag));
+
+        match (tag, fields.last_mut()) {
+            (Some((tag, value)), _) => fields.push(Field {
+                tag,
+                lines: vec![value],
+            }),
+            (None, Some(field)) => field.lines.push(line),
+            // Text before first field
+            (None, None) => (),
+        }
+    }
+
+    fields
+}
+
+/// Returns `true` if text is a field tag: 2 digits, and an optional letter
+fn is_tag(tag: &str) -> bool {
+    let mut chars = tag.chars();
+    chars.next().is_some_and(|ch| ch.is_ascii_digit())
+        && chars.next().is_some_and(|ch| ch.is_ascii_digit())
+        && chars.all(|ch| ch.is_ascii_uppercase())
+        && tag.len() <= 3
+}
+
+/// Read transactions of every statement in MT940 file
+///
+/// Each statement line (`:61:`) is read with the information (`:86:`) following it, which may have multiple lines
+///
+/// Transaction IDs are the account (`:25:`) and reference of bank (after `//` in `:61:`)
+pub fn parse(text: &str) -> Result<Vec<Transaction>, Mt940Error> {
+    let fields = fields(text);
+    if !fields.iter().any(|field| field.tag == "61") {
+        return Err(Mt940Error::NotMt940);
+    }
+
+    let mut transactions: Vec<Transaction> = Vec::new();
+    let mut account = None;
+    // Whether last transaction can still be given information by `:86:`
+    let mut open = false;
+
+    for field in fields {
+        match field.tag {
+            "25" => account = field.lines.first().map(|account| account.trim()),
+            "61" => {
+                transactions.push(statement_line(&field.lines, account)?);
+                open = true;
+            }
+            "86" if open => {
+                let transaction = transactions.last_mut().expect("Should be open");
+                let name = std::mem::take(&mut transaction.row.label);
+                let (party, narrative) = narrative(&field.lines);
+
+                // Supplementary details of statement line are only used if no information is given
+                transaction.row.label = match party {
+                    Some(party) => join_label(party, Some(narrative)),
+                    None if narrative.is_empty() => name,
+                    None => narrative,
+                };
+                open = false;
+            }
+            _ => open = false,
+        }
+    }
+
+    Ok(transactions)
+}
+
+/// Read statement line (`:61:`)
+///
+/// Format is `YYMMDD[MMDD]{C|D|RC|RD}[funds code]amount{type}reference[//bank reference]`,
+///     with supplementary details on next line
+fn statement_line(lines: &[&str], account: Option<&str>) -> Result<Transaction, Mt940Error> {
+    let line = lines.first().copied().unwrap_or_default();
+    let error = || Mt940Error::InvalidStatementLine(line.to_string());
+
+    // Value date
+    let date = line.get(..6).ok_or_else(error)?;
+    let date = NaiveDate::parse_from_str(&format!("20{date}"), "%Y%m%d").map_err(|_| error())?;
+    let mut rest = &line[6..];
+
+    // Entry date (ignored)
+    if rest
+        .get(..4)
+        .is_some_and(|entry| entry.chars().all(|ch| ch.is_ascii_digit()))
+    {
+        rest = &rest[4..];
+    }
+
+    // Reversal of credit is money going out
+    let negative = if let Some(next) = rest.strip_prefix("RC") {
+        rest = next;
+        true
+    } else if let Some(next) = rest.strip_prefix("RD") {
+        rest = next;
+        false
+    } else if let Some(next) = rest.strip_prefix('C') {
+        rest = next;
+        false
+    } else if let Some(next) = rest.strip_prefix('D') {
+        rest = next;
+        true
+    } else {
+        return Err(error());
+    };
+
+    // Funds code, as last letter of currency
+    if rest.starts_with(|ch: char| ch.is_ascii_alphabetic()) {
+        rest = &rest[1..];
+    }
+
+    let end = rest
+        .find(|ch: char| !ch.is_ascii_digit() && ch != ',')
+        .unwrap_or(rest.len());
+    let value: Money = parse_amount(&rest[..end], true).ok_or_else(error)?;
+    let value = if negative { -value } else { value };
+
+    // Transaction type (such as `NTRF`), then references
+    let references = rest[end..].get(4..).unwrap_or_default();
+    let (_, bank_reference) = references.split_once("//").unwrap_or((references, ""));
+    let bank_reference = bank_reference.trim();
+
+    Ok(Transaction {
+        id: Some(bank_reference)
+            .filter(|reference| !reference.is_empty())
+            .map(|reference| match account {
+                Some(account) => format!("{account}/{reference}"),
+                None => reference.to_string(),
+            }),
+        row: CsvRow {
+            // Supplementary details, until replaced by information (`:86:`)
+            label: lines[1..].join(" ").trim().to_string(),
+            value,
+            date: Some(date),
+            tags: Vec::new(),
+        },
+    })
+}
+
+/// Read information of transaction (`:86:`), with name of other party if given
+///
+/// Structured information (used by German banks) has subfields such as `?20` (purpose) and `?32` (name)
+///
+/// Unstructured information is joined into one line
+fn narrative(lines: &[&str]) -> (Option<String>, String) {
+    let text = lines.concat();
+
+    // Structured, such as `166?00SEPA-UEBERWEISUNG?20Invoice 12?32Example GmbH`
+    if text.contains("?20") || text.contains("?32") {
+        let mut purpose = Vec::new();
+        let mut name = Vec::new();
+
+        for subfield in text.split('?').skip(1) {
+            let code = subfield.get(..2).unwrap_or_default();
+            let value = subfield.get(2..).unwrap_or_default().trim();
+            if value.is_empty() {
+                continue;
+            }
+            match code {
+                "20" | "21" | "22" | "23" | "24" | "25" | "26" | "27" | "28" | "29" | "60"
+                | "61" | "62" | "63" => purpose.push(value),
+                "32" | "33" => name.push(value),
+                _ => (),
+            }
+        }
+
+        let name = Some(name.join(" ")).filter(|name| !name.is_empty());
+        return (name, purpose.join(" "));
+    }
+
+    (
+        None,
+        lines
+            .iter()
+            .map(|line| line.trim())
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
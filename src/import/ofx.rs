@@ -0,0 +1,127 @@
+use std::fmt::Display;
+
+use chrono::NaiveDate;
+
+use super::{
+    join_label,
+    markup::{tokens, unescape, Token},
+    parse_amount, Transaction,
+};
+use crate::csv::CsvRow;
+
+/// Error reading OFX file
+#[derive(Debug, PartialEq)]
+pub enum OfxError {
+    /// File has no `<OFX>` element
+    NotOfx,
+    /// Transaction does not have a required element
+    MissingElement(&'static str),
+    /// Date is not written as `YYYYMMDD`
+    InvalidDate(String),
+    /// Amount is not a valid amount of money
+    InvalidAmount(String),
+}
+
+impl Display for OfxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotOfx => write!(f, "File is not an OFX statement"),
+            Self::MissingElement(name) => write!(f, "Transaction has no <{name}>"),
+            Self::InvalidDate(date) => write!(f, "Invalid date {date:?}"),
+            Self::InvalidAmount(amount) => write!(f, "Invalid amount {amount:?}"),
+        }
+    }
+}
+
+/// Values of one `<STMTTRN>` element
+#[derive(Default)]
+struct Record {
+    date: Option<String>,
+    amount: Option<String>,
+    id: Option<String>,
+    name: Option<String>,
+    memo: Option<String>,
+}
+
+/// Read transactions of every statement in OFX or QFX file
+///
+/// Transaction IDs are the account ID and `<FITID>`, so IDs of different accounts do not overlap
+pub fn parse(text: &str) -> Result<Vec<Transaction>, OfxError> {
+    let tokens = tokens(text);
+    if !tokens.contains(&Token::Start("OFX")) {
+        return Err(OfxError::NotOfx);
+    }
+
+    let mut transactions = Vec::new();
+    let mut account = None;
+    let mut in_account = false;
+    let mut record: Option<Record> = None;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            // Account of statement, not another account such as `<BANKACCTTO>` of a transfer
+            Token::Start("BANKACCTFROM" | "CCACCTFROM") => in_account = true,
+            Token::End("BANKACCTFROM" | "CCACCTFROM") => in_account = false,
+
+            Token::Start("STMTTRN") => record = Some(Record::default()),
+            Token::End("STMTTRN") => {
+                if let Some(record) = record.take() {
+                    transactions.push(convert(record, account.as_deref())?);
+                }
+            }
+
+            // Value of element is the text after start tag
+            Token::Start(name) => {
+                let Some(Token::Text(value)) = tokens.get(index + 1) else {
+                    continue;
+                };
+                let value = Some(unescape(value));
+
+                match (*name, &mut record) {
+                    ("ACCTID", _) if in_account => account = value,
+                    ("DTPOSTED", Some(record)) => record.date = value,
+                    ("TRNAMT", Some(record)) => record.amount = value,
+                    ("FITID", Some(record)) => record.id = value,
+                    // Also inside `<PAYEE>`, in place of `<NAME>`
+                    ("NAME", Some(record)) => record.name = value,
+                    ("MEMO", Some(record)) => record.memo = value,
+                    _ => (),
+                }
+            }
+
+            _ => (),
+        }
+    }
+
+    Ok(transactions)
+}
+
+/// Convert values of `<STMTTRN>` element to transaction
+fn convert(record: Record, account: Option<&str>) -> Result<Transaction, OfxError> {
+    let date = record.date.ok_or(OfxError::MissingElement("DTPOSTED"))?;
+    // Time and time zone after date are ignored (`20230105120000.000[-5:EST]`)
+    let date = date
+        .get(..8)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+        .ok_or(OfxError::InvalidDate(date))?;
+
+    let amount = record.amount.ok_or(OfxError::MissingElement("TRNAMT"))?;
+    // Some banks write amounts with a decimal comma
+    let decimal_comma = amount.contains(',') && !amount.contains('.');
+    let value = parse_amount(&amount, decimal_comma).ok_or(OfxError::InvalidAmount(amount))?;
+
+    let label = join_label(record.name.unwrap_or_default(), record.memo);
+
+    Ok(Transaction {
+        id: record.id.map(|id| match account {
+            Some(account) => format!("{account}/{id}"),
+            None => id,
+        }),
+        row: CsvRow {
+            label,
+            value,
+            date: Some(date),
+            tags: Vec::new(),
+        },
+    })
+}
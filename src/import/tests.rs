@@ -0,0 +1,368 @@
+use chrono::NaiveDate;
+
+use super::{bank::*, *};
+use crate::{
+    config,
+    csv::{Csv, CsvRow, Header},
+};
+
+#[test]
+fn amounts_are_parsed() {
+    let cents = |text, decimal_comma| parse_amount(text, decimal_comma).map(|value| value.cents());
+
+    assert_eq!(cents("12.30", false), Some(1230));
+    assert_eq!(cents("-12.3", false), Some(-1230));
+    assert_eq!(cents("$1,234.56", false), Some(123456));
+    assert_eq!(cents("(45.00)", false), Some(-4500));
+    assert_eq!(cents("45.00-", false), Some(-4500));
+    assert_eq!(cents("1.234,56 €", true), Some(123456));
+    assert_eq!(cents("-0,5", true), Some(-50));
+    assert_eq!(cents("", false), None);
+    assert_eq!(cents("abc", false), None);
+    assert_eq!(cents("1/2", false), None);
+}
+
+#[test]
+fn bank_rows_are_mapped() {
+    let file = "\
+Date;Payee;Memo;Debit;Credit
+03.01.2023;Grocer;Weekly shop;45,20;
+05.01.2023;Employer;;;2.500,00
+bad date;Nobody;;1,00;
+";
+
+    let mut profile = Profile::guess(file);
+    assert_eq!(profile.delimiter, ';');
+    assert_eq!(profile.skip_rows, 1);
+
+    profile.date_format = "%d.%m.%Y".to_string();
+    profile.decimal_comma = true;
+    profile.set_role(0, Role::Date);
+    profile.set_role(1, Role::Label);
+    profile.set_role(2, Role::Label);
+    profile.set_role(3, Role::Debit);
+    profile.set_role(4, Role::Credit);
+    assert_eq!(profile.role(2), Role::Label);
+
+    let records = profile.read_records(file).expect("Should be valid");
+    let rows: Vec<_> = records
+        .iter()
+        .map(|record| profile.convert(&record.cells))
+        .collect();
+
+    assert_eq!(
+        rows,
+        vec![
+            Ok(CsvRow {
+                label: "Grocer Weekly shop".to_string(),
+                value: Money::from_cents(-4520),
+                date: NaiveDate::from_ymd_opt(2023, 1, 3),
+                tags: Vec::new(),
+            }),
+            Ok(CsvRow {
+                label: "Employer".to_string(),
+                value: Money::from_cents(250000),
+                date: NaiveDate::from_ymd_opt(2023, 1, 5),
+                tags: Vec::new(),
+            }),
+            Err(ImportError::InvalidDate("bad date".to_string())),
+        ]
+    );
+
+    // Bank shows spending as positive, in 1 column
+    let mut profile = Profile {
+        invert_sign: true,
+        ..Profile::default()
+    };
+    profile.set_role(0, Role::Amount);
+    profile.set_role(1, Role::Label);
+    assert_eq!(
+        profile.convert(&["12.50".to_string(), "Cafe".to_string()]),
+        Ok(CsvRow {
+            label: "Cafe".to_string(),
+            value: Money::from_cents(-1250),
+            date: None,
+            tags: Vec::new(),
+        })
+    );
+    assert_eq!(
+        profile.convert(&["12.50".to_string()]),
+        Err(ImportError::MissingColumn(1))
+    );
+
+    // Role moves to new column
+    profile.set_role(1, Role::Amount);
+    assert_eq!(profile.role(0), Role::Ignore);
+    assert_eq!(
+        Profile::default().convert(&[]),
+        Err(ImportError::NoAmountColumn)
+    );
+}
+
+#[test]
+fn bank_text_is_decoded() {
+    assert_eq!(
+        decode_text(b"\xEF\xBB\xBFDate;Caf\xC3\xA9\n"),
+        "Date;Caf\u{e9}\n"
+    );
+    // Windows-1252, with euro sign
+    assert_eq!(decode_text(b"Caf\xE9;\x80 5\n"), "Caf\u{e9};\u{20ac} 5\n");
+}
+
+#[test]
+fn profiles_are_saved() {
+    let path = std::env::temp_dir()
+        .join(format!("magictax-test-profiles-{}", std::process::id()))
+        .join("bank-profiles.json");
+
+    assert_eq!(
+        config::load::<Vec<Profile>>(&path).expect("Should be empty"),
+        Vec::new()
+    );
+
+    let mut profile = Profile {
+        name: "My bank".to_string(),
+        delimiter: '\t',
+        skip_rows: 2,
+        ..Profile::default()
+    };
+    profile.set_role(3, Role::Amount);
+    config::save(&path, &[profile.clone()]).expect("Should save");
+
+    assert_eq!(
+        config::load::<Vec<Profile>>(&path).expect("Should load"),
+        vec![profile]
+    );
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn ofx_statements_are_read() {
+    let row = |label: &str, cents, (year, month, day)| CsvRow {
+        label: label.to_string(),
+        value: Money::from_cents(cents),
+        date: NaiveDate::from_ymd_opt(year, month, day),
+        tags: Vec::new(),
+    };
+    let id = |id: &str| Some(id.to_string());
+
+    // OFX 1.x, without end tags of values
+    let sgml = ofx::parse(include_str!("samples/statement-sgml.qfx")).expect("Should be valid");
+    assert_eq!(
+        sgml,
+        vec![
+            Transaction {
+                id: id("1234567890/202301030001"),
+                row: row("GROCER & SONS Weekly shop", -4520, (2023, 1, 3)),
+            },
+            Transaction {
+                id: id("1234567890/202301050001"),
+                row: row("EMPLOYER PAYROLL", 250000, (2023, 1, 5)),
+            },
+            Transaction {
+                id: id("1234567890/202301100001"),
+                row: row("STREAMING CO MONTHLY", -999, (2023, 1, 10)),
+            },
+        ]
+    );
+
+    // OFX 2.x, as XML
+    let xml = ofx::parse(include_str!("samples/statement-xml.ofx")).expect("Should be valid");
+    assert_eq!(
+        xml,
+        vec![
+            Transaction {
+                id: id("4111-XXXX/CC-0214-A"),
+                row: row("Café Lumière", -6250, (2023, 2, 14)),
+            },
+            Transaction {
+                id: id("4111-XXXX/CC-0220-B"),
+                row: row("Refund Returned item <shoes>", 1500, (2023, 2, 20)),
+            },
+        ]
+    );
+
+    // Account of transfer is not account of statement
+    let transfer = ofx::parse(
+        "<OFX><BANKACCTFROM><ACCTID>123</BANKACCTFROM>\
+        <STMTTRN><DTPOSTED>20230301<TRNAMT>-10<FITID>A<BANKACCTTO><ACCTID>999</BANKACCTTO></STMTTRN>\
+        <STMTTRN><DTPOSTED>20230302<TRNAMT>-20<FITID>B</STMTTRN></OFX>",
+    )
+    .expect("Should be valid");
+    let ids: Vec<_> = transfer
+        .iter()
+        .map(|transaction| transaction.id.as_deref())
+        .collect();
+    assert_eq!(ids, [Some("123/A"), Some("123/B")]);
+
+    assert_eq!(ofx::parse("Date,Amount\n"), Err(ofx::OfxError::NotOfx));
+    assert_eq!(
+        ofx::parse("<OFX><STMTTRN><DTPOSTED>2023<TRNAMT>1</STMTTRN></OFX>"),
+        Err(ofx::OfxError::InvalidDate("2023".to_string()))
+    );
+}
+
+#[test]
+fn imported_transactions_are_skipped() {
+    let mut transactions =
+        ofx::parse(include_str!("samples/statement-sgml.qfx")).expect("Should be valid");
+    let imported = vec!["1234567890/202301050001".to_string()];
+
+    assert_eq!(skip_imported(&mut transactions, &imported), 1);
+    assert_eq!(transactions.len(), 2);
+
+    // Imported ids are saved with file
+    let header = Header {
+        imported_ids: transactions
+            .iter()
+            .filter_map(|transaction| transaction.id.clone())
+            .collect(),
+        ..Default::default()
+    };
+    let file = Csv::default().encode_with_header(&header);
+    let (decoded, _) = Csv::decode_with_header(&file).expect("Should be valid");
+    assert_eq!(decoded.imported_ids, header.imported_ids);
+
+    let mut transactions =
+        ofx::parse(include_str!("samples/statement-sgml.qfx")).expect("Should be valid");
+    assert_eq!(skip_imported(&mut transactions, &decoded.imported_ids), 2);
+}
+
+#[test]
+fn camt_statements_are_read() {
+    let row = |label: &str, cents, (year, month, day)| CsvRow {
+        label: label.to_string(),
+        value: Money::from_cents(cents),
+        date: NaiveDate::from_ymd_opt(year, month, day),
+        tags: Vec::new(),
+    };
+    let id = |id: &str| Some(id.to_string());
+
+    // Pending entry is skipped
+    let camt = camt::parse(include_str!("samples/camt053-v02.xml")).expect("Should be valid");
+    assert_eq!(
+        camt,
+        vec![
+            Transaction {
+                id: id("DE89370400440532013000/20230302-0001"),
+                row: row(
+                    "Stadtwerke München Strom März Kunde 4711",
+                    -8990,
+                    (2023, 3, 2)
+                ),
+            },
+            Transaction {
+                id: id("DE89370400440532013000/20230315-0002"),
+                row: row("Example GmbH Gehalt 03/2023", 310000, (2023, 3, 15)),
+            },
+            Transaction {
+                id: id("DE89370400440532013000/20230331-FEE"),
+                row: row("Kontoführungsgebühr", -450, (2023, 3, 31)),
+            },
+        ]
+    );
+
+    // Newer version, with namespace prefix
+    let camt = camt::parse(include_str!("samples/camt053-v08.xml")).expect("Should be valid");
+    assert_eq!(
+        camt,
+        vec![Transaction {
+            id: id("CH-ACCOUNT-77/REF-A"),
+            row: row("Immobilien & Co AG", -125000, (2023, 4, 3)),
+        }]
+    );
+
+    assert_eq!(
+        camt::parse(include_str!("samples/statement-xml.ofx")),
+        Err(camt::CamtError::NotCamt)
+    );
+    assert_eq!(
+        camt::parse(
+            "<BkToCstmrStmt><Stmt><Ntry><Amt>1</Amt><CdtDbtInd>X</CdtDbtInd>\
+            <BookgDt><Dt>2023-01-01</Dt></BookgDt></Ntry></Stmt></BkToCstmrStmt>"
+        ),
+        Err(camt::CamtError::InvalidIndicator("X".to_string()))
+    );
+
+    // Format is detected from contents
+    assert_eq!(
+        parse_statement(include_str!("samples/camt053-v08.xml")).map(|rows| rows.len()),
+        Ok(1)
+    );
+    assert_eq!(
+        parse_statement(include_str!("samples/statement-sgml.qfx")).map(|rows| rows.len()),
+        Ok(3)
+    );
+    assert_eq!(
+        parse_statement("Date,Amount\n"),
+        Err(StatementError::UnknownFormat)
+    );
+}
+
+#[test]
+fn mt940_statements_are_read() {
+    let row = |label: &str, cents, (year, month, day)| CsvRow {
+        label: label.to_string(),
+        value: Money::from_cents(cents),
+        date: NaiveDate::from_ymd_opt(year, month, day),
+        tags: Vec::new(),
+    };
+    let id = |id: &str| Some(id.to_string());
+
+    let mt940 = mt940::parse(include_str!("samples/statement.sta")).expect("Should be valid");
+    assert_eq!(
+        mt940,
+        vec![
+            // Structured information, split over lines
+            Transaction {
+                id: id("10020030/1234567/B3A01"),
+                row: row(
+                    "Stadtwerke Musterstadt EREF+INV-2023-01 Strom Januar Kunde 4711",
+                    -4520,
+                    (2023, 1, 3)
+                ),
+            },
+            // Unstructured information, with multiple lines
+            Transaction {
+                id: id("10020030/1234567/B3A02"),
+                row: row(
+                    "Salary January EMPLOYER LTD PAYROLL 2023-01",
+                    250000,
+                    (2023, 1, 5)
+                ),
+            },
+            // Reversal of credit, without information
+            Transaction {
+                id: id("10020030/1234567/B3A03"),
+                row: row("ACCOUNT FEE REVERSAL", -1250, (2023, 1, 10)),
+            },
+            // Empty information, and no reference of bank
+            Transaction {
+                id: None,
+                row: row("Streaming subscription", -999, (2023, 1, 11)),
+            },
+        ]
+    );
+
+    assert_eq!(
+        parse_statement(include_str!("samples/statement.sta")).map(|rows| rows.len()),
+        Ok(4)
+    );
+    assert_eq!(
+        mt940::parse(":20:X\n:61:2301X\n"),
+        Err(mt940::Mt940Error::InvalidStatementLine("2301X".to_string()))
+    );
+    assert_eq!(
+        mt940::parse(":20:X\n:25:123\n"),
+        Err(mt940::Mt940Error::NotMt940)
+    );
+
+    // Continuation line starting with `-` is not end of message
+    let mt940 = mt940::parse(
+        ":20:X\n:25:123\n:61:230103D5,NTRFNONREF\n:86:Refund\n-partial\n:62F:C230131EUR0,\n-\n",
+    )
+    .expect("Should be valid");
+    assert_eq!(mt940.len(), 1);
+    assert_eq!(mt940[0].row.label, "Refund -partial");
+}
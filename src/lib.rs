@@ -7,18 +7,29 @@ mod app;
 mod attempt;
 /// Wrapper for `Sender` and `Receiver` types in `std::sync::mpsc`
 mod channel;
+/// Read and write settings of user, in config folder
+mod config;
 /// Handle CSV format for unencrypted files
 mod csv;
-/// Export (print) file information to html
+/// Export (print) file information to html or pdf
 mod export;
 /// Handle file input/output and save state
 mod file;
 /// Create simple file open/save dialog with `rfd`
 mod file_dialog;
+/// Import rows from bank statements
+mod import;
+/// Exact amounts of money
+mod money;
+/// Read and write QIF files, for other accounting programs
+mod qif;
+/// Change rows automatically with rules, such as to add tags
+mod rules;
 
 pub use crate::{
     app::App,
     csv::{Csv, CsvRow},
+    money::Money,
 };
 use crate::{attempt::Attempt, channel::Channel, file::File};
 
@@ -30,18 +41,7 @@ macro_rules! print_info {
     }
 }
 
-/// Cryption key which every file uses
-///
-/// This is not very secure, but at least the file cannot be opened by any program
-const KEY: &str = "super-secure-encryption-key";
-
 /// Set window scale
 ///
 /// Affects window zoom, position, and size
 pub const GLOBAL_WINDOW_SCALE: f32 = 0.6;
-
-/// Round a float to 2 decimal places and convert to string
-fn round_to_string(number: f32) -> String {
-    let rounded = (number * 100.0).round() / 100.0;
-    rounded.to_string()
-}
@@ -0,0 +1,234 @@
+#[cfg(test)]
+mod tests;
+
+use std::{
+    error::Error,
+    fmt::Display,
+    iter::Sum,
+    ops::{Add, AddAssign, Neg, Sub, SubAssign},
+    str::FromStr,
+};
+
+use eframe::emath;
+
+/// Number of minor units (cents) in one major unit (dollar)
+const CENTS_PER_DOLLAR: i64 = 100;
+
+/// Greatest amount of cents which can be edited (See `Money::MAX`)
+const MAX_CENTS: i64 = 99_999_999_999_999;
+
+/// Amount of money, stored exactly as a whole number of cents
+///
+/// Displays with up to 2 decimal places, without trailing zeros (`12.5`, `-3`, `0.05`)
+///
+/// Arithmetic saturates at bounds, instead of overflowing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(i64);
+
+/// Error parsing money from string
+#[derive(Debug, PartialEq)]
+pub struct ParseMoneyError;
+
+impl Display for ParseMoneyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid amount of money")
+    }
+}
+
+impl Error for ParseMoneyError {}
+
+impl Money {
+    /// Zero dollars
+    pub const ZERO: Self = Self(0);
+
+    /// Least amount which can be edited, just under -1 trillion dollars
+    ///
+    /// Amount in dollars is exact as a float, which is used while editing (See `emath::Numeric`)
+    pub const MIN: Self = Self(-MAX_CENTS);
+
+    /// Greatest amount which can be edited, just under 1 trillion dollars
+    pub const MAX: Self = Self(MAX_CENTS);
+
+    /// Create from whole number of cents
+    pub const fn from_cents(cents: i64) -> Self {
+        Self(cents)
+    }
+
+    /// Get whole number of cents
+    pub const fn cents(&self) -> i64 {
+        self.0
+    }
+
+    /// Returns `true` if amount is zero
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if amount is more than zero (income)
+    pub fn is_positive(&self) -> bool {
+        self.0 > 0
+    }
+
+    /// Returns `true` if amount is less than zero (expense)
+    pub fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+
+    /// Get amount without sign
+    pub fn abs(&self) -> Self {
+        Self(self.0.saturating_abs())
+    }
+}
+
+impl FromStr for Money {
+    type Err = ParseMoneyError;
+
+    /// Parse decimal number, such as `12`, `-0.5`, or `+1234.56`
+    ///
+    /// Digits after the 2nd decimal place are rounded (half away from zero),
+    ///     as older files stored floating point values
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let string = string.trim();
+
+        // Split sign from digits
+        let (negative, digits) = match string.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, string.strip_prefix('+').unwrap_or(string)),
+        };
+
+        // Split whole and fractional parts
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+        // Must have at least one digit, and only digits
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(ParseMoneyError);
+        }
+        if !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|ch| ch.is_ascii_digit())
+        {
+            return Err(ParseMoneyError);
+        }
+
+        // Whole dollars
+        let mut cents: i64 = 0;
+        for digit in whole.bytes() {
+            cents = cents
+                .checked_mul(10)
+                .and_then(|cents| cents.checked_add((digit - b'0') as i64))
+                .ok_or(ParseMoneyError)?;
+        }
+        cents = cents.checked_mul(CENTS_PER_DOLLAR).ok_or(ParseMoneyError)?;
+
+        // First 2 decimal places
+        let mut fraction = fraction.bytes().map(|digit| (digit - b'0') as i64);
+        let tens = fraction.next().unwrap_or(0);
+        let ones = fraction.next().unwrap_or(0);
+        // Round remaining decimal places
+        let round_up = fraction.next().unwrap_or(0) >= 5;
+
+        cents = cents
+            .checked_add(tens * 10 + ones + round_up as i64)
+            .ok_or(ParseMoneyError)?;
+
+        Ok(Self(if negative { -cents } else { cents }))
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.is_negative() { "-" } else { "" };
+
+        // Use unsigned, so `i64::MIN` does not overflow
+        let cents = self.0.unsigned_abs();
+        let dollars = cents / CENTS_PER_DOLLAR as u64;
+        let cents = cents % CENTS_PER_DOLLAR as u64;
+
+        // Remove trailing zeros from decimal places
+        if cents == 0 {
+            write!(f, "{sign}{dollars}")
+        } else if cents.is_multiple_of(10) {
+            write!(f, "{sign}{dollars}.{}", cents / 10)
+        } else {
+            write!(f, "{sign}{dollars}.{cents:02}")
+        }
+    }
+}
+
+impl Add for Money {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Money {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl Neg for Money {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self(self.0.saturating_neg())
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+/// Serialize as decimal string, such as `"-12.5"`, so amounts stay exact
+impl serde::Serialize for Money {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Money {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        string.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Allow editing with `egui::DragValue`
+///
+/// Value is given to egui in dollars, and rounded to nearest cent when changed
+impl emath::Numeric for Money {
+    const INTEGRAL: bool = false;
+    const MIN: Self = Money::MIN;
+    const MAX: Self = Money::MAX;
+
+    fn to_f64(self) -> f64 {
+        self.0 as f64 / CENTS_PER_DOLLAR as f64
+    }
+
+    fn from_f64(num: f64) -> Self {
+        // Float to int conversion saturates at bounds
+        Self((num * CENTS_PER_DOLLAR as f64).round() as i64)
+    }
+}
@@ -0,0 +1,97 @@
+use eframe::emath::Numeric;
+
+use super::*;
+
+#[test]
+fn parse_works() {
+    let cases = [
+        ("0", 0),
+        ("12", 1200),
+        ("123.5", 12350),
+        ("-1.0", -100),
+        ("+0.05", 5),
+        (" 4.20 ", 420),
+        (".5", 50),
+        ("7.", 700),
+        // Older files may have floating point errors
+        ("0.30000001", 30),
+        ("12.345", 1235),
+        ("-12.345", -1235),
+        ("99.995", 10000),
+    ];
+
+    for (string, cents) in cases {
+        assert_eq!(
+            string.parse::<Money>(),
+            Ok(Money::from_cents(cents)),
+            "{string:?} should parse"
+        );
+    }
+}
+
+#[test]
+fn parse_should_fail() {
+    for string in [
+        "",
+        "-",
+        ".",
+        "foo",
+        "1.2.3",
+        "1,5",
+        "--1",
+        "1e5",
+        "99999999999999999999",
+    ] {
+        assert_eq!(
+            string.parse::<Money>(),
+            Err(ParseMoneyError),
+            "{string:?} should not parse"
+        );
+    }
+}
+
+#[test]
+fn display_works() {
+    let cases = [
+        (0, "0"),
+        (1200, "12"),
+        (12350, "123.5"),
+        (12305, "123.05"),
+        (-100, "-1"),
+        (-5, "-0.05"),
+        (i64::MIN, "-92233720368547758.08"),
+    ];
+
+    for (cents, string) in cases {
+        assert_eq!(Money::from_cents(cents).to_string(), string);
+    }
+}
+
+#[test]
+fn round_trip_is_exact() {
+    for cents in (-100_000..100_000).step_by(7) {
+        let money = Money::from_cents(cents);
+        assert_eq!(money.to_string().parse(), Ok(money));
+    }
+}
+
+#[test]
+fn sum_is_exact() {
+    // Adding this many floats would drift away from the exact total
+    let total: Money = std::iter::repeat_n(Money::from_cents(10), 100_000).sum();
+    assert_eq!(total, Money::from_cents(1_000_000));
+    assert_eq!(total.to_string(), "10000");
+}
+
+#[test]
+fn arithmetic_should_not_overflow() {
+    let max = Money::from_cents(i64::MAX);
+    let min = Money::from_cents(i64::MIN);
+    assert_eq!(max + Money::from_cents(1), max);
+    assert_eq!(min - Money::from_cents(1), min);
+    assert_eq!(-min, max);
+    assert_eq!(min.abs(), max);
+
+    // Editing is limited to amounts which are exact as floats
+    assert_eq!(Money::from_f64(Money::MAX.to_f64()), Money::MAX);
+}
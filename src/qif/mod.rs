@@ -0,0 +1,246 @@
+#[cfg(test)]
+mod tests;
+
+use std::{error::Error, fmt::Display, fmt::Write};
+
+use chrono::{Datelike, NaiveDate};
+
+use crate::{
+    csv::{Csv, CsvRow},
+    import::{join_label, parse_amount},
+};
+
+/// Type of account written at start of exported file
+const EXPORT_TYPE: &str = "!Type:Bank";
+
+/// Types of account sections with transactions
+///
+/// Other sections (such as category lists, `!Type:Cat`) are skipped
+const TRANSACTION_TYPES: &[&str] = &["bank", "cash", "ccard", "oth a", "oth l"];
+
+/// Error reading QIF file
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// Date is not a valid `MM/DD/YYYY` or `YYYY-MM-DD` date
+    InvalidDate,
+    /// Amount is not a valid amount of money
+    InvalidAmount,
+    /// Transaction has no `T` (amount) line
+    MissingAmount,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidDate => write!(f, "Date is not valid (Should be MM/DD/YYYY)"),
+            Self::InvalidAmount => write!(f, "Amount is not a number"),
+            Self::MissingAmount => write!(f, "Transaction has no amount"),
+        }
+    }
+}
+
+/// Error reading QIF file, with line of error
+#[derive(Debug, PartialEq)]
+pub struct QifError {
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Text of line which caused error
+    pub text: String,
+    pub error: ParseError,
+}
+
+impl Display for QifError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { line, text, error } = self;
+        write!(f, "Line {line}: {error} ({text:?})")
+    }
+}
+
+impl Error for QifError {}
+
+/// Read rows from QIF file
+///
+/// Reads date (`D`), amount (`T`), payee (`P`), memo (`M`), and category (`L`) of each transaction
+///
+/// Category (and class, after `/`) is read as tags
+pub fn decode(file: &str) -> Result<Csv, QifError> {
+    let mut csv = Csv::default();
+    let mut row = CsvRow::default();
+    let mut has_amount = false;
+    let mut payee = String::new();
+    let mut memo = None;
+    // Files without a type are read as bank transactions
+    let mut in_transactions = true;
+
+    for (index, text) in file.lines().enumerate() {
+        let line = index + 1;
+        let text = text.trim_end();
+
+        let error = |error| QifError {
+            line,
+            text: text.to_string(),
+            error,
+        };
+
+        // Section header, such as `!Type:Bank` or `!Account`
+        if let Some(section) = text.strip_prefix('!') {
+            in_transactions = section
+                .to_lowercase()
+                .strip_prefix("type:")
+                .is_some_and(|kind| TRANSACTION_TYPES.contains(&kind.trim()));
+            continue;
+        }
+        if !in_transactions {
+            continue;
+        }
+
+        let mut chars = text.chars();
+        let Some(code) = chars.next() else {
+            continue;
+        };
+        let value = chars.as_str().trim();
+
+        match code {
+            'D' => {
+                row.date = Some(parse_date(value).ok_or_else(|| error(ParseError::InvalidDate))?)
+            }
+            'T' => {
+                // Some programs write amounts with a decimal comma, like OFX
+                let decimal_comma = value.contains(',') && !value.contains('.');
+                row.value = parse_amount(value, decimal_comma)
+                    .ok_or_else(|| error(ParseError::InvalidAmount))?;
+                has_amount = true;
+            }
+            'P' => payee = value.to_string(),
+            'M' => memo = Some(value.to_string()).filter(|memo| !memo.is_empty()),
+            'L' => row.tags = category_to_tags(value),
+
+            // End of transaction
+            '^' => {
+                if !has_amount {
+                    return Err(error(ParseError::MissingAmount));
+                }
+                row.label = join_label(std::mem::take(&mut payee), memo.take());
+                csv.rows.push(std::mem::take(&mut row));
+                has_amount = false;
+            }
+
+            // Other fields, such as check number (`N`) and splits (`S`, `E`, `$`)
+            _ => (),
+        }
+    }
+
+    // Last transaction may not end with `^`
+    if has_amount {
+        row.label = join_label(payee, memo);
+        csv.rows.push(row);
+    }
+
+    Ok(csv)
+}
+
+/// Write rows to QIF file, as a bank account
+///
+/// First tag is written as category, and any other tags as class
+pub fn encode(csv: &Csv) -> String {
+    let mut file = String::new();
+    writeln!(file, "{EXPORT_TYPE}").expect("Write to string");
+
+    for row in &csv.rows {
+        if let Some(date) = row.date {
+            writeln!(
+                file,
+                "D{:02}/{:02}/{}",
+                date.month(),
+                date.day(),
+                date.year()
+            )
+            .expect("Write to string");
+        }
+
+        // Always 2 decimal places, as some programs expect it
+        let cents = row.value.cents();
+        let sign = if cents < 0 { "-" } else { "" };
+        writeln!(
+            file,
+            "T{sign}{}.{:02}",
+            cents.unsigned_abs() / 100,
+            cents.unsigned_abs() % 100
+        )
+        .expect("Write to string");
+
+        if !row.label.is_empty() {
+            writeln!(file, "P{}", single_line(&row.label)).expect("Write to string");
+        }
+        if let Some((category, classes)) = row.tags.split_first() {
+            let category = single_line(category);
+            if classes.is_empty() {
+                writeln!(file, "L{category}").expect("Write to string");
+            } else {
+                let classes = single_line(&classes.join(":"));
+                writeln!(file, "L{category}/{classes}").expect("Write to string");
+            }
+        }
+
+        writeln!(file, "^").expect("Write to string");
+    }
+
+    file
+}
+
+/// Replace line breaks with spaces, as each field is one line
+///
+/// Labels may have line breaks, which would otherwise be read as other fields
+fn single_line(text: &str) -> String {
+    text.replace("\r\n", " ").replace(['\r', '\n'], " ")
+}
+
+/// Parse date, as written by different programs
+///
+/// Allows `MM/DD/YYYY`, `MM/DD/YY`, `MM/DD'YY` (Quicken), with or without leading zeros, and `YYYY-MM-DD`
+///
+/// 2 digit years are from 1970 to 2069
+fn parse_date(text: &str) -> Option<NaiveDate> {
+    let text: String = text.chars().filter(|ch| !ch.is_whitespace()).collect();
+    let parts: Vec<u32> = text
+        .split(['/', '\'', '-', '.'])
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+
+    let [first, second, third] = parts[..] else {
+        return None;
+    };
+
+    let (year, month, day) = if first > 999 {
+        (first, second, third)
+    } else {
+        let year = match third {
+            0..=69 => third + 2000,
+            70..=99 => third + 1900,
+            _ => third,
+        };
+        (year, first, second)
+    };
+
+    NaiveDate::from_ymd_opt(year as i32, month, day)
+}
+
+/// Convert category (such as `Auto:Fuel/Business`) to tags
+///
+/// Category and class are separate tags, and transfers (`[Savings]`) are tagged with account name
+///
+/// Whitespace is replaced with `-`, as tags cannot contain whitespace
+fn category_to_tags(category: &str) -> Vec<String> {
+    let category = category.trim_start_matches('[').replace(']', "");
+    let (category, class) = category.split_once('/').unwrap_or((&category, ""));
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in std::iter::once(category).chain(class.split(':')) {
+        let tag = tag.split_whitespace().collect::<Vec<_>>().join("-");
+        let tag = tag.replace([',', ';'], "-");
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
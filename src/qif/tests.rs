@@ -0,0 +1,125 @@
+use chrono::NaiveDate;
+
+use super::*;
+use crate::money::Money;
+
+/// Create row with date, value in cents, and tags
+fn row(label: &str, cents: i64, (year, month, day): (i32, u32, u32), tags: &[&str]) -> CsvRow {
+    CsvRow {
+        label: label.to_string(),
+        value: Money::from_cents(cents),
+        date: NaiveDate::from_ymd_opt(year, month, day),
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+    }
+}
+
+#[test]
+fn qif_should_decode() {
+    let file = "\
+!Account
+NChecking
+TBank
+^
+!Type:Cat
+NGroceries
+E
+^
+!Type:Bank
+D1/ 3'23
+T-1,045.20
+PGrocer
+MWeekly shop
+LFood:Groceries
+^
+D01/05/2023
+U2500.00
+T2500.00
+PEmployer
+LSalary/Work Trip:Reimbursed
+^
+D2023-01-10
+T-100
+PTransfer
+L[Savings Account]
+^
+";
+
+    let csv = decode(file).expect("Should be valid");
+    assert_eq!(
+        csv.rows,
+        vec![
+            row(
+                "Grocer Weekly shop",
+                -104520,
+                (2023, 1, 3),
+                &["Food:Groceries"]
+            ),
+            row(
+                "Employer",
+                250000,
+                (2023, 1, 5),
+                &["Salary", "Work-Trip", "Reimbursed"]
+            ),
+            row("Transfer", -10000, (2023, 1, 10), &["Savings-Account"]),
+        ]
+    );
+
+    assert_eq!(
+        decode("!Type:Bank\nD13/45/2023\nT1\n^\n"),
+        Err(QifError {
+            line: 2,
+            text: "D13/45/2023".to_string(),
+            error: ParseError::InvalidDate,
+        })
+    );
+    assert_eq!(
+        decode("!Type:CCard\nD01/01/99\nPNo amount\n^\n").map_err(|e| (e.line, e.error)),
+        Err((4, ParseError::MissingAmount))
+    );
+
+    // Decimal comma
+    assert_eq!(
+        decode("!Type:Bank\nD2023-01-09\nT-12,50\nPCafe\n^\n").map(|csv| csv.rows),
+        Ok(vec![row("Cafe", -1250, (2023, 1, 9), &[])])
+    );
+
+    // Last transaction without `^`
+    assert_eq!(
+        decode("!Type:Bank\nD2023-01-09\nT1\nPFirst\n^\nD2023-01-10\nT-2.50\nPLast\n")
+            .map(|csv| csv.rows),
+        Ok(vec![
+            row("First", 100, (2023, 1, 9), &[]),
+            row("Last", -250, (2023, 1, 10), &[]),
+        ])
+    );
+}
+
+#[test]
+fn qif_should_round_trip() {
+    let csv = Csv {
+        rows: vec![
+            row("Grocer", -4520, (2023, 1, 3), &["Food:Groceries"]),
+            row("Employer", 250000, (2023, 1, 5), &["Salary", "Work"]),
+            row("Cash", 5, (1999, 12, 31), &[]),
+        ],
+    };
+
+    let file = encode(&csv);
+    assert_eq!(
+        file,
+        "!Type:Bank\n\
+        D01/03/2023\nT-45.20\nPGrocer\nLFood:Groceries\n^\n\
+        D01/05/2023\nT2500.00\nPEmployer\nLSalary/Work\n^\n\
+        D12/31/1999\nT0.05\nPCash\n^\n"
+    );
+    assert_eq!(decode(&file), Ok(csv));
+
+    // Line breaks in label are not read as other fields
+    let csv = Csv {
+        rows: vec![row("Note\nT999", -100, (2023, 1, 3), &[])],
+    };
+    assert_eq!(
+        decode(&encode(&csv)).map(|csv| csv.rows),
+        Ok(vec![row("Note T999", -100, (2023, 1, 3), &[])])
+    );
+}
@@ -0,0 +1,212 @@
+#[cfg(test)]
+mod tests;
+
+use std::{error::Error, fmt::Display};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{csv::CsvRow, money::Money};
+
+/// How pattern of rule is matched against label of row
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum MatchKind {
+    /// Label contains pattern, ignoring case
+    #[default]
+    Substring,
+    /// Label matches regular expression, ignoring case
+    Regex,
+}
+
+/// Sign to give value of row
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Sign {
+    Positive,
+    Negative,
+}
+
+/// Rule to change rows which match it, such as to tag rows from a shop
+///
+/// A row matches if its label matches pattern, and its value is inside amount range
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rule {
+    /// Name to show to user
+    pub name: String,
+    /// Whether rule is used
+    pub enabled: bool,
+    /// Text to find in label
+    ///
+    /// Empty pattern matches every label
+    pub pattern: String,
+    pub match_kind: MatchKind,
+    /// Least value of row (inclusive), such as `-100` for expenses up to 100
+    pub min_amount: Option<Money>,
+    /// Greatest value of row (inclusive)
+    pub max_amount: Option<Money>,
+    /// Tags to add to row
+    pub add_tags: Vec<String>,
+    /// New label of row
+    ///
+    /// For regex rules, `$1` or `${name}` is replaced with a group of the match
+    pub rewrite_label: Option<String>,
+    /// Sign to give value, such as positive for refunds written as expenses
+    ///
+    /// Values which already have this sign are not changed, so rules can be applied again
+    pub set_sign: Option<Sign>,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            pattern: String::new(),
+            match_kind: MatchKind::default(),
+            min_amount: None,
+            max_amount: None,
+            add_tags: Vec::new(),
+            rewrite_label: None,
+            set_sign: None,
+        }
+    }
+}
+
+/// Error preparing rules
+#[derive(Debug)]
+pub struct RuleError {
+    /// Name of rule with error
+    pub rule: String,
+    pub error: regex::Error,
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Rule {:?} has an invalid pattern: {}",
+            self.rule, self.error
+        )
+    }
+}
+
+impl Error for RuleError {}
+
+/// Rule with pattern compiled, ready to apply to rows
+struct Compiled<'a> {
+    rule: &'a Rule,
+    regex: Regex,
+}
+
+/// Enabled rules, ready to apply to rows
+///
+/// Rules apply in order, and each rule sees changes made by rules before it
+pub struct RuleSet<'a> {
+    rules: Vec<Compiled<'a>>,
+}
+
+/// Row which would be changed by rules
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    /// Index of row
+    pub index: usize,
+    /// Row after rules are applied
+    pub row: CsvRow,
+}
+
+impl<'a> RuleSet<'a> {
+    /// Compile patterns of enabled rules
+    ///
+    /// Returns error for first rule with an invalid regular expression
+    pub fn new(rules: impl IntoIterator<Item = &'a Rule>) -> Result<Self, RuleError> {
+        let mut compiled = Vec::new();
+
+        for rule in rules.into_iter().filter(|rule| rule.enabled) {
+            // Substrings are matched as escaped regular expressions, so both kinds work the same
+            let pattern = match rule.match_kind {
+                MatchKind::Substring => regex::escape(&rule.pattern),
+                MatchKind::Regex => rule.pattern.clone(),
+            };
+            let regex = RegexBuilder::new(&pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|error| RuleError {
+                    rule: rule.name.clone(),
+                    error,
+                })?;
+
+            compiled.push(Compiled { rule, regex });
+        }
+
+        Ok(Self { rules: compiled })
+    }
+
+    /// Get row with every matching rule applied
+    ///
+    /// `None` if row is not changed
+    pub fn apply(&self, row: &CsvRow) -> Option<CsvRow> {
+        let mut changed = row.clone();
+
+        for Compiled { rule, regex } in &self.rules {
+            if rule.min_amount.is_some_and(|min| changed.value < min)
+                || rule.max_amount.is_some_and(|max| changed.value > max)
+            {
+                continue;
+            }
+            let Some(captures) = regex.captures(&changed.label) else {
+                continue;
+            };
+
+            if let Some(template) = &rule.rewrite_label {
+                let mut label = String::new();
+                match rule.match_kind {
+                    MatchKind::Substring => label.push_str(template),
+                    MatchKind::Regex => captures.expand(template, &mut label),
+                }
+                changed.label = label;
+            }
+
+            for tag in &rule.add_tags {
+                if !changed.has_tag(tag) {
+                    changed.tags.push(tag.clone());
+                }
+            }
+
+            match rule.set_sign {
+                Some(Sign::Positive) => changed.value = changed.value.abs(),
+                Some(Sign::Negative) => changed.value = -changed.value.abs(),
+                None => (),
+            }
+        }
+
+        (changed != *row).then_some(changed)
+    }
+
+    /// Get every row which would be changed by rules, to preview before applying
+    pub fn preview(&self, rows: &[CsvRow]) -> Vec<Change> {
+        rows.iter()
+            .enumerate()
+            .filter_map(|(index, row)| {
+                Some(Change {
+                    index,
+                    row: self.apply(row)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Apply rules to every row, and get amount of rows changed
+    pub fn apply_all(&self, rows: &mut [CsvRow]) -> usize {
+        let mut count = 0;
+        for row in rows {
+            if let Some(changed) = self.apply(row) {
+                *row = changed;
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+/// Name of file of rules of user, in user config folder (See `config::path`)
+pub const FILE: &str = "rules.json";
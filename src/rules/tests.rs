@@ -0,0 +1,163 @@
+use chrono::NaiveDate;
+
+use super::*;
+use crate::{
+    config,
+    csv::{Csv, Header},
+};
+
+/// Create row with value in cents
+fn row(label: &str, cents: i64) -> CsvRow {
+    CsvRow {
+        label: label.to_string(),
+        value: Money::from_cents(cents),
+        date: NaiveDate::from_ymd_opt(2023, 1, 1),
+        tags: Vec::new(),
+    }
+}
+
+#[test]
+fn rules_should_apply() {
+    let rules = vec![
+        Rule {
+            name: "Groceries".to_string(),
+            pattern: "grocer".to_string(),
+            max_amount: Some(Money::ZERO),
+            add_tags: vec!["food".to_string()],
+            ..Rule::default()
+        },
+        Rule {
+            name: "Card payments".to_string(),
+            pattern: r"^CARD \d+ (?P<shop>.+)$".to_string(),
+            match_kind: MatchKind::Regex,
+            rewrite_label: Some("${shop}".to_string()),
+            ..Rule::default()
+        },
+        Rule {
+            name: "Refunds".to_string(),
+            pattern: "refund".to_string(),
+            min_amount: Some(Money::from_cents(-5000)),
+            max_amount: Some(Money::from_cents(-1)),
+            set_sign: Some(Sign::Positive),
+            ..Rule::default()
+        },
+        Rule {
+            name: "Disabled".to_string(),
+            enabled: false,
+            add_tags: vec!["never".to_string()],
+            ..Rule::default()
+        },
+    ];
+    let rules = RuleSet::new(&rules).expect("Should be valid");
+
+    // Later rules see changes of earlier rules
+    assert_eq!(
+        rules.apply(&row("CARD 1234 Grocer & Sons", -4520)),
+        Some(CsvRow {
+            tags: vec!["food".to_string()],
+            ..row("Grocer & Sons", -4520)
+        })
+    );
+    // Amount is outside range
+    assert_eq!(rules.apply(&row("GROCER", 100)), None);
+    assert_eq!(rules.apply(&row("Refund of order", -10000)), None);
+    assert_eq!(
+        rules.apply(&row("Refund of order", -2500)),
+        Some(row("Refund of order", 2500))
+    );
+    // Tags are not repeated
+    let tagged = CsvRow {
+        tags: vec!["food".to_string()],
+        ..row("grocer", -1)
+    };
+    assert_eq!(rules.apply(&tagged), None);
+
+    // Sign is set, not flipped, so rules can be applied again
+    let signs = [Rule {
+        set_sign: Some(Sign::Negative),
+        ..Rule::default()
+    }];
+    let signs = RuleSet::new(&signs).expect("Should be valid");
+    assert_eq!(signs.apply(&row("Rent", 1000)), Some(row("Rent", -1000)));
+    assert_eq!(signs.apply(&row("Rent", -1000)), None);
+
+    // Preview does not change rows
+    let mut rows = vec![
+        row("Salary", 250000),
+        row("grocer", -100),
+        row("Rent", -1000),
+    ];
+    let changes = rules.preview(&rows);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].index, 1);
+    assert_eq!(rows[1].tags, Vec::<String>::new());
+
+    assert_eq!(rules.apply_all(&mut rows), 1);
+    assert_eq!(rows[1], changes[0].row);
+}
+
+#[test]
+fn invalid_rules_should_fail() {
+    let rules = [Rule {
+        name: "Broken".to_string(),
+        pattern: "(unclosed".to_string(),
+        match_kind: MatchKind::Regex,
+        ..Rule::default()
+    }];
+    let error = RuleSet::new(&rules).err().expect("Should fail");
+    assert_eq!(error.rule, "Broken");
+
+    // Same pattern is only text for substring rules
+    let rules = [Rule {
+        match_kind: MatchKind::Substring,
+        rewrite_label: Some("$1".to_string()),
+        ..rules[0].clone()
+    }];
+    let rules = RuleSet::new(&rules).expect("Should be valid");
+    assert_eq!(rules.apply(&row("a (UNCLOSED b", 1)), Some(row("$1", 1)));
+}
+
+#[test]
+fn rules_should_be_saved() {
+    let rule = Rule {
+        name: "Fuel".to_string(),
+        pattern: "shell|bp".to_string(),
+        match_kind: MatchKind::Regex,
+        min_amount: Some(Money::from_cents(-20050)),
+        add_tags: vec!["car".to_string()],
+        ..Rule::default()
+    };
+
+    // Rules of user
+    let path = std::env::temp_dir()
+        .join(format!("magictax-test-rules-{}", std::process::id()))
+        .join("rules.json");
+    assert_eq!(
+        config::load::<Vec<Rule>>(&path).expect("Should be empty"),
+        Vec::new()
+    );
+    config::save(&path, std::slice::from_ref(&rule)).expect("Should save");
+    assert_eq!(
+        config::load::<Vec<Rule>>(&path).expect("Should load"),
+        vec![rule.clone()]
+    );
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    // Rules of file
+    let header = Header {
+        rules: vec![rule],
+        ..Header::default()
+    };
+    let file = Csv::default().encode_with_header(&header);
+    assert!(file.contains("\"min_amount\":\"-200.5\""));
+    let (decoded, _) = Csv::decode_with_header(&file).expect("Should be valid");
+    assert_eq!(decoded.rules, header.rules);
+
+    // Invalid rule is skipped, but kept when saved again
+    let file = file.replace("#rule {", "#rule {broken\n#rule {");
+    let (decoded, csv) = Csv::decode_with_header(&file).expect("Should be valid");
+    assert_eq!(decoded.rules, header.rules);
+    assert_eq!(decoded.invalid_rules, ["{broken"]);
+    assert_eq!(csv.count(), 0);
+    assert!(csv.encode_with_header(&decoded).contains("#rule {broken\n"));
+}
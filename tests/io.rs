@@ -1,5 +1,6 @@
 use magictax::App;
 use magictax::CsvRow;
+use magictax::Money;
 
 #[test]
 #[ignore]
@@ -15,15 +16,20 @@ fn app_io_works() {
     let contents = app.file.contents_mut();
     contents.rows.push(CsvRow {
         label: "foo".to_string(),
-        value: 69.0,
+        value: Money::from_cents(6900),
+        date: None,
+        tags: Vec::new(),
     });
     contents.rows.push(CsvRow {
         label: "bar".to_string(),
-        value: 420.0,
+        value: Money::from_cents(42000),
+        date: None,
+        tags: Vec::new(),
     });
 
     pause("save file");
-    app.file_save_as(&ctx);
+    app.file_save_as();
+    app.submit_password("password".to_string(), "password".to_string(), &ctx);
 
     drop(app);
 
@@ -33,6 +39,7 @@ fn app_io_works() {
 
     pause("open file that you just saved");
     app.file_open();
+    app.submit_password("password".to_string(), String::new(), &ctx);
 
     let contents = app.file.contents();
     let mut rows = contents.rows.clone().into_iter();
@@ -40,14 +47,18 @@ fn app_io_works() {
         rows.next().unwrap(),
         CsvRow {
             label: "foo".to_string(),
-            value: 69.0
+            value: Money::from_cents(6900),
+            date: None,
+            tags: Vec::new(),
         }
     );
     assert_eq!(
         rows.next().unwrap(),
         CsvRow {
             label: "bar".to_string(),
-            value: 420.0
+            value: Money::from_cents(42000),
+            date: None,
+            tags: Vec::new(),
         }
     );
     assert!(rows.next().is_none());